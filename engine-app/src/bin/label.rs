@@ -0,0 +1,119 @@
+use std::io::BufRead;
+
+use clap::{Parser, ValueEnum};
+use random_chess::{AlphaBeta, Engine, FeatureEval, MyBoard, ProportionCount, Weights};
+use rayon::prelude::*;
+
+/// Arguments to the labeller
+#[derive(Parser, Debug)]
+#[command(
+    name = "Random Chess - Label",
+    about = "Reads FENs from stdin, one per line, and writes `fen,score` lines with the engine's \
+             evaluation of each (the expected value for white)."
+)]
+struct Cli {
+    /// The static evaluator the engine should use
+    #[arg(short, long, default_value = "features")]
+    evaluator: ArgEvaluator,
+    /// The maximum lookahead of the engine
+    #[arg(short, long, default_value = "4")]
+    lookahead: u8,
+    /// The timeout for each position in milliseconds
+    #[arg(short, long, default_value = "1000")]
+    timeout: u64,
+    /// The number of entries in the position table of each worker thread
+    #[arg(long, default_value = "262144")]
+    table_size: usize,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug)]
+enum ArgEvaluator {
+    Features,
+    Proportion,
+}
+
+impl Cli {
+    fn engine(&self) -> AlphaBeta {
+        let weights = Weights::standard();
+        match self.evaluator {
+            ArgEvaluator::Features => AlphaBeta::with_capacity(
+                FeatureEval::new(weights, 15.0),
+                self.lookahead,
+                false,
+                false,
+                0,
+                self.timeout,
+                self.table_size,
+            ),
+            ArgEvaluator::Proportion => AlphaBeta::with_capacity(
+                ProportionCount,
+                self.lookahead,
+                false,
+                false,
+                0,
+                self.timeout,
+                self.table_size,
+            ),
+        }
+    }
+}
+
+/// Labels each FEN with its evaluation, spreading the positions across all
+/// cores. Each line of the result is either `fen,score` or an error message
+/// for FENs that couldn't be parsed.
+fn label(cli: &Cli, fens: &[String]) -> Vec<Result<String, String>> {
+    fens.par_iter()
+        .map_init(
+            || cli.engine(),
+            |engine, fen| {
+                let board = MyBoard::from_fen(fen)
+                    .map_err(|e| format!("invalid FEN ({:?}): {}", e, fen))?;
+                let score = engine.evaluate(&board);
+                Ok(format!("{},{}", fen, score))
+            },
+        )
+        .collect()
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let fens: Vec<_> = std::io::stdin()
+        .lock()
+        .lines()
+        .map(|line| line.expect("could not read from stdin").trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    for line in label(&cli, &fens) {
+        match line {
+            Ok(line) => println!("{}", line),
+            Err(message) => eprintln!("{}", message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label() {
+        let cli = Cli::parse_from(["label", "--lookahead", "3", "--timeout", "10000"]);
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
+            "4k3/8/8/8/8/8/8/3QK3 b - - 0 1".to_string(),
+            "not a fen".to_string(),
+        ];
+
+        let lines = label(&cli, &fens);
+        assert_eq!(lines.len(), 3);
+        for (fen, line) in fens.iter().zip(&lines[..2]) {
+            let (labelled_fen, score) = line.as_ref().unwrap().rsplit_once(',').unwrap();
+            assert_eq!(labelled_fen, fen);
+            let score: f32 = score.parse().unwrap();
+            assert!((0.0..=1.0).contains(&score));
+        }
+        assert!(lines[2].is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use std::io::Write;
+use std::{io::Write, path::PathBuf};
 
 use chess::{
     ChessMove,
@@ -8,7 +8,7 @@ use chess::{
     Rank, Square, ALL_PIECES,
 };
 use clap::{Parser, ValueEnum};
-use random_chess::{AlphaBeta, Engine, FeatureEval, Status, Weights};
+use random_chess::{game_phase, AlphaBeta, Engine, FeatureEval, Status, Weights};
 
 const INSTRUCTIONS: &str = "\
     Please enter your move as 5 space-separated integers:\n    \
@@ -21,6 +21,10 @@ const INSTRUCTIONS: &str = "\
         - a promotion to a queen pushing white's e-pawn would be \"4 7 4 8 4\".\n\
 ";
 
+/// The game phase (out of `random_chess::MAX_PHASE`) at or below which the
+/// game is announced as being in the endgame
+const ENDGAME_PHASE: u8 = 8;
+
 /// Arguments to the engine
 #[derive(Parser, Debug)]
 #[command(
@@ -41,6 +45,9 @@ struct Cli {
     /// from being printed
     #[arg(short, long)]
     quiet: bool,
+    /// A JSON file of weights for the evaluator, instead of the standard ones
+    #[arg(short, long)]
+    weights: Option<PathBuf>,
 }
 
 #[derive(ValueEnum, Copy, Clone, Debug)]
@@ -61,17 +68,16 @@ impl ArgColor {
 fn main() {
     let cli = Cli::parse();
 
-    let weights = Weights {
-        pieces: [[1.0, 3.0, 3.0, 5.0, 9.0, 0.0], [
-            -1.0, -3.0, -3.0, -5.0, -9.0, 0.0,
-        ]],
-        king_danger: [-0.5, 0.5],
-        pawn_advancement: [0.5, -0.5],
-        side_to_move: 3.0,
+    let evaluator = match &cli.weights {
+        Some(path) => FeatureEval::from_weights_file(path, 15.0).unwrap_or_else(|e| {
+            eprintln!("Could not load the weights: {}", e);
+            std::process::exit(1);
+        }),
+        None => FeatureEval::new(Weights::standard(), 15.0),
     };
 
     let mut engine = AlphaBeta::new(
-        FeatureEval::new(weights, 15.0),
+        evaluator,
         10,
         true,
         true,
@@ -80,21 +86,56 @@ fn main() {
     );
 
     let mut board = random_chess::MyBoard::initial_board(cli.starting_color.to_color());
+    let mut in_endgame = false;
 
     while board.get_status().is_in_progress() {
-        if board.get_side_to_move() == cli.engine_color.to_color() {
+        if !in_endgame && game_phase(&board) <= ENDGAME_PHASE {
+            in_endgame = true;
+            if !cli.quiet {
+                println!("Entering the endgame");
+            }
+        }
+        if board.is_awaiting_bonus() {
+            let bonus = loop {
+                if !cli.quiet {
+                    print!("\"bonus\" or \"no_bonus\": ");
+                    std::io::stdout().flush().unwrap();
+                }
+                let mut input = String::new();
+                std::io::stdin().read_line(&mut input).unwrap();
+                let bonus = match input.trim() {
+                    "bonus" => true,
+                    "no_bonus" => false,
+                    _ => {
+                        println!("Please enter \"bonus\" or \"no_bonus\".");
+                        continue;
+                    }
+                };
+                break bonus;
+            };
+            board.apply_bonus(bonus);
+        } else if board.get_side_to_move() == cli.engine_color.to_color() {
             let mv = engine.get_move(&board);
             if !cli.quiet {
                 println!(
-                    "Engine played: {} {} {} {} {} [{} -> {}]",
+                    "Engine played: {} {} {} {} {} [{}]",
                     mv.get_source().get_file().to_index(),
                     mv.get_source().get_rank().to_index(),
                     mv.get_dest().get_file().to_index(),
                     mv.get_dest().get_rank().to_index(),
                     mv.get_promotion().unwrap_or(Pawn).to_index(),
-                    mv.get_source(),
-                    mv.get_dest(),
+                    board.move_to_san(mv),
                 );
+                match engine.last_forced_result() {
+                    Some((Status::Draw, plies)) => println!("Forced draw in {} plies", plies),
+                    Some((Status::Win(color), plies)) if color == board.get_side_to_move() => {
+                        println!("King capture in {} plies", plies)
+                    }
+                    Some((Status::Win(_), plies)) => {
+                        println!("Engine's king is captured in {} plies", plies)
+                    }
+                    _ => {}
+                }
             } else {
                 println!(
                     "{} {} {} {} {}",
@@ -141,34 +182,12 @@ fn main() {
                     Some(ALL_PIECES[nums[4]])
                 },
             );
-            if !board.moves_from(mv.get_source()).contains(&mv) {
-                println!("Illegal move.");
+            if let Err(e) = board.try_apply_move(mv) {
+                println!("Illegal move: {}.", e);
                 println!("{}", INSTRUCTIONS);
                 continue;
             }
-            board.apply_move(mv);
         }
-        if !board.get_status().is_in_progress() {
-            break;
-        }
-        let bonus = loop {
-            if !cli.quiet {
-                print!("\"bonus\" or \"no_bonus\": ");
-                std::io::stdout().flush().unwrap();
-            }
-            let mut input = String::new();
-            std::io::stdin().read_line(&mut input).unwrap();
-            let bonus = match input.trim() {
-                "bonus" => true,
-                "no_bonus" => false,
-                _ => {
-                    println!("Please enter \"bonus\" or \"no_bonus\".");
-                    continue;
-                }
-            };
-            break bonus;
-        };
-        board.apply_bonus(bonus);
     }
     match board.get_status() {
         Status::Win(White) => {
@@ -4,10 +4,10 @@ use std::{
 };
 
 use chess::{Color, ALL_COLORS};
-use rand::{thread_rng, Rng};
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
 use random_chess::{
-    bonus_chance, AlphaBeta, Engine, FeatureEval, Features, Logger, MyBoard, ProportionCount,
-    StaticEvaluator, Status, Weights,
+    bonus_chance, compare_evaluators, AlphaBeta, Engine, FeatureEval, Features, GameResult, Logger,
+    MyBoard, ProportionCount, StaticEvaluator, Status, Weights,
 };
 
 const LOG_LEVEL: u8 = 1;
@@ -15,14 +15,7 @@ const LOG_LEVEL: u8 = 1;
 fn main() { _run_concurrent_matches(); }
 
 fn _feature_testing() {
-    let weights1 = Weights {
-        pieces: [[1.0, 3.0, 3.0, 5.0, 9.0, 0.0], [
-            -1.0, -3.0, -3.0, -5.0, -9.0, 0.0,
-        ]],
-        king_danger: [-0.5, 0.5],
-        pawn_advancement: [0.5, -0.5],
-        side_to_move: 3.0,
-    };
+    let weights1 = Weights::standard();
 
     let mut white = AlphaBeta::new(
         ProportionCount::default(),
@@ -115,14 +108,20 @@ fn _feature_testing() {
         "Magnitude self-error features {}",
         magnitude_error2 / boards.len() as f32
     );
+    println!(
+        "Static comparison: {:?}",
+        compare_evaluators(&static_eval, &new_static_eval, &boards)
+    );
 }
 
 fn _run_single_match(
     white_player: &mut dyn Engine, black_player: &mut dyn Engine,
-) -> (Status, Vec<MyBoard>) {
+) -> (GameResult, Vec<MyBoard>) {
     let mut rng = thread_rng();
-    let mut board = MyBoard::initial_board(ALL_COLORS[rng.gen_range(0..=1)]);
+    let starting_color = ALL_COLORS[rng.gen_range(0..=1)];
+    let mut board = MyBoard::initial_board(starting_color);
     let mut boards = vec![board];
+    let mut moves = Vec::new();
 
     loop {
         if !board.get_status().is_in_progress() {
@@ -135,11 +134,26 @@ fn _run_single_match(
             black_player.get_move(&board)
         };
 
+        let is_bonus = rng.gen_bool(bonus_chance().into());
         board.apply_move(mv);
-        board.apply_bonus(rng.gen_bool(bonus_chance().into()));
+        board.apply_bonus(is_bonus);
         boards.push(board);
+        moves.push((mv, is_bonus));
+    }
+    let result = GameResult::from_moves(&moves, starting_color).expect("the game should be valid");
+    (result, boards)
+}
+
+fn _evaluation_trajectory() {
+    let mut white = AlphaBeta::new(ProportionCount, 3, true, false, LOG_LEVEL, 100000);
+    let mut black = AlphaBeta::new(ProportionCount, 3, true, false, LOG_LEVEL, 100000);
+    let (res, boards) = _run_single_match(&mut white, &mut black);
+
+    let mut analyser = AlphaBeta::new(ProportionCount, 4, false, false, LOG_LEVEL, 100000);
+    for (i, score) in analyser.evaluate_game(&boards, 100_000).iter().enumerate() {
+        println!("{}: {}", i, score);
     }
-    (board.get_status(), boards)
+    println!("Result: {}", res.status);
 }
 
 fn _bench_single_match() {
@@ -161,12 +175,83 @@ fn _bench_single_match() {
         100000,
     );
 
+    println!("White: {}", white.describe());
+    println!("Black: {}", black.describe());
     logger.time_start(1, "single match time");
-    let (res, boards) = _run_single_match(&mut white, &mut black);
-    println!("Result: {} in {} moves", res, boards.len());
+    let (res, _) = _run_single_match(&mut white, &mut black);
+    println!(
+        "Result: {} in {} moves ({} bonuses)",
+        res.status, res.num_plies, res.num_bonuses
+    );
     logger.time_end(1, "single match time");
 }
 
+/// Searches a few positions with different numbers of threads and prints the
+/// total nodes per second, to check how the threaded search scales
+fn _thread_scaling() {
+    let mut boards = vec![MyBoard::initial_board(Color::White)];
+    let mut board = boards[0];
+    for _ in 0..6 {
+        let moves: Vec<_> = board.all_moves().collect();
+        board.apply_move(moves[thread_rng().gen_range(0..moves.len())]);
+        board.apply_bonus(false);
+        boards.push(board);
+    }
+
+    for threads in [1, 2, 4, 8] {
+        let mut engine =
+            AlphaBeta::new(ProportionCount, 20, false, false, 0, 5000).with_threads(threads);
+        let mut nodes = 0;
+        let start = std::time::Instant::now();
+        for board in &boards {
+            engine.get_move(board);
+            let stats = engine.last_search_stats();
+            nodes += stats.nodes + stats.helper_nodes;
+        }
+        println!(
+            "{} threads: {:.0} nodes/s",
+            threads,
+            nodes as f64 / start.elapsed().as_secs_f64()
+        );
+    }
+}
+
+/// Searches a position to a fixed depth with and without the feature cache,
+/// printing the cache's hit rate. The position comes from a seeded random
+/// game and the search has no practical time limit, so the hit rate is the
+/// same on every run.
+fn _feature_cache_hit_rate() {
+    const DEPTH: u8 = 6;
+    let mut rng = StdRng::seed_from_u64(798);
+    let mut board = MyBoard::initial_board(Color::White);
+    for _ in 0..6 {
+        let moves: Vec<_> = board.all_moves().collect();
+        board.apply_move(moves[rng.gen_range(0..moves.len())]);
+        board.apply_bonus(false);
+    }
+
+    for capacity in [None, Some(1 << 16)] {
+        let mut eval = FeatureEval::new(Weights::standard(), 15.0);
+        if let Some(capacity) = capacity {
+            eval = eval.with_feature_cache(capacity);
+        }
+        let mut engine = AlphaBeta::new(eval, DEPTH, false, false, 0, 24 * 60 * 60 * 1000);
+        let start = std::time::Instant::now();
+        engine.get_move(&board);
+        assert_eq!(engine.last_search_stats().depth, DEPTH);
+        println!(
+            "cache {:?} at depth {}: {} ms, {} nodes, {}",
+            capacity,
+            DEPTH,
+            start.elapsed().as_millis(),
+            engine.last_search_stats().nodes,
+            engine
+                .evaluator_statistics()
+                .unwrap_or_else(|| "no cache".to_string())
+        );
+    }
+}
+
 fn _run_concurrent_matches() {
     let white_wins = Arc::new(Mutex::new(0));
     let black_wins = Arc::new(Mutex::new(0));
@@ -179,22 +264,8 @@ fn _run_concurrent_matches() {
         let black_wins = Arc::clone(&black_wins);
         let draws = Arc::clone(&draws);
         thread_handles.push(thread::spawn(move || {
-            let weights1 = Weights {
-                pieces: [[1.0, 3.0, 3.0, 5.0, 9.0, 0.0], [
-                    -1.0, -3.0, -3.0, -5.0, -9.0, 0.0,
-                ]],
-                king_danger: [-0.5, 0.5],
-                pawn_advancement: [0.5, -0.5],
-                side_to_move: 3.0,
-            };
-            let weights2 = Weights {
-                pieces: [[1.0, 3.0, 3.0, 5.0, 9.0, 0.0], [
-                    -1.0, -3.0, -3.0, -5.0, -9.0, 0.0,
-                ]],
-                king_danger: [-0.5, 0.5],
-                pawn_advancement: [0.5, -0.5],
-                side_to_move: 3.0,
-            };
+            let weights1 = Weights::standard();
+            let weights2 = Weights::standard();
 
             let mut logger = Logger::new(LOG_LEVEL);
 
@@ -220,7 +291,7 @@ fn _run_concurrent_matches() {
                 let (res, _) = _run_single_match(&mut white, &mut black);
                 // println!("{}: Result: {} in {} moves", t, res, moves);
                 logger.time_end(1, "single match time");
-                match res {
+                match res.status {
                     Status::Win(Color::White) => *white_wins.lock().unwrap() += 1,
                     Status::Win(Color::Black) => *black_wins.lock().unwrap() += 1,
                     Status::Draw => *draws.lock().unwrap() += 1,
@@ -0,0 +1,96 @@
+use chess::{ChessMove, Color};
+
+use crate::{
+    deadline::Deadline,
+    engine::{alphabeta::AlphaBeta, proportion_count::ProportionCount, Engine},
+    game::GameResult,
+    my_board::MyBoard,
+    utils::SeededRng,
+};
+
+/// The position table size for the benchmark engines, which is kept small so
+/// that allocating a fresh table for each game is quick
+const BENCHMARK_TABLE_SIZE: usize = 1 << 16;
+
+/// The time limit for each move in milliseconds, which is a day so that the
+/// searches are only limited by their lookahead
+const BENCHMARK_MOVE_TIME: u64 = 24 * 60 * 60 * 1000;
+
+/// The totals from `benchmark_games`
+#[derive(Clone, Debug)]
+pub struct Benchmark {
+    /// The number of nodes searched by both engines over all the games
+    pub nodes: u64,
+    /// The wall time taken to play all the games, in milliseconds
+    pub millis: u64,
+    pub results: Vec<GameResult>,
+}
+
+/// Plays `num_games` games between two identical `AlphaBeta` engines which
+/// search to `lookahead` with no time limit, rolling the bonuses with a
+/// generator seeded by `seed`. The engines are deterministic without a time
+/// limit, so the same seed always gives the same games and node counts,
+/// which makes this useful for catching performance regressions in realistic
+/// play. Only the wall time varies between runs.
+pub fn benchmark_games(seed: u64, num_games: usize, lookahead: u8) -> Benchmark {
+    let mut rng = SeededRng::new(seed);
+    let deadline = Deadline::from_now(0);
+    let mut nodes = 0;
+    let mut results = Vec::with_capacity(num_games);
+
+    for game in 0..num_games {
+        let starting_color = if game % 2 == 0 {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let mut engines = [(); 2].map(|_| {
+            AlphaBeta::with_capacity(
+                ProportionCount,
+                lookahead,
+                false,
+                false,
+                0,
+                BENCHMARK_MOVE_TIME,
+                BENCHMARK_TABLE_SIZE,
+            )
+        });
+
+        let mut board = MyBoard::initial_board(starting_color);
+        let mut moves: Vec<(ChessMove, bool)> = Vec::new();
+        while board.get_status().is_in_progress() {
+            let engine = &mut engines[board.get_side_to_move().to_index()];
+            let mv = engine.get_move(&board);
+            nodes += engine.last_search_stats().nodes;
+
+            let is_bonus = rng.next_f32() < crate::bonus_chance().to_num::<f32>();
+            board.apply_move(mv);
+            board.apply_bonus(is_bonus);
+            moves.push((mv, is_bonus));
+        }
+        results.push(
+            GameResult::from_moves(&moves, starting_color).expect("the game should be valid"),
+        );
+    }
+
+    Benchmark {
+        nodes,
+        millis: deadline.elapsed_millis(),
+        results,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_benchmark_deterministic() {
+        let first = benchmark_games(7, 2, 2);
+        let second = benchmark_games(7, 2, 2);
+        assert_eq!(first.results.len(), 2);
+        assert!(first.nodes > 0);
+        assert_eq!(first.nodes, second.nodes);
+        assert_eq!(first.results, second.results);
+    }
+}
@@ -0,0 +1,187 @@
+use std::{fs, io, path::Path};
+
+use chess::{ChessMove, Color};
+
+use crate::{
+    engine::Engine,
+    game::{decode_game, replay, verify_game, GameError},
+    my_board::{MyBoard, Status},
+    Score,
+};
+
+/// Why a file of recorded games couldn't be turned into a dataset
+#[derive(Debug)]
+pub enum DatasetError {
+    /// The file couldn't be read
+    Io(io::Error),
+    /// The game on the given line (counting from 1) isn't in the format
+    /// `parse_games` takes
+    Malformed { line: usize },
+    /// The game on the given line isn't a valid, finished game
+    InvalidGame { line: usize, error: GameError },
+}
+
+/// A position from a recorded game, labelled with an engine's evaluation of
+/// it and the result the game eventually had
+#[derive(Clone, Debug)]
+pub struct LabelledPosition {
+    pub board: MyBoard,
+    pub evaluation: Score,
+    pub result: Status,
+}
+
+/// A game read by `parse_games`, with its moves in the format `verify_game`
+/// takes
+#[derive(Clone, Debug)]
+pub struct RecordedGame {
+    /// The line the game was on, counting from 1
+    pub line: usize,
+    pub starting_color: Color,
+    pub moves: Vec<(ChessMove, bool)>,
+}
+
+/// Parses recorded games, one per line. Each line is the starting color
+/// (`w` or `b`) followed by the moves encoded with `encode_game`, as
+/// space-separated hexadecimal numbers. Blank lines are skipped.
+pub fn parse_games(contents: &str) -> Result<Vec<RecordedGame>, DatasetError> {
+    let mut games = Vec::new();
+    for (index, text) in contents.lines().enumerate() {
+        let line = index + 1;
+        let mut fields = text.split_whitespace();
+        let starting_color = match fields.next() {
+            None => continue,
+            Some("w") => Color::White,
+            Some("b") => Color::Black,
+            Some(_) => return Err(DatasetError::Malformed { line }),
+        };
+        let encoded = fields
+            .map(|field| u16::from_str_radix(field, 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| DatasetError::Malformed { line })?;
+        games.push(RecordedGame {
+            line,
+            starting_color,
+            moves: decode_game(&encoded).ok_or(DatasetError::Malformed { line })?,
+        });
+    }
+    Ok(games)
+}
+
+/// Reads a file of recorded games (in the format `parse_games` takes) and
+/// replays each one, labelling every position that is still in progress
+/// with `engine`'s evaluation and the game's result. This turns played games
+/// into training data in one pass.
+///
+/// Every game is checked before any are evaluated, so an invalid game is
+/// reported without wasting time on the others.
+pub fn label_games(
+    path: impl AsRef<Path>, engine: &mut dyn Engine,
+) -> Result<Vec<LabelledPosition>, DatasetError> {
+    let contents = fs::read_to_string(path).map_err(DatasetError::Io)?;
+    let games = parse_games(&contents)?;
+
+    let mut replayed = Vec::with_capacity(games.len());
+    for game in games {
+        let result = verify_game(&game.moves, game.starting_color).map_err(|error| {
+            DatasetError::InvalidGame {
+                line: game.line,
+                error,
+            }
+        })?;
+        let (history, _) =
+            replay(&game.moves, game.starting_color).expect("the game should already be verified");
+        replayed.push((history, result));
+    }
+
+    Ok(replayed
+        .into_iter()
+        .flat_map(|(history, result)| history.into_iter().map(move |board| (board, result)))
+        .filter(|(board, _)| board.get_status().is_in_progress())
+        .map(|(board, result)| LabelledPosition {
+            board,
+            evaluation: engine.evaluate(&board),
+            result,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use chess::Square;
+
+    use super::*;
+    use crate::{
+        engine::{alphabeta::AlphaBeta, proportion_count::ProportionCount},
+        game::encode_game,
+    };
+
+    fn write_games(name: &str, games: &[(Color, Vec<(ChessMove, bool)>)]) -> std::path::PathBuf {
+        let contents: String = games
+            .iter()
+            .map(|(color, moves)| {
+                let color = if *color == Color::White { "w" } else { "b" };
+                let moves: Vec<_> = encode_game(moves)
+                    .iter()
+                    .map(|e| format!("{:04x}", e))
+                    .collect();
+                format!("{} {}\n\n", color, moves.join(" "))
+            })
+            .collect();
+        // The process id keeps concurrent test runs from sharing the file
+        let path =
+            std::env::temp_dir().join(format!("random_chess_{}_{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_label_games() {
+        let moves = vec![
+            (ChessMove::new(Square::E2, Square::E4, None), true),
+            (ChessMove::new(Square::D1, Square::H5, None), false),
+            (ChessMove::new(Square::F7, Square::F6, None), false),
+            (ChessMove::new(Square::H5, Square::E8, None), false),
+        ];
+        let path = write_games("test_label_games.txt", &[
+            (Color::White, moves.clone()),
+            (Color::White, moves[..3].to_vec()),
+        ]);
+        let mut engine = AlphaBeta::new(ProportionCount, 2, false, false, 0, 10000);
+        assert!(matches!(
+            label_games(&path, &mut engine),
+            Err(DatasetError::InvalidGame {
+                line: 3,
+                error: GameError::Unfinished
+            })
+        ));
+
+        let path = write_games("test_label_games.txt", &[
+            (Color::White, moves.clone()),
+            (Color::White, moves),
+        ]);
+        let rows = label_games(&path, &mut engine).unwrap();
+        fs::remove_file(path).unwrap();
+
+        // The final position of each game is over, so it isn't labelled
+        assert_eq!(rows.len(), 8);
+        assert!(rows.iter().all(|r| r.result == Status::Win(Color::White)));
+        assert_eq!(
+            rows[0].board.get_zobrist_hash(),
+            MyBoard::initial_board(Color::White).get_zobrist_hash()
+        );
+
+        assert!(matches!(
+            parse_games("w 0c4a\nx"),
+            Err(DatasetError::Malformed { line: 2 })
+        ));
+        assert!(matches!(
+            parse_games("b zz"),
+            Err(DatasetError::Malformed { line: 1 })
+        ));
+        // The promotion bits of 7000 don't name a piece
+        assert!(matches!(
+            parse_games("w 0c4a\nw 7000"),
+            Err(DatasetError::Malformed { line: 2 })
+        ));
+    }
+}
@@ -10,16 +10,20 @@ mod wasm {
 
     #[derive(Copy, Clone)]
     pub struct Deadline {
+        start: u64,
         expiry: u64,
     }
 
     impl Deadline {
         pub fn from_now(millis: u64) -> Deadline {
+            let start = Date::now() as u64;
             Deadline {
-                expiry: (Date::now() as u64) + millis,
+                start,
+                expiry: start + millis,
             }
         }
         pub fn expired(&self) -> bool { Date::now() as u64 >= self.expiry }
+        pub fn elapsed_millis(&self) -> u64 { Date::now() as u64 - self.start }
     }
 }
 
@@ -30,15 +34,19 @@ mod normal {
 
     #[derive(Copy, Clone)]
     pub struct Deadline {
+        start: Instant,
         expiry: Instant,
     }
 
     impl Deadline {
         pub fn from_now(millis: u64) -> Deadline {
+            let start = Instant::now();
             Deadline {
-                expiry: Instant::now() + Duration::from_millis(millis),
+                start,
+                expiry: start + Duration::from_millis(millis),
             }
         }
         pub fn expired(&self) -> bool { Instant::now() >= self.expiry }
+        pub fn elapsed_millis(&self) -> u64 { self.start.elapsed().as_millis() as u64 }
     }
 }
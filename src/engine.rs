@@ -2,16 +2,20 @@ pub mod alphabeta;
 mod greedy;
 mod minimax;
 
+pub mod draw_table;
 mod evaluator;
 pub mod feature_eval;
+pub mod piece_square_eval;
 pub mod proportion_count;
 
 mod position_table;
 
 use chess::{ChessMove, Color};
-pub use evaluator::StaticEvaluator;
+#[cfg(any(test, feature = "test-util"))]
+pub use evaluator::assert_evaluation_symmetric;
+pub use evaluator::{compare_evaluators, EvaluatorComparison, StaticEvaluator};
 
-use crate::{logger::Logger, my_board::MyBoard, Score};
+use crate::{logger::Logger, my_board::MyBoard, Score, ONE};
 
 pub trait Engine {
     fn default(static_evaluator: impl StaticEvaluator + 'static) -> Self
@@ -19,12 +23,19 @@ pub trait Engine {
         Self: Sized;
     fn evaluate(&mut self, board: &MyBoard) -> Score;
 
+    /// The chance of a bonus that the engine assumes when weighting the
+    /// boards after each move
+    fn bonus_chance(&self) -> Score { crate::bonus_chance() }
+
     fn get_move(&mut self, board: &MyBoard) -> ChessMove {
         let move_evaluations = board.all_moves().map(|mv| {
             let (bonus_board, no_bonus_board) = self.next_boards(board, mv, true);
-            // Assumes the chance of bonus and chance of no bonus
-            let evaluation = self.evaluate(&bonus_board) * crate::bonus_chance()
-                + self.evaluate(&no_bonus_board) * crate::no_bonus_chance();
+            let evaluation = weighted_score(
+                self.evaluate(&bonus_board),
+                self.bonus_chance(),
+                self.evaluate(&no_bonus_board),
+                ONE - self.bonus_chance(),
+            );
             (mv, evaluation)
         });
 
@@ -63,9 +74,25 @@ pub trait Engine {
     /// move is chosen.
     fn log_info(&self) {}
 
+    /// Changes how many plies the engine looks ahead, for engines that have
+    /// a lookahead. Anything the engine has learned, such as its position
+    /// table, is kept.
+    fn set_max_lookahead(&mut self, _max_lookahead: u8) {}
+
+    /// Changes the time limit for each move in milliseconds, for engines
+    /// that have one
+    fn set_max_time(&mut self, _max_time: u64) {}
+
+    /// A human readable summary of the engine and its key parameters, for
+    /// recording which engine played a game
+    fn describe(&self) -> String { short_type_name::<Self>().to_string() }
+
     /// Generate both the bonus and no bonus boards for a move. If `checked` is
     /// true, then `apply_bonus` will be called, but otherwise
     /// `apply_bonus_unchecked` will be called, which doesn't check for draws.
+    ///
+    /// The move is only applied once, and the two children are copies of the
+    /// resulting board, so they share its zobrist hash up to the color toggle.
     fn next_boards(&self, board: &MyBoard, mv: ChessMove, checked: bool) -> (MyBoard, MyBoard) {
         let mut new_board = *board;
         new_board.apply_move_unchecked(mv);
@@ -84,7 +111,26 @@ pub trait Engine {
     fn get_logger(&self) -> &Logger;
 }
 
+/// Combines the scores of the bonus and no-bonus boards after a move into the
+/// probability-weighted score of the move.
+///
+/// All engines should combine the scores with this, so that they round in
+/// exactly the same way. Rounding only happens in the two multiplications,
+/// since fixed-point addition is exact, so the order in which the branches are
+/// searched (the no-bonus branch first in `AlphaBeta`) doesn't matter.
+pub(crate) fn weighted_score(
+    b_score: Score, b_chance: Score, nb_score: Score, nb_chance: Score,
+) -> Score {
+    b_score * b_chance + nb_score * nb_chance
+}
+
+/// The name of a type without its module path
+pub(crate) fn short_type_name<T: ?Sized>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
 #[allow(dead_code)]
 pub fn default_engine() -> impl Engine {
-    alphabeta::AlphaBeta::default(proportion_count::ProportionCount::default())
+    alphabeta::AlphaBeta::default(proportion_count::ProportionCount)
 }
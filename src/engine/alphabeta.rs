@@ -10,13 +10,52 @@ use search_result::SearchResult::{self, *};
 mod branch_info;
 use branch_info::BranchInfo;
 
+mod depth_info;
+pub use depth_info::DepthInfo;
+
+mod search_stats;
+pub use search_stats::SearchStats;
+
+mod focus_comparison;
+pub use focus_comparison::{compare_focussed, FocusComparison};
+
 #[cfg(test)] mod tests;
 
-use chess::{ChessMove, Color::*};
+use std::{
+    cmp::Ordering,
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc,
+    },
+    time::Duration,
+};
+
+use chess::{ChessMove, Color::*, Piece};
 use either::Either::{Left, Right};
 
-use super::{evaluator::StaticEvaluator, position_table::PositionTable, Engine};
-use crate::{deadline::Deadline, logger::Logger, my_board::MyBoard, Score, ONE};
+use super::{
+    evaluator::StaticEvaluator, position_table::ShardedPositionTable, weighted_score, Engine,
+};
+use crate::{
+    deadline::Deadline,
+    game::Game,
+    logger::Logger,
+    my_board::{MoveError, MoveKind, MyBoard, Status},
+    Score, DELTA, ONE, ZERO,
+};
+
+/// The number of shards the position table is split into when it is shared
+/// between threads. This is well above the number of threads that would be
+/// used, so that threads rarely wait for each other.
+const THREADED_TABLE_SHARDS: usize = 64;
+
+/// The number of moves a player is assumed to still have to make when
+/// dividing up their remaining time in `get_move_timed`
+const EXPECTED_MOVES_LEFT: u64 = 30;
+
+/// A score along with the number of plies to the end of the game if the
+/// result is forced
+type ScoreWithPlies = (Score, Option<u8>);
 
 pub struct AlphaBeta {
     static_evaluator: Box<dyn StaticEvaluator>,
@@ -24,8 +63,86 @@ pub struct AlphaBeta {
     max_time: u64,
     is_pessimistic: bool,
     is_focussed: bool,
-    position_table: PositionTable<ScoreInfo>,
+    /// The chance of a bonus that the search assumes, before any pessimistic
+    /// adjustment
+    bonus_chance: Score,
+    position_table: ShardedPositionTable<ScoreInfo>,
     logger: Logger,
+    /// The number of threads searching, including this one. The others run
+    /// helper engines which share the position table.
+    threads: usize,
+    /// The number of threads the root moves are split between, where each
+    /// thread scores its moves independently
+    root_threads: usize,
+    /// The position tables of the root threads other than this one, which
+    /// are kept between searches. The capacity is split equally between
+    /// these and `position_table`.
+    root_tables: Vec<ShardedPositionTable<ScoreInfo>>,
+    /// The search times out once this is true. It is cleared when each
+    /// search starts. Helper engines are stopped with this when the main
+    /// search finishes.
+    stop: Arc<AtomicBool>,
+    /// The number of nodes searched since the current search started
+    nodes: u64,
+    /// If set, the current search times out after this many nodes
+    node_limit: Option<u64>,
+    /// If set, every search times out after this many nodes, as well as at
+    /// the time limit
+    max_nodes: Option<u64>,
+    /// The deadline of the current search. The follow-up searches that
+    /// `get_move` makes after iterative deepening share it (and the node
+    /// limit), so that they don't add to the time given for the move.
+    search_deadline: Option<Deadline>,
+    /// If set, `get_move` checks whether the chosen move was forced, i.e.
+    /// whether every alternative is worse by more than this margin
+    forced_margin: Option<Score>,
+    last_move_forced: bool,
+    last_forced_result: Option<(Status, u8)>,
+    /// If set, positions with at least this many dead moves whose static
+    /// evaluation is within the epsilon of a draw aren't searched further
+    drawish_cutoff: Option<(Score, u8)>,
+    draw_blend_margin: Option<u8>,
+    /// If set, positions at the horizon are searched for up to this many
+    /// more plies of loud moves before being evaluated statically
+    quiescence_plies: Option<u8>,
+    /// If set, the side to move may pass, with the resulting position
+    /// searched this many plies shallower, to prune nodes early
+    null_move_reduction: Option<u8>,
+    /// Whether a null move is being searched, in which case another isn't
+    /// tried until it is finished
+    in_null_move: bool,
+    /// The number of nodes searched for each depth completed in the last
+    /// search, starting from depth 2
+    depth_nodes: Vec<u64>,
+    /// The time taken by the depths in `depth_nodes`, in milliseconds
+    depth_millis: u64,
+    /// Whether iterative deepening stops before starting a depth that isn't
+    /// expected to finish in time, which is only the case in `get_move_timed`
+    soft_deadline: bool,
+    /// The time budget computed by the last call to `get_move_timed`, in
+    /// milliseconds
+    last_time_budget: Option<u64>,
+    /// If set, each depth after the first is searched with bounds this far
+    /// either side of the previous depth's score, widening them on failure
+    aspiration_window: Option<Score>,
+    early_commit: Option<Score>,
+    /// If set, each depth of the iterative deepening is searched again
+    /// without the position table to check that the scores agree
+    verify_tt: bool,
+    /// Whether the position table is ignored, for both lookups and inserts,
+    /// which is only the case while verifying a result. Inserts are skipped
+    /// too so that the check doesn't change the table it is checking.
+    table_disabled: bool,
+    /// If set, `get_move` samples its move using this temperature, with the
+    /// function giving uniformly random numbers in `[0, 1)`
+    temperature: Option<(f32, Box<dyn FnMut() -> f32 + Send>)>,
+    /// If set, this is called with the information for each depth as soon
+    /// as the iterative deepening completes it
+    on_depth: Option<Box<dyn FnMut(DepthInfo) + Send>>,
+    /// The positions searched by `ponder`, which are empty unless pondering
+    /// was started with `start_ponder`
+    ponder_boards: Vec<MyBoard>,
+    stats: SearchStats,
     // Debug info
     branch_info: BranchInfo,
     iter_deep_failures: u32,
@@ -37,27 +154,376 @@ impl AlphaBeta {
     pub fn new(
         static_evaluator: impl StaticEvaluator + 'static, max_lookahead: u8, is_pessimistic: bool,
         is_focussed: bool, log_level: u8, max_time: u64,
+    ) -> Self {
+        let logger = Logger::new(log_level);
+        let position_table = ShardedPositionTable::new(1, &logger);
+        AlphaBeta::from_parts(
+            Box::new(static_evaluator),
+            max_lookahead,
+            max_time,
+            is_pessimistic,
+            is_focussed,
+            position_table,
+            logger,
+        )
+    }
+
+    /// Like `new`, but with a position table with space for `table_capacity`
+    /// entries, which is useful where memory is limited. Unlike
+    /// `with_table_capacity`, the full-sized table is never allocated.
+    pub fn with_capacity(
+        static_evaluator: impl StaticEvaluator + 'static, max_lookahead: u8, is_pessimistic: bool,
+        is_focussed: bool, log_level: u8, max_time: u64, table_capacity: usize,
+    ) -> Self {
+        let logger = Logger::new(log_level);
+        let position_table = ShardedPositionTable::with_capacity(table_capacity, 1, &logger);
+        AlphaBeta::from_parts(
+            Box::new(static_evaluator),
+            max_lookahead,
+            max_time,
+            is_pessimistic,
+            is_focussed,
+            position_table,
+            logger,
+        )
+    }
+
+    /// Creates an engine with the default options, which the constructors
+    /// and the helper engines for threaded searches share
+    fn from_parts(
+        static_evaluator: Box<dyn StaticEvaluator>, max_lookahead: u8, max_time: u64,
+        is_pessimistic: bool, is_focussed: bool, position_table: ShardedPositionTable<ScoreInfo>,
+        logger: Logger,
     ) -> Self {
         assert!(max_lookahead > 0, "lookahead must be positive");
         assert!(
             !is_focussed || max_lookahead > 1,
             "lookahead must be greater than 1 if focussed"
         );
-        let logger = Logger::new(log_level);
         AlphaBeta {
-            static_evaluator: Box::new(static_evaluator),
+            static_evaluator,
             max_lookahead,
             max_time,
             is_pessimistic,
             is_focussed,
-            position_table: PositionTable::new(&logger),
+            bonus_chance: crate::bonus_chance(),
+            position_table,
             logger,
+            threads: 1,
+            root_threads: 1,
+            root_tables: Vec::new(),
+            stop: Arc::new(AtomicBool::new(false)),
+            nodes: 0,
+            node_limit: None,
+            max_nodes: None,
+            search_deadline: None,
+            forced_margin: None,
+            last_move_forced: false,
+            last_forced_result: None,
+            drawish_cutoff: None,
+            draw_blend_margin: None,
+            quiescence_plies: None,
+            null_move_reduction: None,
+            in_null_move: false,
+            depth_nodes: Vec::new(),
+            depth_millis: 0,
+            soft_deadline: false,
+            last_time_budget: None,
+            aspiration_window: None,
+            early_commit: None,
+            verify_tt: false,
+            table_disabled: false,
+            temperature: None,
+            on_depth: None,
+            ponder_boards: Vec::new(),
+            stats: SearchStats::default(),
             branch_info: BranchInfo::new(max_lookahead),
             iter_deep_failures: 0,
             iter_deep_lookups: 0,
         }
     }
 
+    /// Makes `get_move` also check whether the move it returns is forced,
+    /// which is the case when every other move scores worse than it by more
+    /// than `margin`. This requires searching every root move exactly, so it
+    /// makes `get_move` slower.
+    pub fn with_forced_margin(mut self, margin: Score) -> Self {
+        self.forced_margin = Some(margin);
+        self
+    }
+
+    /// Replaces the position table with one with space for `capacity`
+    /// entries. The search still works with a small table, but it is slower.
+    /// The old table is only freed once the new one is allocated, so use
+    /// `with_capacity` instead where memory is limited.
+    pub fn with_table_capacity(mut self, capacity: usize) -> Self {
+        self.split_table_capacity(capacity);
+        self
+    }
+
+    /// Limits each search to `nodes` nodes, as well as to the time limit,
+    /// with the search timing out at whichever comes first. Unlike the time
+    /// limit, this gives the same moves on any machine, which makes it
+    /// useful for benchmarks and for comparing engines fairly. The limit
+    /// must be enough to complete the first depth.
+    pub fn with_max_nodes(mut self, nodes: u64) -> Self {
+        assert!(nodes > 0, "node limit must be positive");
+        self.max_nodes = Some(nodes);
+        self
+    }
+
+    /// Makes each search run on `threads` threads, using the Lazy SMP
+    /// approach: the extra threads run the same iterative deepening as the
+    /// main one, and only help by filling the shared position table with
+    /// results the main search can use. Half of them start a depth deeper,
+    /// so that they get ahead of the main search rather than following it.
+    ///
+    /// The evaluator must support `clone_for_thread`. The position table is
+    /// replaced with a sharded one of the same capacity.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        assert!(threads > 0, "there must be at least one thread");
+        assert!(
+            threads == 1 || self.static_evaluator.clone_for_thread().is_some(),
+            "the evaluator can't be used from several threads"
+        );
+        assert!(
+            threads == 1 || self.root_threads == 1,
+            "the root moves are already split between threads"
+        );
+        let shards = if threads > 1 {
+            THREADED_TABLE_SHARDS
+        } else {
+            1
+        };
+        self.position_table = ShardedPositionTable::with_capacity(
+            self.position_table.capacity(),
+            shards,
+            &self.logger,
+        );
+        self.threads = threads;
+        self
+    }
+
+    /// Makes each search split the root moves between `threads` threads.
+    /// Each thread scores its moves independently with a full window, using
+    /// its own position table with an equal share of the capacity. This
+    /// prunes less than searching the moves in turn, but each move's score
+    /// doesn't depend on how the threads are scheduled, so the same moves
+    /// are always chosen. Ties are broken by the order of `all_moves`.
+    ///
+    /// The evaluator must support `clone_for_thread`. This can't be combined
+    /// with `with_threads`, and the root threads don't use the aspiration
+    /// window, early commit or table verification.
+    pub fn with_root_threads(mut self, threads: usize) -> Self {
+        assert!(threads > 0, "there must be at least one thread");
+        assert!(
+            threads == 1 || self.static_evaluator.clone_for_thread().is_some(),
+            "the evaluator can't be used from several threads"
+        );
+        assert!(
+            threads == 1 || self.threads == 1,
+            "the search already uses several threads"
+        );
+        if threads != self.root_threads {
+            let capacity = self.table_capacity();
+            self.root_threads = threads;
+            self.split_table_capacity(capacity);
+        }
+        self
+    }
+
+    /// The total number of entries in the position tables, including those
+    /// of the root threads
+    fn table_capacity(&self) -> usize {
+        self.position_table.capacity()
+            + self
+                .root_tables
+                .iter()
+                .map(|table| table.capacity())
+                .sum::<usize>()
+    }
+
+    /// Replaces the position tables with ones that have `capacity` entries
+    /// in total, split equally between the root threads
+    fn split_table_capacity(&mut self, capacity: usize) {
+        let share = (capacity / self.root_threads).max(1);
+        self.position_table =
+            ShardedPositionTable::with_capacity(share, self.position_table.shards(), &self.logger);
+        self.root_tables = (1..self.root_threads)
+            .map(|_| ShardedPositionTable::with_capacity(share, 1, &self.logger))
+            .collect();
+    }
+
+    /// Makes each depth of the iterative deepening (after the first) start by
+    /// searching within `width` of the previous depth's score. If the score
+    /// turns out to be outside this window, the failing side of the window is
+    /// doubled and the depth is searched again.
+    pub fn with_aspiration_window(mut self, width: Score) -> Self {
+        assert!(width > Score::ZERO, "window width must be positive");
+        self.aspiration_window = Some(width);
+        self
+    }
+
+    /// Makes iterative deepening stop early once two consecutive depths
+    /// agree on the best move and its score for the side to move is at least
+    /// `threshold`. This saves time in self-play when a move is clearly
+    /// winning, at the cost of not confirming it at deeper depths, so it is
+    /// off by default.
+    pub fn with_early_commit(mut self, threshold: Score) -> Self {
+        assert!(threshold > ONE / 2, "threshold must be better than a draw");
+        self.early_commit = Some(threshold);
+        self
+    }
+
+    /// Makes each depth of the iterative deepening be searched a second time
+    /// with the position table ignored, panicking if the scores differ by
+    /// more than `DELTA`. This makes searches far slower, so it is only for
+    /// catching bugs in how bounds and table entries are used.
+    pub fn with_verify_tt(mut self) -> Self {
+        self.verify_tt = true;
+        self
+    }
+
+    /// Makes `get_move` sample its move rather than always playing the best
+    /// one, which is useful for generating varied games. Each root move is
+    /// chosen with probability proportional to `exp(score / temperature)`,
+    /// where the score is from the perspective of the side to move, using
+    /// `rng` to give uniformly random numbers in `[0, 1)`. A temperature of 0
+    /// always plays the best move.
+    pub fn with_temperature(
+        mut self, temperature: f32, rng: impl FnMut() -> f32 + Send + 'static,
+    ) -> Self {
+        assert!(temperature >= 0.0, "temperature can't be negative");
+        self.temperature = Some((temperature, Box::new(rng)));
+        self
+    }
+
+    /// Calls `on_depth` with the information for each depth as soon as the
+    /// iterative deepening completes it, much like the `info` lines of a UCI
+    /// engine. This lets an analysis display follow a search while
+    /// `get_move` (or `analyze`) is still running. Only the main search
+    /// reports depths, not the helper threads.
+    pub fn with_on_depth(mut self, on_depth: impl FnMut(DepthInfo) + Send + 'static) -> Self {
+        self.on_depth = Some(Box::new(on_depth));
+        self
+    }
+
+    /// A flag which stops the current search when it is set, so that a
+    /// search can be cancelled from another thread. The search then finishes
+    /// as if it had reached its deadline, returning the best move from the
+    /// last depth it completed, or a move suggested by the position table if
+    /// it didn't complete one. The flag is only cleared once a search has
+    /// been stopped by it, so setting it just before a search starts still
+    /// cancels that search.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> { Arc::clone(&self.stop) }
+
+    /// Statistics about the last call to `get_move` (or `analyze`, etc.)
+    pub fn last_search_stats(&self) -> SearchStats { self.stats }
+
+    /// The statistics kept by the static evaluator, if it keeps any
+    pub fn evaluator_statistics(&self) -> Option<String> { self.static_evaluator.statistics() }
+
+    /// Resets the search statistics and the debug info of the position table
+    /// and branches, without clearing the position table itself. Searches
+    /// already reset these as they go, so this is for measuring a search
+    /// from a clean slate.
+    pub fn reset_stats(&mut self) {
+        self.stats = SearchStats::default();
+        self.reset_debug_info();
+    }
+
+    /// Clears everything the engine has learned from earlier searches,
+    /// including the position table, so that it plays as a fresh engine
+    /// with the same options would. This is for starting a new game without
+    /// allocating a new table.
+    pub fn reset(&mut self) {
+        self.position_table.clear();
+        for table in &self.root_tables {
+            table.clear();
+        }
+        self.stop_ponder();
+        self.depth_nodes.clear();
+        self.depth_millis = 0;
+        self.last_move_forced = false;
+        self.last_forced_result = None;
+        self.last_time_budget = None;
+        self.reset_stats();
+    }
+
+    /// Makes the search treat positions as likely draws, and so evaluate them
+    /// statically rather than searching them, when at least `min_dead_moves`
+    /// dead moves have been played and the static evaluation is within
+    /// `epsilon` of 0.5. This trades a little accuracy for speed, since such
+    /// positions tend to stay level until the game is drawn.
+    pub fn with_drawish_cutoff(mut self, epsilon: Score, min_dead_moves: u8) -> Self {
+        self.drawish_cutoff = Some((epsilon, min_dead_moves));
+        self
+    }
+
+    /// Makes the search blend static evaluations toward 0.5 when the position
+    /// is within `margin` dead moves of a draw by the fifty move rule, in
+    /// proportion to how close it is. This stops the engine from investing in
+    /// lines that look won but will be drawn before the advantage can be
+    /// converted.
+    pub fn with_draw_blending(mut self, margin: u8) -> Self {
+        self.draw_blend_margin = Some(margin);
+        self
+    }
+
+    /// Makes the search continue past the horizon with a quiescence search,
+    /// which only plays loud moves (captures, promotions and, on its first
+    /// ply, moves that attack the king) for up to `max_plies` plies. Without
+    /// this, positions at the horizon are evaluated statically even when a
+    /// piece is about to be captured, so the engine can walk into losing
+    /// its queen just past the horizon.
+    pub fn with_quiescence(mut self, max_plies: u8) -> Self {
+        self.quiescence_plies = Some(max_plies);
+        self
+    }
+
+    /// Makes the search assume a bonus happens with probability `chance`,
+    /// rather than `crate::bonus_chance`, for playing other variants. The
+    /// chance must be strictly between 0 and 1, since otherwise one of the
+    /// boards after a move would never happen and its bounds couldn't be
+    /// scaled.
+    pub fn with_bonus_chance(mut self, chance: Score) -> Self {
+        assert!(
+            chance > ZERO && chance < ONE,
+            "bonus chance must be between 0 and 1"
+        );
+        self.bonus_chance = chance;
+        self
+    }
+
+    /// Makes the search try a null move before searching a node's moves: the
+    /// side to move passes, and the position is searched `reduction` plies
+    /// shallower than its moves would be. If even passing is good enough to
+    /// fall outside the bounds, the node is pruned.
+    ///
+    /// With the bonus, a pass would leave the position unchanged, so only
+    /// the pass without the bonus is searched. That is enough, since if
+    /// passing is no better than the best move, neither is the pass without
+    /// the bonus, whatever the bonus chance. Null moves aren't tried in
+    /// check, or when the side to move has only pawns, since then passing
+    /// might really be better than any move.
+    pub fn with_null_move_pruning(mut self, reduction: u8) -> Self {
+        assert!(reduction > 0, "reduction must be positive");
+        self.null_move_reduction = Some(reduction);
+        self
+    }
+
+    /// The result that the move returned by the last call to `get_move`
+    /// forces, along with the number of plies until the game ends, such as
+    /// a king capture in 3 plies. This is `None` if the result isn't forced
+    /// within the lookahead, or if it depends on the bonus rolls (e.g. a win
+    /// with the bonus but a draw without it).
+    pub fn last_forced_result(&self) -> Option<(Status, u8)> { self.last_forced_result }
+
+    /// Whether the move returned by the last call to `get_move` was the only
+    /// reasonable move. This is always false if no margin was set with
+    /// `with_forced_margin`, or if checking timed out.
+    pub fn last_move_was_forced(&self) -> bool { self.last_move_forced }
+
     /// Gets the best move for the current player, along with its score.
     ///
     /// This function takes in `bounds` to search for the move within.
@@ -75,10 +541,12 @@ impl AlphaBeta {
     ) -> SearchResult {
         assert!(bounds.valid());
 
-        if deadline.expired() {
+        if self.should_stop(deadline) {
             return Timeout;
         }
 
+        self.nodes += 1;
+
         let mut bounds = bounds;
 
         let finish_depth = if self.is_focussed { 1 } else { 0 };
@@ -86,14 +554,22 @@ impl AlphaBeta {
         self.branch_info[depth as usize].not_pruned += 1;
 
         // Check if there is an existing entry in the position table
-        if let Some(score_info) = self.position_table.get(board, depth) {
+        let table_info = if self.table_disabled {
+            None
+        } else {
+            self.position_table.get(board, depth)
+        };
+        if let Some(score_info) = table_info {
             if bounds.info_too_low(score_info) {
+                self.branch_info[depth as usize].table_cutoffs += 1;
                 return Low;
             } else if bounds.info_too_high(score_info) {
+                self.branch_info[depth as usize].table_cutoffs += 1;
                 return High;
             } else if let Some(score) = score_info.actual_score() {
                 if !get_move {
-                    return Result(score, None);
+                    self.branch_info[depth as usize].table_exact += 1;
+                    return Result(score, None, score_info.plies_to_result);
                 }
             }
             // Updating the bounds here should be possible, but it's fraught,
@@ -104,13 +580,43 @@ impl AlphaBeta {
 
         self.branch_info[depth as usize].expanded += 1;
 
-        if depth <= finish_depth || !board.get_status().is_in_progress() {
-            let evaluation = self.static_evaluator.evaluate(board);
+        let drawish_evaluation = if get_move || depth <= finish_depth {
+            None
+        } else {
+            self.drawish_evaluation(board)
+        };
+
+        // Boards at the horizon are generated without checking for a no moves
+        // draw, so it has to be checked for here instead. Finding the first
+        // move is usually cheap, since the iterator is lazy.
+        let no_moves = depth <= finish_depth
+            && board.get_status().is_in_progress()
+            && board.all_moves().next().is_none();
+
+        if depth <= finish_depth
+            || !board.get_status().is_in_progress()
+            || drawish_evaluation.is_some()
+        {
+            if let Some(max_plies) = self.quiescence_plies {
+                if depth <= finish_depth && board.get_status().is_in_progress() && !no_moves {
+                    let res = self.quiescence(board, bounds, max_plies, deadline);
+                    self.update_table_for_result(board, depth, bounds, &res);
+                    return res;
+                }
+            }
+
+            let (evaluation, plies_to_result) = match drawish_evaluation {
+                Some(evaluation) => (evaluation, None),
+                None => self.leaf_evaluation(board, no_moves),
+            };
 
             // TODO: Take advantage of the fact that a lot of the computation when just the
             //   side to move changes is redundant (see below)
-            self.position_table
-                .insert(board, depth, ScoreInfo::from_score(evaluation));
+            self.insert_into_table(
+                board,
+                depth,
+                ScoreInfo::from_score(evaluation).with_plies_to_result(plies_to_result),
+            );
 
             return if bounds.score_too_low(evaluation) {
                 Low
@@ -118,11 +624,24 @@ impl AlphaBeta {
                 High
             } else {
                 assert!(!get_move, "depth was too small to return a move");
-                Result(evaluation, None)
+                Result(evaluation, None, plies_to_result)
             };
         }
 
         let is_maxing = board.get_side_to_move() == White;
+
+        if !get_move {
+            match self.null_move_search(board, bounds, depth, deadline) {
+                Some(Timeout) => return Timeout,
+                Some(res) => {
+                    self.update_table_for_result(board, depth, bounds, &res);
+                    self.branch_info[depth as usize].null_move_prunes += 1;
+                    return res;
+                }
+                None => {}
+            }
+        }
+
         let mut best_result = None;
 
         let moves = if depth > finish_depth + 1 {
@@ -136,7 +655,12 @@ impl AlphaBeta {
 
                 let mut key = None;
 
-                if let Some(info) = self.position_table.get_lenient(&nb_board) {
+                let table_info = if self.table_disabled {
+                    None
+                } else {
+                    self.position_table.get_lenient(&nb_board)
+                };
+                if let Some(info) = table_info {
                     if let Some(score) = info.actual_score() {
                         key = Some(score);
                     }
@@ -144,14 +668,22 @@ impl AlphaBeta {
 
                 let key = key.unwrap_or_else(|| {
                     self.iter_deep_failures += 1;
-                    let eval = self.static_evaluator.evaluate(&nb_board);
+                    // Score the child the same way it would be at the
+                    // horizon, so the entry matches what a search would store
+                    let no_moves = nb_board.get_status().is_in_progress()
+                        && nb_board.all_moves().next().is_none();
+                    let (eval, plies_to_result) = self.leaf_evaluation(&nb_board, no_moves);
                     // TODO: Take advantage of the fact that a lot of the computation when just the
                     //   side to move changes is redundant (see above)
-                    self.position_table.insert(
-                        &nb_board,
-                        finish_depth,
-                        ScoreInfo::from_score(eval),
-                    );
+                    // With quiescence, the horizon isn't scored statically,
+                    // so the static evaluation can't go in the table
+                    if self.quiescence_plies.is_none() {
+                        self.insert_into_table(
+                            &nb_board,
+                            finish_depth,
+                            ScoreInfo::from_score(eval).with_plies_to_result(plies_to_result),
+                        );
+                    }
                     eval
                 });
 
@@ -167,74 +699,23 @@ impl AlphaBeta {
             Right(board.all_moves())
         };
 
+        let b_depth = depth - if self.is_focussed { 2 } else { 1 };
         for mv in moves {
-            let (b_board, nb_board) = self.next_boards(board, mv, depth > finish_depth + 1);
-
-            // Define the bonus and non-bonus chances in an adjusted way.
-            // This has the effect of making the AI more defensive.
-            // This makes it more fun to play against, and also probably more
-            // consistent against weaker opponents.
-            let mut b_chance = crate::bonus_chance();
-            let mut nb_chance = crate::no_bonus_chance();
-
-            if self.is_pessimistic {
-                let adjustment = Score::from_num(
-                    ((b_board.get_black_pieces() | b_board.get_white_pieces()).count()) as f64
-                        / 200.0,
-                );
-                if is_maxing {
-                    b_chance += adjustment;
-                    nb_chance -= adjustment;
-                } else {
-                    b_chance -= adjustment;
-                    nb_chance += adjustment;
-                }
-            }
-
-            // Calculate the implied bounds on the no-bonus branch, assuming
-            // a worst-case scenario for the bonus branch at both sides of the
-            // bound.
-            let nb_bounds = bounds.min_decreased_by(b_chance).expanded(nb_chance);
-
-            let nb_result =
-                self.get_scored_best_move(&nb_board, nb_bounds, depth - 1, false, deadline);
-
-            // Determine a probability weighted score for this move, or a prune
-            let result = if let Result(nb_score, _) = nb_result {
-                let b_bounds = bounds
-                    .both_decreased_by(nb_score * nb_chance)
-                    .expanded(b_chance);
-                let b_result = self.get_scored_best_move(
-                    &b_board,
-                    b_bounds,
-                    depth - if self.is_focussed { 2 } else { 1 },
-                    false,
-                    deadline,
-                );
-                if let Result(b_score, _) = b_result {
-                    let score = b_score * b_chance + nb_score * nb_chance;
-                    if !bounds.contains(score) {
-                        if Some(score) == bounds.min {
-                            Low
-                        } else if Some(score) == bounds.max {
-                            High
-                        } else {
-                            panic!("score is distinctly out of bounds");
-                        }
-                    } else {
-                        Result(score, None)
-                    }
-                } else {
-                    b_result
-                }
-            } else {
-                nb_result
-            };
+            let result = self.search_move(
+                board,
+                mv,
+                bounds,
+                depth > finish_depth + 1,
+                |engine, child, child_bounds, is_bonus| {
+                    let child_depth = if is_bonus { b_depth } else { depth - 1 };
+                    engine.get_scored_best_move(child, child_bounds, child_depth, false, deadline)
+                },
+            );
 
             // Set `score` to be the actual score, unless it was a prune, in
             // which case we either continue or return, depending on the
             // direction of the prune
-            let Result(score, _) = result else {
+            let Result(score, _, plies) = result else {
                 if result == Timeout {
                     return Timeout;
                 }
@@ -262,18 +743,19 @@ impl AlphaBeta {
 
             // Update the best result found so far
             best_result = match best_result {
-                None => Some((score, mv)),
-                Some((best_score, _))
-                    if (is_maxing && score > best_score) || (!is_maxing && score < best_score) =>
+                None => Some((score, mv, plies)),
+                Some((best_score, _, best_plies))
+                    if compare_results(is_maxing, (score, plies), (best_score, best_plies))
+                        == Ordering::Greater =>
                 {
-                    Some((score, mv))
+                    Some((score, mv, plies))
                 }
                 _ => best_result,
             };
         }
 
-        let res = if let Some((score, mv)) = best_result {
-            Result(score, Some(mv))
+        let res = if let Some((score, mv, plies)) = best_result {
+            Result(score, Some(mv), plies)
         } else if is_maxing {
             Low
         } else {
@@ -284,6 +766,1146 @@ impl AlphaBeta {
         res
     }
 
+    /// Searches the position after the side to move passes, with a null window
+    /// at the bound that would prune `board`. Returns the prune if passing is
+    /// already outside `bounds` (or a timeout), and `None` if null moves
+    /// aren't enabled or allowed here, or if passing wasn't good enough.
+    fn null_move_search(
+        &mut self, board: &MyBoard, bounds: Bounds, depth: u8, deadline: Deadline,
+    ) -> Option<SearchResult> {
+        let reduction = self.null_move_reduction?;
+        let finish_depth = if self.is_focussed { 1 } else { 0 };
+        let color = board.get_side_to_move();
+        if self.in_null_move
+            || depth <= finish_depth + reduction + 1
+            || board.in_check(color)
+            || board.material(color)[Piece::Pawn.to_index() + 1..Piece::King.to_index()]
+                .iter()
+                .all(|&count| count == 0)
+        {
+            return None;
+        }
+
+        let is_maxing = color == White;
+        let null_bounds = if is_maxing {
+            let max = bounds.max?;
+            Bounds {
+                min: max.checked_sub(DELTA),
+                max: Some(max),
+            }
+        } else {
+            let min = bounds.min?;
+            Bounds {
+                min: Some(min),
+                max: min.checked_add(DELTA).filter(|&max| max <= ONE),
+            }
+        };
+
+        self.in_null_move = true;
+        let res = self.get_scored_best_move(
+            &board.with_null_move(),
+            null_bounds,
+            depth - 1 - reduction,
+            false,
+            deadline,
+        );
+        self.in_null_move = false;
+
+        match (&res, is_maxing) {
+            (Timeout, _) | (High, true) | (Low, false) => Some(res),
+            _ => None,
+        }
+    }
+
+    /// Searches the bonus and no-bonus boards after playing `mv` on `board`
+    /// with `search`, which is also told whether it is given the bonus board.
+    /// Returns the probability weighted score of the move, or a prune if it
+    /// is outside `bounds`.
+    fn search_move(
+        &mut self, board: &MyBoard, mv: ChessMove, bounds: Bounds, checked: bool,
+        search: impl Fn(&mut Self, &MyBoard, Bounds, bool) -> SearchResult,
+    ) -> SearchResult {
+        let is_maxing = board.get_side_to_move() == White;
+        let (b_board, nb_board) = self.next_boards(board, mv, checked);
+
+        let (b_chance, nb_chance) = self.bonus_chances(&b_board, is_maxing);
+
+        // Calculate the implied bounds on the no-bonus branch, assuming
+        // a worst-case scenario for the bonus branch at both sides of the
+        // bound.
+        let nb_bounds = bounds.min_decreased_by(b_chance).expanded(nb_chance);
+
+        let nb_result = search(self, &nb_board, nb_bounds, false);
+        let Result(nb_score, _, nb_plies) = nb_result else {
+            return nb_result;
+        };
+
+        let b_bounds = bounds
+            .both_decreased_by(nb_score * nb_chance)
+            .expanded(b_chance);
+        let b_result = search(self, &b_board, b_bounds, true);
+        let Result(b_score, _, b_plies) = b_result else {
+            return b_result;
+        };
+
+        let score = weighted_score(b_score, b_chance, nb_score, nb_chance);
+        if !bounds.contains(score) {
+            if Some(score) == bounds.min {
+                Low
+            } else if Some(score) == bounds.max {
+                High
+            } else {
+                panic!("score is distinctly out of bounds");
+            }
+        } else {
+            Result(score, None, plies_after(nb_plies, b_plies))
+        }
+    }
+
+    /// Searches only the loud moves from `board` for up to `plies` more
+    /// plies, so that positions aren't evaluated statically in the middle of
+    /// an exchange. The side to move can always stand pat with the static
+    /// evaluation instead of playing a loud move.
+    fn quiescence(
+        &mut self, board: &MyBoard, bounds: Bounds, plies: u8, deadline: Deadline,
+    ) -> SearchResult {
+        if self.should_stop(deadline) {
+            return Timeout;
+        }
+
+        self.nodes += 1;
+
+        let no_moves = board.get_status().is_in_progress() && board.all_moves().next().is_none();
+        let (stand_pat, plies_to_result) = self.leaf_evaluation(board, no_moves);
+        if plies_to_result.is_some() || plies == 0 {
+            return if bounds.score_too_low(stand_pat) {
+                Low
+            } else if bounds.score_too_high(stand_pat) {
+                High
+            } else {
+                Result(stand_pat, None, plies_to_result)
+            };
+        }
+
+        let is_maxing = board.get_side_to_move() == White;
+        let mut bounds = bounds;
+        let mut best_result = None;
+
+        if is_maxing && bounds.score_too_high(stand_pat) {
+            return High;
+        } else if !is_maxing && bounds.score_too_low(stand_pat) {
+            return Low;
+        } else if bounds.contains(stand_pat) {
+            best_result = Some((stand_pat, None));
+            if is_maxing {
+                bounds.update_min(stand_pat);
+            } else {
+                bounds.update_max(stand_pat);
+            }
+        }
+
+        // Moves that attack the king are only searched on the first ply,
+        // since searching them on every ply makes the search explode
+        let include_threats = Some(plies) == self.quiescence_plies;
+        let moves: Vec<_> = board
+            .all_moves()
+            .filter(|&mv| is_loud(board, mv, include_threats))
+            .collect();
+
+        for mv in moves {
+            let result = self.search_move(
+                board,
+                mv,
+                bounds,
+                false,
+                |engine, child, child_bounds, _| {
+                    engine.quiescence(child, child_bounds, plies - 1, deadline)
+                },
+            );
+
+            // Every result within the bounds is better than the best so far,
+            // since the bounds are tightened to it
+            let Result(score, _, plies) = result else {
+                if result == Timeout {
+                    return Timeout;
+                }
+                if is_maxing == (result == Low) { continue; }
+                else {
+                    return if is_maxing { High } else { Low };
+                }
+            };
+
+            if is_maxing {
+                bounds.update_min(score);
+            } else {
+                bounds.update_max(score);
+            }
+            best_result = Some((score, plies));
+        }
+
+        match best_result {
+            Some((score, plies)) => Result(score, None, plies),
+            None if is_maxing => Low,
+            None => High,
+        }
+    }
+
+    /// The evaluation of a board that isn't searched any further, along with
+    /// the plies to the result if the game is over. `no_moves` should be
+    /// true if the game is in progress but the side to move has no moves.
+    fn leaf_evaluation(&self, board: &MyBoard, no_moves: bool) -> (Score, Option<u8>) {
+        if no_moves {
+            let evaluation = match board.status_without_moves() {
+                Status::Win(White) => ONE,
+                Status::Win(Black) => ZERO,
+                _ => ONE / 2,
+            };
+            (evaluation, Some(0))
+        } else if board.get_status().is_in_progress() {
+            (
+                self.blend_toward_draw(board, self.static_evaluator.evaluate(board)),
+                None,
+            )
+        } else {
+            (self.static_evaluator.evaluate(board), Some(0))
+        }
+    }
+
+    /// Returns the static evaluation of `board` if the drawish cutoff is
+    /// enabled and applies to it
+    fn drawish_evaluation(&self, board: &MyBoard) -> Option<Score> {
+        let (epsilon, min_dead_moves) = self.drawish_cutoff?;
+        if !board.get_status().is_in_progress() || board.get_dead_moves() < min_dead_moves {
+            return None;
+        }
+        let evaluation = self.static_evaluator.evaluate(board);
+        (evaluation.abs_diff(ONE / 2) <= epsilon).then_some(evaluation)
+    }
+
+    /// Blends `evaluation` toward 0.5 if draw blending is enabled and the
+    /// board is near a draw by the fifty move rule
+    fn blend_toward_draw(&self, board: &MyBoard, evaluation: Score) -> Score {
+        let Some(margin) = self.draw_blend_margin else {
+            return evaluation;
+        };
+        if !board.get_status().is_in_progress() || !board.is_near_draw(margin) {
+            return evaluation;
+        }
+        let remaining =
+            Score::from_num(board.moves_until_fifty_move_draw() as f32 / (margin as f32 + 1.0));
+        if evaluation >= ONE / 2 {
+            ONE / 2 + (evaluation - ONE / 2) * remaining
+        } else {
+            ONE / 2 - (ONE / 2 - evaluation) * remaining
+        }
+    }
+
+    /// Returns the chances of `(bonus, no bonus)` to use when weighting the
+    /// children of a move, where `b_board` is the bonus board.
+    fn bonus_chances(&self, b_board: &MyBoard, is_maxing: bool) -> (Score, Score) {
+        // Define the bonus and non-bonus chances in an adjusted way.
+        // This has the effect of making the AI more defensive.
+        // This makes it more fun to play against, and also probably more
+        // consistent against weaker opponents.
+        let mut b_chance = self.bonus_chance;
+        let mut nb_chance = ONE - self.bonus_chance;
+
+        if self.is_pessimistic {
+            // The adjustment is limited so that neither chance reaches 0,
+            // which only matters for small or large configured chances
+            let adjustment = Score::from_num(
+                ((b_board.get_black_pieces() | b_board.get_white_pieces()).count()) as f64 / 200.0,
+            )
+            .min(b_chance.min(nb_chance) - DELTA);
+            if is_maxing {
+                b_chance += adjustment;
+                nb_chance -= adjustment;
+            } else {
+                b_chance -= adjustment;
+                nb_chance += adjustment;
+            }
+        }
+
+        (b_chance, nb_chance)
+    }
+
+    /// Scores and ranks only the given `candidates`, best first for the side
+    /// to move. This is cheaper than a full search when only a few moves are
+    /// of interest, e.g. when giving feedback on a player's move.
+    ///
+    /// Each depth is searched in turn, and the ranking from the deepest depth
+    /// that completed before the deadline is returned.
+    ///
+    /// Returns why the first of the candidates that isn't a legal move
+    /// couldn't be played, since the candidates may come from user input.
+    pub fn rank_moves(
+        &mut self, board: &MyBoard, candidates: &[ChessMove],
+    ) -> std::result::Result<Vec<(ChessMove, Score)>, MoveError> {
+        for &mv in candidates {
+            let mut played = *board;
+            played.try_apply_move(mv)?;
+        }
+
+        let deadline = self.start_search(None);
+        let ranked = self.rank_moves_until(board, candidates, deadline);
+        self.consume_stop();
+        Ok(ranked
+            .into_iter()
+            .map(|(mv, (score, _))| (mv, score))
+            .collect())
+    }
+
+    /// Ranks the `candidates` like `rank_moves`, with the given deadline,
+    /// keeping the plies to the result of each move
+    fn rank_moves_until(
+        &mut self, board: &MyBoard, candidates: &[ChessMove], deadline: Deadline,
+    ) -> Vec<(ChessMove, (Score, Option<u8>))> {
+        let is_maxing = board.get_side_to_move() == White;
+        let mut ranked = Vec::new();
+
+        'depths: for depth in 2..=self.max_lookahead {
+            let mut scored = Vec::with_capacity(candidates.len());
+            for &mv in candidates {
+                let Some(result) = self.score_move(board, mv, depth, deadline) else {
+                    break 'depths;
+                };
+                scored.push((mv, result));
+            }
+            ranked = scored;
+        }
+
+        ranked.sort_by(|(_, a), (_, b)| compare_results(is_maxing, *b, *a));
+        ranked
+    }
+
+    /// Searches a single move from `board` to `depth`, returning its exact
+    /// score and the number of plies to the end of the game if it is forced,
+    /// or `None` if the search timed out.
+    fn score_move(
+        &mut self, board: &MyBoard, mv: ChessMove, depth: u8, deadline: Deadline,
+    ) -> Option<(Score, Option<u8>)> {
+        let is_maxing = board.get_side_to_move() == White;
+        let (b_board, nb_board) = self.next_boards(board, mv, true);
+        let (b_chance, nb_chance) = self.bonus_chances(&b_board, is_maxing);
+        let ((b_score, b_plies), (nb_score, nb_plies)) =
+            self.search_children(&b_board, &nb_board, depth, deadline)?;
+
+        Some((
+            weighted_score(b_score, b_chance, nb_score, nb_chance),
+            plies_after(nb_plies, b_plies),
+        ))
+    }
+
+    /// Searches the bonus and no-bonus boards after playing `mv` on `board`,
+    /// as `score_move` would, returning the score and plies to the result of
+    /// each (bonus first), or `None` if the search timed out.
+    fn score_children(
+        &mut self, board: &MyBoard, mv: ChessMove, depth: u8, deadline: Deadline,
+    ) -> Option<(ScoreWithPlies, ScoreWithPlies)> {
+        let (b_board, nb_board) = self.next_boards(board, mv, true);
+        self.search_children(&b_board, &nb_board, depth, deadline)
+    }
+
+    /// Searches the bonus and no-bonus boards after a move that was searched
+    /// to `depth`, for `score_children` and `score_move`
+    fn search_children(
+        &mut self, b_board: &MyBoard, nb_board: &MyBoard, depth: u8, deadline: Deadline,
+    ) -> Option<(ScoreWithPlies, ScoreWithPlies)> {
+        let Result(nb_score, _, nb_plies) =
+            self.get_scored_best_move(nb_board, Bounds::widest(), depth - 1, false, deadline)
+        else {
+            return None;
+        };
+        let b_depth = depth - if self.is_focussed { 2 } else { 1 };
+        let Result(b_score, _, b_plies) =
+            self.get_scored_best_move(b_board, Bounds::widest(), b_depth, false, deadline)
+        else {
+            return None;
+        };
+
+        Some(((b_score, b_plies), (nb_score, nb_plies)))
+    }
+
+    /// When the search found a forced result, the move it returned is only
+    /// guaranteed to have the best score, since moves with an equal score are
+    /// pruned. This finds the other moves with the same score, with a search
+    /// that fails quickly for the rest, and scores them exactly to find the
+    /// one that reaches the result soonest if it is favourable, or latest if
+    /// it is unfavourable. This shares the search's deadline and node limit,
+    /// and keeps the best move found so far if they run out.
+    fn best_by_distance(
+        &mut self, board: &MyBoard, best: &DepthInfo,
+    ) -> (ChessMove, ScoreWithPlies) {
+        let is_maxing = board.get_side_to_move() == White;
+        if best.plies_to_result.is_none() || best.score == ONE / 2 {
+            return (best.best_move, (best.score, best.plies_to_result));
+        }
+
+        let deadline = self.search_deadline();
+        let depth = best.depth;
+        let b_depth = depth - if self.is_focussed { 2 } else { 1 };
+        let tie_bounds = Bounds::around(best.score, DELTA, DELTA);
+        let moves: Vec<_> = board
+            .all_moves()
+            .filter(|&mv| mv != best.best_move)
+            .collect();
+        let mut chosen = (best.best_move, (best.score, best.plies_to_result));
+
+        for mv in moves {
+            let tie = self.search_move(
+                board,
+                mv,
+                tie_bounds,
+                true,
+                |engine, child, child_bounds, is_bonus| {
+                    let child_depth = if is_bonus { b_depth } else { depth - 1 };
+                    engine.get_scored_best_move(child, child_bounds, child_depth, false, deadline)
+                },
+            );
+            match tie {
+                Timeout => break,
+                Low | High => continue,
+                Result(..) => {}
+            }
+            let Some(result) = self.score_move(board, mv, depth, deadline) else {
+                break;
+            };
+            if compare_results(is_maxing, result, chosen.1) == Ordering::Greater {
+                chosen = (mv, result);
+            }
+        }
+
+        chosen
+    }
+
+    /// Samples a root move with probability proportional to
+    /// `exp(score / temperature)`, scoring each move to the depth of `best`.
+    /// Falls back to the best move if scoring runs past the search's
+    /// deadline.
+    fn sample_move(&mut self, board: &MyBoard, best: &DepthInfo, temperature: f32) -> ChessMove {
+        let deadline = self.search_deadline();
+        let moves: Vec<_> = board.all_moves().collect();
+        let mut scores = Vec::with_capacity(moves.len());
+        for &mv in &moves {
+            let Some((score, _)) = self.score_move(board, mv, best.depth, deadline) else {
+                return best.best_move;
+            };
+            scores.push(match board.get_side_to_move() {
+                White => score.to_num::<f32>(),
+                Black => 1.0 - score.to_num::<f32>(),
+            });
+        }
+
+        // Subtract the maximum so that the exponentials can't overflow
+        let max = scores.iter().copied().fold(f32::MIN, f32::max);
+        let weights: Vec<_> = scores
+            .iter()
+            .map(|s| ((s - max) / temperature).exp())
+            .collect();
+
+        let (_, rng) = self
+            .temperature
+            .as_mut()
+            .expect("temperature should be set");
+        let mut remaining = rng() * weights.iter().sum::<f32>();
+        for (mv, weight) in moves.iter().zip(&weights) {
+            if remaining < *weight {
+                return *mv;
+            }
+            remaining -= weight;
+        }
+        *moves.last().unwrap()
+    }
+
+    /// If the side to move is certain to have its king captured, returns the
+    /// move that delays the capture for the most plies. Returns `None` if the
+    /// search didn't find a forced loss.
+    ///
+    /// `get_move` already makes this choice in lost positions, so this is for
+    /// callers that want to know whether the position is lost.
+    pub fn best_defensive_move(&mut self, board: &MyBoard) -> Option<ChessMove> {
+        let is_maxing = board.get_side_to_move() == White;
+        let best = self
+            .iterative_deepening(board, None)
+            .pop()
+            .expect("could not search the board in the time/lookahead given");
+        if best.plies_to_result.is_none() || !is_favourable(!is_maxing, best.score) {
+            return None;
+        }
+        Some(self.best_by_distance(board, &best).0)
+    }
+
+    /// How much the bonus roll after the best move matters: the absolute
+    /// difference between the scores of the bonus and no-bonus boards after
+    /// it. This is large when the bonus decides the game, e.g. when a bonus
+    /// would let the side to move capture the king.
+    ///
+    /// The children are searched to the depth of the best move's search,
+    /// within the same time and node limits as the search for the best move.
+    /// Returns `None` if either search runs out of time (or nodes) before
+    /// completing a depth.
+    pub fn bonus_swing(&mut self, board: &MyBoard) -> Option<Score> {
+        let best = self.iterative_deepening(board, None).pop()?;
+        let deadline = self.search_deadline();
+        let ((b_score, _), (nb_score, _)) =
+            self.score_children(board, best.best_move, best.depth, deadline)?;
+        Some(b_score.abs_diff(nb_score))
+    }
+
+    /// Like `get_move`, but also avoids draws by repetition in `game`. If the
+    /// best move lets the game be drawn by repetition while the side to move
+    /// is winning, the best move that can't repeat is played instead, as
+    /// long as it is also winning and scores within 5% of the best move.
+    /// The moves are ranked within the time and node limits of the search
+    /// for the best move.
+    pub fn get_move_in_game(&mut self, game: &Game) -> ChessMove {
+        let board = game.board();
+        let best_move = self.get_move(board);
+        if !allows_repetition(game, best_move) {
+            return best_move;
+        }
+
+        let is_maxing = board.get_side_to_move() == White;
+        let moves: Vec<_> = board.all_moves().collect();
+        let deadline = self.search_deadline();
+        let ranked = self.rank_moves_until(board, &moves, deadline);
+        let Some(&(_, (best_score, _))) = ranked.iter().find(|(mv, _)| *mv == best_move) else {
+            return best_move;
+        };
+        if !is_favourable(is_maxing, best_score) {
+            return best_move;
+        }
+
+        let margin = ONE / 20;
+        let Some((mv, result)) = ranked.into_iter().find(|&(mv, (score, _))| {
+            is_favourable(is_maxing, score)
+                && best_score.abs_diff(score) <= margin
+                && !allows_repetition(game, mv)
+        }) else {
+            return best_move;
+        };
+        // The best move was a reasonable alternative, so the move played
+        // isn't forced, and the result is the one the new move forces
+        self.last_forced_result = forced_result(result);
+        self.last_move_forced = false;
+        mv
+    }
+
+    /// Starts pondering on the opponent's turn, where `board` is the position
+    /// the opponent has to move in. The opponent's reply is predicted from
+    /// the position table, and both the bonus and no-bonus positions after
+    /// it are pondered. If there is no prediction, `board` itself is
+    /// pondered instead.
+    ///
+    /// This doesn't search anything by itself, so that it works without
+    /// threads (e.g. on the web). The caller should call `ponder` repeatedly
+    /// until the opponent moves, and then `stop_ponder`. The searches fill
+    /// the position table, so the next `get_move` is faster if the
+    /// prediction was right.
+    pub fn start_ponder(&mut self, board: &MyBoard) {
+        self.ponder_boards.clear();
+        if !board.get_status().is_in_progress() {
+            return;
+        }
+
+        match self.table_move(board) {
+            Some(mv) => {
+                let (b_board, nb_board) = self.next_boards(board, mv, true);
+                self.ponder_boards.extend([nb_board, b_board]);
+            }
+            None => self.ponder_boards.push(*board),
+        }
+        self.ponder_boards
+            .retain(|board| board.get_status().is_in_progress());
+    }
+
+    /// Stops pondering, so that `ponder` does nothing until `start_ponder`
+    /// is called again. The position table is kept.
+    pub fn stop_ponder(&mut self) { self.ponder_boards.clear(); }
+
+    /// Whether `start_ponder` has been called without `stop_ponder`
+    pub fn is_pondering(&self) -> bool { !self.ponder_boards.is_empty() }
+
+    /// Spends about `nodes` nodes pondering the positions chosen by
+    /// `start_ponder`, split evenly between them. Each call searches the
+    /// positions again from the shallowest depth, but the depths reached
+    /// before are mostly answered by the position table, so repeated calls
+    /// search deeper. This doesn't report depths to `on_depth`, or change
+    /// `last_search_stats` or the timing used for `get_move_timed`.
+    pub fn ponder(&mut self, nodes: u64) {
+        let boards = self.ponder_boards.clone();
+        for board in &boards {
+            self.background_search(board, nodes / boards.len() as u64);
+        }
+    }
+
+    /// Runs iterative deepening on `board` within `node_limit` nodes, only to
+    /// fill the position table. Nothing is reported, and the statistics,
+    /// timing and deadline of the last search are kept. A stop isn't
+    /// cleared, so it still applies to the next search.
+    fn background_search(&mut self, board: &MyBoard, node_limit: u64) {
+        let on_depth = self.on_depth.take();
+        let stats = self.stats;
+        let depth_nodes = std::mem::take(&mut self.depth_nodes);
+        let depth_millis = self.depth_millis;
+        let search_deadline = self.search_deadline;
+        let previous_limit = self.node_limit;
+
+        let deadline = self.start_search(Some(node_limit));
+        self.search_until(board, deadline);
+
+        self.on_depth = on_depth;
+        self.stats = stats;
+        self.depth_nodes = depth_nodes;
+        self.depth_millis = depth_millis;
+        self.search_deadline = search_deadline;
+        self.node_limit = previous_limit;
+    }
+
+    /// Returns true if every move other than `best_move` scores worse than
+    /// `best_score` by more than `margin` when searched to `depth`. This
+    /// shares the deadline of the search, and returns false if it runs out.
+    fn is_forced(
+        &mut self, board: &MyBoard, best_move: ChessMove, best_score: Score, depth: u8,
+        margin: Score,
+    ) -> bool {
+        let deadline = self.search_deadline();
+        let is_maxing = board.get_side_to_move() == White;
+        let alternatives: Vec<_> = board.all_moves().filter(|mv| *mv != best_move).collect();
+        let mut close_alternatives = 0;
+
+        for mv in alternatives {
+            let Some((score, _)) = self.score_move(board, mv, depth, deadline) else {
+                return false;
+            };
+            let drop = if is_maxing {
+                best_score.saturating_sub(score)
+            } else {
+                score.saturating_sub(best_score)
+            };
+            if drop <= margin {
+                close_alternatives += 1;
+            }
+        }
+
+        self.logger.log(
+            4,
+            &format!(
+                "{} alternatives within the forced margin",
+                close_alternatives
+            ),
+        );
+
+        close_alternatives == 0
+    }
+
+    /// Analyses the position with iterative deepening, stopping once `nodes`
+    /// nodes have been searched (or the time limit or maximum lookahead is
+    /// reached). Returns the information for each completed depth, in
+    /// increasing order of depth.
+    pub fn analyze(&mut self, board: &MyBoard, nodes: u64) -> Vec<DepthInfo> {
+        self.iterative_deepening(board, Some(nodes))
+    }
+
+    /// Searches the position with iterative deepening up to `max_depth`
+    /// (rather than the engine's maximum lookahead), returning the depth,
+    /// best move and score for each depth that completed before the time
+    /// limit. This shows how the engine's choice evolves as it searches
+    /// deeper, e.g. to find positions where it oscillates between moves.
+    pub fn best_move_per_depth(
+        &mut self, board: &MyBoard, max_depth: u8,
+    ) -> Vec<(u8, ChessMove, Score)> {
+        if max_depth > self.max_lookahead {
+            self.branch_info = BranchInfo::new(max_depth);
+        }
+        let max_lookahead = std::mem::replace(&mut self.max_lookahead, max_depth);
+        let depth_infos = self.iterative_deepening(board, None);
+        self.max_lookahead = max_lookahead;
+
+        depth_infos
+            .into_iter()
+            .map(|info| (info.depth, info.best_move, info.score))
+            .collect()
+    }
+
+    /// Runs a search up to `depth` on each of `boards` that is still in
+    /// progress, to populate the position table before a real search. This
+    /// is useful when analysing related positions (e.g. consecutive positions
+    /// of a game), since they share subtrees and the table improves the move
+    /// ordering.
+    pub fn warm(&mut self, boards: &[MyBoard], depth: u8) {
+        for board in boards.iter().filter(|b| b.get_status().is_in_progress()) {
+            self.best_move_per_depth(board, depth);
+        }
+    }
+
+    /// Evaluates each position of a recorded game that is still in progress,
+    /// searching up to `nodes` nodes for each. This shows how the advantage
+    /// swung over the game. Positions where not even the first depth could be
+    /// completed within the budget are evaluated statically.
+    pub fn evaluate_game(&mut self, boards: &[MyBoard], nodes: u64) -> Vec<Score> {
+        boards
+            .iter()
+            .filter(|board| board.get_status().is_in_progress())
+            .map(|board| match self.analyze(board, nodes).last() {
+                Some(info) => info.score,
+                None => self.static_evaluator.evaluate(board),
+            })
+            .collect()
+    }
+
+    /// Resets the debug info, which is logged for each depth
+    fn reset_debug_info(&mut self) {
+        self.iter_deep_lookups = 0;
+        self.iter_deep_failures = 0;
+        self.position_table.reset_debug_info();
+        self.branch_info.reset_statistics();
+    }
+
+    /// Starts the clock and the node count for a search, which times out
+    /// after `node_limit` or `max_nodes` nodes, whichever is smaller
+    fn start_search(&mut self, node_limit: Option<u64>) -> Deadline {
+        let deadline = Deadline::from_now(self.max_time);
+        self.search_deadline = Some(deadline);
+        self.nodes = 0;
+        self.node_limit = match (node_limit, self.max_nodes) {
+            (Some(limit), Some(max)) => Some(limit.min(max)),
+            (limit, max) => limit.or(max),
+        };
+        deadline
+    }
+
+    /// The deadline of the current search, for the follow-up searches of its
+    /// result
+    fn search_deadline(&self) -> Deadline {
+        self.search_deadline
+            .expect("a search should have been started")
+    }
+
+    /// Clears the stop flag if it stopped the search that just finished. The
+    /// search's deadline is then expired, so that the follow-up searches of
+    /// its result stop straight away too.
+    fn consume_stop(&mut self) {
+        if self.stop.swap(false, Relaxed) {
+            self.search_deadline = Some(Deadline::from_now(0));
+        }
+    }
+
+    /// Whether the search should time out, because the deadline has passed,
+    /// the node limit has been reached, or the search was stopped
+    fn should_stop(&self, deadline: Deadline) -> bool {
+        deadline.expired()
+            || self.node_limit.is_some_and(|limit| self.nodes >= limit)
+            || self.stop.load(Relaxed)
+    }
+
+    /// Creates an engine for another thread with the same search options,
+    /// which uses `position_table` and times out once `stop` is set
+    fn thread_engine(
+        &self, position_table: ShardedPositionTable<ScoreInfo>, stop: &Arc<AtomicBool>,
+    ) -> AlphaBeta {
+        let static_evaluator = self
+            .static_evaluator
+            .clone_for_thread()
+            .expect("the evaluator should be checked when adding threads");
+        AlphaBeta {
+            drawish_cutoff: self.drawish_cutoff,
+            draw_blend_margin: self.draw_blend_margin,
+            quiescence_plies: self.quiescence_plies,
+            null_move_reduction: self.null_move_reduction,
+            bonus_chance: self.bonus_chance,
+            aspiration_window: self.aspiration_window,
+            stop: Arc::clone(stop),
+            ..AlphaBeta::from_parts(
+                static_evaluator,
+                self.max_lookahead,
+                self.max_time,
+                self.is_pessimistic,
+                self.is_focussed,
+                position_table,
+                Logger::new(0),
+            )
+        }
+    }
+
+    /// Creates the engines for the extra threads, which share the position
+    /// table and search options, and time out once `stop` is set
+    fn helpers(&self, stop: &Arc<AtomicBool>) -> Vec<AlphaBeta> {
+        (1..self.threads)
+            .map(|_| self.thread_engine(self.position_table.clone(), stop))
+            .collect()
+    }
+
+    /// Runs iterative deepening with the root moves split between the root
+    /// threads, returning the information for each depth that was completed
+    /// before the deadline (or node limit, which is shared equally between
+    /// the threads). Thread `i` always scores the moves whose index in
+    /// `all_moves` is `i` more than a multiple of the number of threads, so
+    /// that its table is useful for the next depth.
+    fn search_root_moves(&mut self, board: &MyBoard, deadline: Deadline) -> Vec<DepthInfo> {
+        let node_limit = self.node_limit;
+        let threads = self.root_threads;
+        for table in &self.root_tables {
+            table.new_generation();
+        }
+        // The tables are lent to the workers, so that each is the only
+        // handle to its table for the search
+        let tables = std::iter::once(std::mem::take(&mut self.position_table))
+            .chain(std::mem::take(&mut self.root_tables));
+        let mut workers: Vec<_> = tables
+            .map(|table| AlphaBeta {
+                node_limit: node_limit.map(|limit| limit / threads as u64),
+                ..self.thread_engine(table, &self.stop)
+            })
+            .collect();
+
+        let is_maxing = board.get_side_to_move() == White;
+        let moves: Vec<_> = board.all_moves().collect();
+        self.reset_depth_counts();
+        let mut depth_infos = Vec::new();
+
+        for depth in 2..=self.max_lookahead {
+            if !self.start_depth(depth, &depth_infos, deadline) {
+                break;
+            }
+
+            let scored: Vec<_> = std::thread::scope(|scope| {
+                let handles: Vec<_> = workers
+                    .iter_mut()
+                    .enumerate()
+                    .map(|(i, worker)| {
+                        let moves = &moves;
+                        scope.spawn(move || {
+                            moves
+                                .iter()
+                                .skip(i)
+                                .step_by(threads)
+                                .map(|&mv| worker.score_move(board, mv, depth, deadline))
+                                .collect::<Option<Vec<_>>>()
+                        })
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().expect("root thread panicked"))
+                    .collect()
+            });
+            self.nodes = workers.iter().map(|worker| worker.nodes).sum();
+
+            let Some(scored) = scored.into_iter().collect::<Option<Vec<_>>>() else {
+                self.time_out_depth(depth);
+                break;
+            };
+
+            // Going through the moves in order, only replacing the best move
+            // with a strictly better one, so that ties don't depend on the
+            // threads
+            let (index, (score, plies)) = (0..moves.len())
+                .map(|index| (index, scored[index % threads][index / threads]))
+                .reduce(|best, next| {
+                    if compare_results(is_maxing, next.1, best.1) == Ordering::Greater {
+                        next
+                    } else {
+                        best
+                    }
+                })
+                .expect("there should be a legal move");
+            let best_move = moves[index];
+
+            let info = DepthInfo {
+                depth,
+                best_move,
+                score,
+                plies_to_result: plies,
+                pv: workers[index % threads].principal_variation(board, best_move, depth),
+                nodes: self.nodes,
+                elapsed_millis: deadline.elapsed_millis(),
+            };
+            self.finish_depth(&mut depth_infos, info);
+        }
+
+        self.stats.nodes = self.nodes;
+
+        let mut tables = workers.into_iter().map(|worker| worker.position_table);
+        self.position_table = tables.next().expect("there should be a root thread");
+        self.root_tables = tables.collect();
+
+        depth_infos
+    }
+
+    /// Runs the iterative deepening search, returning the information for
+    /// each depth that was completed before the deadline (or node limit,
+    /// which is the smaller of `node_limit` and `max_nodes`).
+    /// A stop that ends the search is cleared afterwards.
+    fn iterative_deepening(&mut self, board: &MyBoard, node_limit: Option<u64>) -> Vec<DepthInfo> {
+        let deadline = self.start_search(node_limit);
+        let depth_infos = self.search_until(board, deadline);
+        self.consume_stop();
+        depth_infos
+    }
+
+    /// Runs iterative deepening for a search started with `start_search`.
+    /// With several threads, the helper engines search alongside this one
+    /// until it finishes. With several root threads, the root moves are
+    /// split between them instead.
+    fn search_until(&mut self, board: &MyBoard, deadline: Deadline) -> Vec<DepthInfo> {
+        let node_limit = self.node_limit;
+        if self.root_threads > 1 {
+            return self.search_root_moves(board, deadline);
+        }
+        if self.threads == 1 {
+            return self.search_depths(board, node_limit, 2, deadline);
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut helpers = self.helpers(&stop);
+        let depth_infos = std::thread::scope(|scope| {
+            for (i, helper) in helpers.iter_mut().enumerate() {
+                scope.spawn(move || helper.search_depths(board, None, 2 + i as u8 % 2, deadline));
+            }
+            let depth_infos = self.search_depths(board, node_limit, 2, deadline);
+            stop.store(true, Relaxed);
+            depth_infos
+        });
+        self.stats.helper_nodes = helpers.iter().map(|helper| helper.nodes).sum();
+        depth_infos
+    }
+
+    /// The best move stored in the position table for `board`, if there is
+    /// one and it is legal
+    fn table_move(&mut self, board: &MyBoard) -> Option<ChessMove> {
+        self.position_table
+            .get_lenient(board)
+            .and_then(|info| info.best_move)
+            // The table could have a colliding entry, so check the move
+            .filter(|mv| board.moves_from(mv.get_source()).contains(mv))
+    }
+
+    /// Runs iterative deepening from `first_depth` up to the lookahead on
+    /// this thread. The node limit stays in place afterwards, for the
+    /// follow-up searches of the result.
+    fn search_depths(
+        &mut self, board: &MyBoard, node_limit: Option<u64>, first_depth: u8, deadline: Deadline,
+    ) -> Vec<DepthInfo> {
+        self.node_limit = node_limit;
+        self.reset_depth_counts();
+
+        let mut depth_infos: Vec<DepthInfo> = Vec::new();
+
+        for depth in first_depth..=self.max_lookahead {
+            self.reset_debug_info();
+
+            if !self.start_depth(depth, &depth_infos, deadline) {
+                break;
+            }
+
+            // The widths of the aspiration window below and above the
+            // previous score
+            let mut window = self.aspiration_window.map(|width| (width, width));
+
+            let result = loop {
+                let bounds = match (window, depth_infos.last()) {
+                    (Some((below, above)), Some(prev)) => Bounds::around(prev.score, below, above),
+                    _ => Bounds::widest(),
+                };
+                match (
+                    self.get_scored_best_move(board, bounds, depth, true, deadline),
+                    window.as_mut(),
+                ) {
+                    (Low, Some((below, _))) => *below = below.saturating_mul_int(2),
+                    (High, Some((_, above))) => *above = above.saturating_mul_int(2),
+                    (result, _) => break result,
+                }
+                self.stats.aspiration_researches += 1;
+                self.logger
+                    .log(4, &format!("depth {}: aspiration window failed", depth));
+            };
+
+            let (s, mv, plies) = match result {
+                Result(s, Some(mv), plies) => (s, mv, plies),
+                Timeout => {
+                    self.time_out_depth(depth);
+                    break;
+                }
+                _ => panic!("actual move should be returned"),
+            };
+
+            if self.verify_tt {
+                self.verify_table_result(board, depth, s, deadline);
+            }
+
+            let info = DepthInfo {
+                depth,
+                best_move: mv,
+                score: s,
+                plies_to_result: plies,
+                pv: self.principal_variation(board, mv, depth),
+                nodes: self.nodes,
+                elapsed_millis: deadline.elapsed_millis(),
+            };
+            self.finish_depth(&mut depth_infos, info);
+            self.log_info();
+
+            if self.is_confident(board, &depth_infos) {
+                self.logger
+                    .log(4, &format!("depth {}: committing to move early", depth));
+                break;
+            }
+        }
+
+        self.stats.nodes = self.nodes;
+
+        depth_infos
+    }
+
+    /// Resets the counts kept for each depth, for a new run of iterative
+    /// deepening
+    fn reset_depth_counts(&mut self) {
+        self.nodes = 0;
+        self.depth_nodes.clear();
+        self.depth_millis = 0;
+        self.stats = SearchStats::default();
+    }
+
+    /// Starts timing `depth`, unless there is a soft deadline and the depth
+    /// isn't expected to finish before it, in which case `false` is returned.
+    /// The first depth is always started.
+    fn start_depth(&mut self, depth: u8, depth_infos: &[DepthInfo], deadline: Deadline) -> bool {
+        if self.soft_deadline && !depth_infos.is_empty() {
+            let expected_millis = self
+                .estimate_next_depth_time()
+                .map_or(0, |time| time.as_millis() as u64);
+            if deadline.elapsed_millis() + expected_millis > self.max_time {
+                self.logger
+                    .log(4, &format!("depth {}: not expected to finish", depth));
+                return false;
+            }
+        }
+        self.logger.time_start(4, &format!("depth {}", depth));
+        true
+    }
+
+    /// Logs that `depth` timed out before it finished
+    fn time_out_depth(&mut self, depth: u8) {
+        self.logger.log(4, &format!("depth {}: timeout", depth));
+        self.logger.time_end(4, &format!("depth {}", depth));
+    }
+
+    /// Adds `info` for a depth that finished to `depth_infos` and reports it,
+    /// updating the counts used to estimate how long the next depth will take
+    fn finish_depth(&mut self, depth_infos: &mut Vec<DepthInfo>, info: DepthInfo) {
+        self.logger.log(
+            4,
+            &format!(
+                "depth {}: move {} with score {}",
+                info.depth, info.best_move, info.score
+            ),
+        );
+        self.depth_nodes
+            .push(info.nodes - depth_infos.last().map_or(0, |last| last.nodes));
+        self.depth_millis = info.elapsed_millis;
+        self.stats.depth = info.depth;
+        let depth = info.depth;
+        depth_infos.push(info);
+        self.report_depth(depth_infos);
+        self.logger.time_end(4, &format!("depth {}", depth));
+    }
+
+    /// Passes the last of `depth_infos` to the `on_depth` callback, if there
+    /// is one
+    fn report_depth(&mut self, depth_infos: &[DepthInfo]) {
+        if let (Some(on_depth), Some(info)) = (&mut self.on_depth, depth_infos.last()) {
+            on_depth(info.clone());
+        }
+    }
+
+    /// Searches `board` to `depth` again with the position table ignored,
+    /// panicking if the score differs from `score` by more than `DELTA`.
+    /// Nothing is checked if the search times out.
+    fn verify_table_result(
+        &mut self, board: &MyBoard, depth: u8, score: Score, deadline: Deadline,
+    ) {
+        self.table_disabled = true;
+        let result = self.get_scored_best_move(board, Bounds::widest(), depth, true, deadline);
+        self.table_disabled = false;
+        if let Result(verified, _, _) = result {
+            assert!(
+                verified.abs_diff(score) <= DELTA,
+                "depth {} scored {} with the position table, but {} without it",
+                depth,
+                score,
+                verified
+            );
+        }
+    }
+
+    /// Whether early commit is enabled and the last two depths agree on a
+    /// move that scores above the threshold
+    fn is_confident(&self, board: &MyBoard, depth_infos: &[DepthInfo]) -> bool {
+        let (Some(threshold), [.., prev, last]) = (self.early_commit, depth_infos) else {
+            return false;
+        };
+        let score = match board.get_side_to_move() {
+            White => last.score,
+            Black => ONE - last.score,
+        };
+        prev.best_move == last.best_move && score >= threshold
+    }
+
+    /// Gets a move with a time budget based on the clock, rather than
+    /// `max_time`. The budget is an even share of `remaining_ms` over the
+    /// moves expected to be left in the game, plus `increment_ms`, but never
+    /// more than half of what remains. The search also stops before
+    /// starting a depth that isn't expected to finish within the budget,
+    /// rather than starting it and timing out.
+    pub fn get_move_timed(
+        &mut self, board: &MyBoard, remaining_ms: u64, increment_ms: u64,
+    ) -> ChessMove {
+        let budget = move_time_budget(remaining_ms, increment_ms);
+        self.logger
+            .log(3, &format!("Using a time budget of {}ms", budget));
+        self.last_time_budget = Some(budget);
+
+        let max_time = std::mem::replace(&mut self.max_time, budget);
+        self.soft_deadline = true;
+        let mv = self.get_move(board);
+        self.soft_deadline = false;
+        self.max_time = max_time;
+        mv
+    }
+
+    /// The time budget in milliseconds computed by the last call to
+    /// `get_move_timed`, if there has been one
+    pub fn last_time_budget(&self) -> Option<u64> { self.last_time_budget }
+
+    /// Estimates how long searching one depth past the deepest depth
+    /// completed in the last search would take, by extrapolating the growth
+    /// in nodes between depths. Returns `None` until at least two depths have
+    /// been completed.
+    pub fn estimate_next_depth_time(&self) -> Option<Duration> {
+        extrapolate_depth_time(&self.depth_nodes, self.depth_millis)
+    }
+
+    /// Recovers the principal variation starting with `first_move` from the
+    /// best moves stored in the position table. After each move, the no-bonus
+    /// continuation is followed.
+    fn principal_variation(
+        &mut self, board: &MyBoard, first_move: ChessMove, depth: u8,
+    ) -> Vec<ChessMove> {
+        let mut pv = vec![first_move];
+        let (_, mut board) = self.next_boards(board, first_move, true);
+
+        while pv.len() < depth as usize && board.get_status().is_in_progress() {
+            let Some(mv) = self.table_move(&board) else {
+                break;
+            };
+            pv.push(mv);
+            board = self.next_boards(&board, mv, true).1;
+        }
+
+        pv
+    }
+
     fn update_table_for_result(
         &mut self, board: &MyBoard, depth: u8, bounds: Bounds, result: &SearchResult,
     ) {
@@ -292,7 +1914,9 @@ impl AlphaBeta {
         // might be referencing table entries which the old result couldn't.
         // This could lead to incompatible ranges.
         let new = match result {
-            Result(score, _) => ScoreInfo::from_score(*score),
+            Result(score, mv, plies) => ScoreInfo::from_score(*score)
+                .with_best_move(*mv)
+                .with_plies_to_result(*plies),
             Low => ScoreInfo::from_max_score(
                 bounds
                     .min
@@ -305,69 +1929,221 @@ impl AlphaBeta {
             ),
             Timeout => return,
         };
-        self.position_table.insert(board, depth, new);
+        self.insert_into_table(board, depth, new);
+    }
+
+    /// Inserts into the position table, unless it is disabled
+    fn insert_into_table(&mut self, board: &MyBoard, depth: u8, info: ScoreInfo) {
+        if !self.table_disabled {
+            self.position_table.insert(board, depth, info);
+        }
     }
 }
 
+/// The time budget in milliseconds for a move, given the time remaining on the
+/// clock and the increment gained after the move. See `get_move_timed`.
+fn move_time_budget(remaining_ms: u64, increment_ms: u64) -> u64 {
+    (remaining_ms / EXPECTED_MOVES_LEFT + increment_ms).min(remaining_ms / 2)
+}
+
+/// Estimates the time for the depth after those in `depth_nodes`, assuming
+/// that the number of nodes keeps growing by the same factor, and that nodes
+/// are searched at the same rate as they were over `elapsed_millis`
+fn extrapolate_depth_time(depth_nodes: &[u64], elapsed_millis: u64) -> Option<Duration> {
+    let [.., previous, last] = depth_nodes else {
+        return None;
+    };
+    let branching_factor = *last as f64 / (*previous).max(1) as f64;
+    let total_nodes: u64 = depth_nodes.iter().sum();
+    let millis_per_node = elapsed_millis.max(1) as f64 / total_nodes.max(1) as f64;
+    Some(Duration::from_secs_f64(
+        *last as f64 * branching_factor * millis_per_node / 1000.0,
+    ))
+}
+
+/// The number of plies until the end of the game after a move, given the
+/// number of plies after the no-bonus and bonus boards. The result is only
+/// forced if it is forced on both boards.
+fn plies_after(nb_plies: Option<u8>, b_plies: Option<u8>) -> Option<u8> {
+    let (nb_plies, b_plies) = (nb_plies?, b_plies?);
+    Some(nb_plies.max(b_plies).saturating_add(1))
+}
+
+/// Whether `mv` is worth searching in the quiescence search, i.e. it
+/// captures, promotes or (if `include_threats` is set) attacks the enemy king
+fn is_loud(board: &MyBoard, mv: ChessMove, include_threats: bool) -> bool {
+    if matches!(
+        board.move_kind(mv),
+        MoveKind::Capture(_) | MoveKind::Promotion { .. }
+    ) {
+        return true;
+    } else if !include_threats {
+        return false;
+    }
+    let mut after = *board;
+    after.apply_move_unchecked(mv);
+    after.in_check(!board.get_side_to_move())
+}
+
+/// Whether playing `mv` in `game` draws by repetition after either roll
+fn allows_repetition(game: &Game, mv: ChessMove) -> bool {
+    [false, true].iter().any(|&is_bonus| {
+        let mut next = game.clone();
+        next.play(mv, is_bonus);
+        next.status() == Status::Draw && next.board().get_status().is_in_progress()
+    })
+}
+
+/// Whether `score` is better than a draw for the side to move
+fn is_favourable(is_maxing: bool, score: Score) -> bool {
+    if is_maxing {
+        score > ONE / 2
+    } else {
+        score < ONE / 2
+    }
+}
+
+/// The single result that a forced score stands for. A forced score can also
+/// mix results, when they differ between the bonus rolls, in which case this
+/// is `None`.
+fn forced_status(score: Score) -> Option<Status> {
+    [
+        (ONE, Status::Win(White)),
+        (ZERO, Status::Win(Black)),
+        (ONE / 2, Status::Draw),
+    ]
+    .iter()
+    .find(|(value, _)| score.abs_diff(*value) <= DELTA)
+    .map(|&(_, status)| status)
+}
+
+/// The result forced by a move with the given `(score, plies_to_result)`,
+/// along with the number of plies until the game ends, if there is one
+fn forced_result((score, plies): (Score, Option<u8>)) -> Option<(Status, u8)> {
+    forced_status(score).zip(plies)
+}
+
+/// Compares two `(score, plies_to_result)` pairs from the perspective of the
+/// side to move, where `Greater` means that `a` is better. Equal scores which
+/// are favourable are better if the game ends sooner, and equal scores which
+/// are unfavourable are better if the game ends later (or isn't forced).
+fn compare_results(is_maxing: bool, a: (Score, Option<u8>), b: (Score, Option<u8>)) -> Ordering {
+    let by_score = if is_maxing {
+        a.0.cmp(&b.0)
+    } else {
+        b.0.cmp(&a.0)
+    };
+    by_score.then_with(|| {
+        let by_speed = match (a.1, b.1) {
+            (Some(a_plies), Some(b_plies)) => b_plies.cmp(&a_plies),
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => Ordering::Equal,
+        };
+        if is_favourable(is_maxing, a.0) {
+            by_speed
+        } else if is_favourable(!is_maxing, a.0) {
+            by_speed.reverse()
+        } else {
+            Ordering::Equal
+        }
+    })
+}
+
 impl Engine for AlphaBeta {
     fn default(static_evaluator: impl StaticEvaluator + 'static) -> Self {
         AlphaBeta::new(static_evaluator, 4, false, false, 10, 10000)
     }
 
-    fn evaluate(&mut self, _board: &MyBoard) -> Score {
-        unimplemented!();
+    fn describe(&self) -> String {
+        format!(
+            "AlphaBeta(evaluator: {}, lookahead: {}, time: {}ms, pessimistic: {}, focussed: {})",
+            self.static_evaluator.describe(),
+            self.max_lookahead,
+            self.max_time,
+            self.is_pessimistic,
+            self.is_focussed
+        )
+    }
+
+    fn evaluate(&mut self, board: &MyBoard) -> Score {
+        if !board.get_status().is_in_progress() {
+            return self.static_evaluator.evaluate(board);
+        }
+        self.iterative_deepening(board, None)
+            .last()
+            .map_or_else(|| self.static_evaluator.evaluate(board), |info| info.score)
+    }
+
+    fn bonus_chance(&self) -> Score { self.bonus_chance }
+
+    fn set_max_lookahead(&mut self, max_lookahead: u8) {
+        assert!(max_lookahead > 0, "lookahead must be positive");
+        assert!(
+            !self.is_focussed || max_lookahead > 1,
+            "lookahead must be greater than 1 if focussed"
+        );
+        self.max_lookahead = max_lookahead;
+        self.branch_info = BranchInfo::new(max_lookahead);
     }
 
+    fn set_max_time(&mut self, max_time: u64) { self.max_time = max_time; }
+
     fn get_move(&mut self, board: &MyBoard) -> ChessMove {
         self.logger
             .log_lazy(5, || format!("Getting move for board:\n{}", board));
 
         self.logger.time_start(2, "full move calculation");
-        let deadline = Deadline::from_now(self.max_time);
 
-        let mut best_move = None;
+        // Entries from searches for earlier moves are kept, since they are
+        // often still useful, but they are the first to be overwritten
+        self.position_table.new_generation();
+        let depth_infos = self.iterative_deepening(board, None);
 
-        for depth in 2..=self.max_lookahead {
-            self.iter_deep_lookups = 0;
-            self.iter_deep_failures = 0;
-            self.position_table.reset_debug_info();
-            self.branch_info.reset_statistics();
-
-            self.logger.time_start(4, &format!("depth {}", depth));
-
-            let (s, mv) =
-                match self.get_scored_best_move(board, Bounds::widest(), depth, true, deadline) {
-                    Result(s, Some(mv)) => (s, mv),
-                    Timeout => {
-                        self.logger.log(4, &format!("depth {}: timeout", depth));
-                        self.logger.time_end(4, &format!("depth {}", depth));
-                        break;
-                    }
-                    _ => panic!("actual move should be returned"),
-                };
+        self.logger.time_end(2, "full move calculation");
 
+        let Some(best) = depth_infos.last() else {
+            // The search was stopped (or ran out of time) before completing
+            // a depth, but a move still has to be played
+            let mv = self
+                .table_move(board)
+                .or_else(|| board.all_moves().next())
+                .expect("a board in progress should have a legal move");
             self.logger
-                .log(4, &format!("depth {}: move {} with score {}", depth, mv, s));
-
-            best_move = Some((mv, s, depth));
-
-            self.logger.time_end(4, &format!("depth {}", depth));
-            self.log_info();
-        }
-
-        self.logger.time_end(2, "full move calculation");
+                .log(2, &format!("No depth completed, so falling back to {}", mv));
+            self.last_forced_result = None;
+            self.last_move_forced = false;
+            return mv;
+        };
+        let (best_move, result) = match self.temperature {
+            Some((temperature, _)) if temperature > 0.0 => {
+                let mv = self.sample_move(board, best, temperature);
+                let result = (best.score, best.plies_to_result);
+                (mv, Some(result).filter(|_| mv == best.best_move))
+            }
+            _ => {
+                let (mv, result) = self.best_by_distance(board, best);
+                (mv, Some(result))
+            }
+        };
+        self.last_forced_result = result.and_then(forced_result);
 
-        let best_move = best_move.expect("could not find a move in the time/lookahead given");
+        self.last_move_forced = match self.forced_margin {
+            Some(margin) => self.is_forced(board, best_move, best.score, best.depth, margin),
+            None => false,
+        };
 
         self.logger.log(
             2,
             &format!(
-                "Reached depth {} and found move {} with score {}",
-                best_move.2, best_move.0, best_move.1
+                "Reached depth {} after {} nodes and found move {} with score {}",
+                best.depth, self.stats.nodes, best_move, best.score
             ),
         );
 
-        best_move.0
+        // A stop that arrived during the follow-up searches was for them
+        self.consume_stop();
+        best_move
     }
 
     fn log_info(&self) {
@@ -383,6 +2159,10 @@ impl Engine for AlphaBeta {
         });
         self.logger.log_lazy(6, || self.position_table.info());
         self.logger.log_lazy(6, || self.branch_info.statistics());
+        self.logger.log_lazy(6, || format!("{:?}", self.stats));
+        if let Some(statistics) = self.static_evaluator.statistics() {
+            self.logger.log(6, &statistics);
+        }
     }
 
     fn get_logger(&self) -> &Logger { &self.logger }
@@ -18,6 +18,14 @@ impl Bounds {
             max: None,
         }
     }
+    /// Bounds which extend `below` under and `above` over `center`, with no
+    /// bound on a side where that would reach 0 or 1
+    pub fn around(center: Score, below: Score, above: Score) -> Bounds {
+        Bounds {
+            min: center.checked_sub(below).filter(|&min| min > ZERO),
+            max: center.checked_add(above).filter(|&max| max < ONE),
+        }
+    }
     pub fn score_too_low(self, score: Score) -> bool {
         if let Some(min) = self.min {
             score <= min
@@ -164,6 +172,23 @@ mod tests {
         assert!(bounds.contains(ONE));
     }
 
+    #[test]
+    fn test_around() {
+        let center = Score::from_num(0.5);
+        let small = Score::from_num(0.125);
+        let bounds = Bounds::around(center, small, small);
+        assert!(bounds.valid());
+        assert_eq!(bounds.min, Some(Score::from_num(0.375)));
+        assert_eq!(bounds.max, Some(Score::from_num(0.625)));
+
+        // Windows reaching past 0 or 1 shouldn't be bounded on that side
+        let bounds = Bounds::around(small, center, center);
+        assert_eq!(bounds.min, None);
+        assert_eq!(bounds.max, Some(Score::from_num(0.625)));
+        let bounds = Bounds::around(ONE - small, small, small);
+        assert_eq!(bounds.max, None);
+    }
+
     #[test]
     fn test_exclusivity() {
         let invalid_bounds = Bounds {
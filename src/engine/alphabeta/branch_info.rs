@@ -3,22 +3,35 @@ use std::ops::{Index, IndexMut};
 /// This represents the information for a certain depth.
 /// - `not_pruned` is the number of nodes that were actually searched at a
 ///   certain depth.
+///   - `table_cutoffs` is the number of nodes (of the `not_pruned` nodes) that
+///     were resolved because a table entry's bound was outside the search
+///     bounds.
+///   - `table_exact` is the number of nodes that were resolved with an exact
+///     score from the table.
 ///   - `expanded` is the number of nodes (of the `not_pruned` nodes) that were
-///      actually expanded (rather than being resolved by a table lookup).
+///     actually expanded (rather than being resolved by a table lookup).
 ///  - `pruned` is the number of nodes that were never searched for a given
 ///    depth, because the were pruned.
+///  - `null_move_prunes` is the number of nodes (of the `expanded` nodes) that
+///    were cut off by a null move rather than by searching their moves.
 #[derive(Clone, Copy)]
 pub struct LayerInfo {
     pub not_pruned: u64,
+    pub table_cutoffs: u64,
+    pub table_exact: u64,
     pub expanded: u64,
     pub prunes: u64,
+    pub null_move_prunes: u64,
 }
 impl LayerInfo {
     pub fn new() -> Self {
         LayerInfo {
             not_pruned: 0,
+            table_cutoffs: 0,
+            table_exact: 0,
             expanded: 0,
             prunes: 0,
+            null_move_prunes: 0,
         }
     }
 }
@@ -49,6 +62,9 @@ impl BranchInfo {
             let np = self.0[depth].not_pruned;
             let p = self.0[depth].prunes;
             let e = self.0[depth].expanded;
+            let c = self.0[depth].table_cutoffs;
+            let x = self.0[depth].table_exact;
+            let n = self.0[depth].null_move_prunes;
             let l = np - e;
 
             if depth == self.0.len() - 1 {
@@ -62,6 +78,10 @@ impl BranchInfo {
                 l,
                 (l * 100).checked_div(np).unwrap_or(0)
             ));
+            s.push_str(&format!(
+                "\t\t\tof these, {} were cut off by a bound and {} had an exact score\n",
+                c, x
+            ));
             s.push_str(&format!(
                 "\t\t{} ({}%) were expanded\n",
                 e,
@@ -72,6 +92,11 @@ impl BranchInfo {
                 p,
                 (p * 100).checked_div(e).unwrap_or(0)
             ));
+            s.push_str(&format!(
+                "\t\t\tof these, {} ({}%) were cut off by a null move\n",
+                n,
+                (n * 100).checked_div(e).unwrap_or(0)
+            ));
         }
 
         s
@@ -0,0 +1,26 @@
+use chess::ChessMove;
+
+use crate::Score;
+
+/// Information about a single completed depth of the iterative deepening
+/// search. This is the kind of information an analysis GUI would display to
+/// show the search progressing.
+#[derive(Clone, Debug)]
+pub struct DepthInfo {
+    /// The depth that was completed
+    pub depth: u8,
+    /// The best move found at this depth
+    pub best_move: ChessMove,
+    /// The score of the best move
+    pub score: Score,
+    /// The number of plies until the game ends, if the best move leads to a
+    /// forced result
+    pub plies_to_result: Option<u8>,
+    /// The principal variation, starting with `best_move`. After each move,
+    /// the no-bonus continuation is followed, since it is the most likely.
+    pub pv: Vec<ChessMove>,
+    /// The total number of nodes searched since the search started
+    pub nodes: u64,
+    /// The time since the search started, in milliseconds
+    pub elapsed_millis: u64,
+}
@@ -0,0 +1,54 @@
+use super::AlphaBeta;
+use crate::{engine::evaluator::StaticEvaluator, my_board::MyBoard};
+
+/// How closely focussed and full searches agree over a set of positions
+#[derive(Clone, Copy, Debug)]
+pub struct FocusComparison {
+    /// The proportion of positions where both searches chose the same move
+    pub agreement: f32,
+    /// The average absolute difference between the scores of the two
+    /// searches
+    pub mean_score_difference: f32,
+}
+
+/// Searches each of `boards` with both a focussed and a full (non-focussed)
+/// search, with the same lookahead and time limit, to measure how much
+/// accuracy focussing costs. `make_evaluator` is called to create the static
+/// evaluator for each engine.
+///
+/// Boards that are no longer in progress are skipped.
+pub fn compare_focussed<E: StaticEvaluator + 'static>(
+    make_evaluator: impl Fn() -> E, boards: &[MyBoard], max_lookahead: u8, max_time: u64,
+) -> FocusComparison {
+    let mut focussed = AlphaBeta::new(make_evaluator(), max_lookahead, false, true, 0, max_time);
+    let mut full = AlphaBeta::new(make_evaluator(), max_lookahead, false, false, 0, max_time);
+
+    let mut positions = 0;
+    let mut agreements = 0;
+    let mut total_difference = 0.0;
+
+    for board in boards.iter().filter(|b| b.get_status().is_in_progress()) {
+        let focussed_info = focussed.iterative_deepening(board, None);
+        let full_info = full.iterative_deepening(board, None);
+        let (Some(focussed_info), Some(full_info)) = (focussed_info.last(), full_info.last())
+        else {
+            panic!("could not search the board in the time given");
+        };
+
+        positions += 1;
+        if focussed_info.best_move == full_info.best_move {
+            agreements += 1;
+        }
+        total_difference += focussed_info
+            .score
+            .abs_diff(full_info.score)
+            .to_num::<f32>();
+    }
+
+    assert!(positions > 0, "need at least one board in progress");
+
+    FocusComparison {
+        agreement: agreements as f32 / positions as f32,
+        mean_score_difference: total_difference / positions as f32,
+    }
+}
@@ -1,3 +1,5 @@
+use chess::ChessMove;
+
 use crate::{Score, ONE, ZERO};
 
 /// Stores a pair of bounds for the score of a given position. Unlike `Bounds`,
@@ -5,10 +7,16 @@ use crate::{Score, ONE, ZERO};
 /// the min and max bounds.
 ///
 /// This is used in the position table to store the results of the search.
+/// When the position was fully expanded, the best move found is also stored,
+/// which allows the principal variation to be recovered from the table.
 #[derive(Clone, Copy, Debug)]
 pub struct ScoreInfo {
     pub min: Score,
     pub max: Score,
+    pub best_move: Option<ChessMove>,
+    /// The number of plies until the game ends, if the score is exact and
+    /// comes from lines that all end the game
+    pub plies_to_result: Option<u8>,
 }
 impl ScoreInfo {
     pub fn actual_score(self) -> Option<Score> {
@@ -22,8 +30,33 @@ impl ScoreInfo {
         ScoreInfo {
             min: score,
             max: score,
+            best_move: None,
+            plies_to_result: None,
+        }
+    }
+    pub fn from_min_score(min: Score) -> Self {
+        ScoreInfo {
+            min,
+            max: ONE,
+            best_move: None,
+            plies_to_result: None,
+        }
+    }
+    pub fn from_max_score(max: Score) -> Self {
+        ScoreInfo {
+            min: ZERO,
+            max,
+            best_move: None,
+            plies_to_result: None,
+        }
+    }
+    pub fn with_best_move(self, best_move: Option<ChessMove>) -> Self {
+        ScoreInfo { best_move, ..self }
+    }
+    pub fn with_plies_to_result(self, plies_to_result: Option<u8>) -> Self {
+        ScoreInfo {
+            plies_to_result,
+            ..self
         }
     }
-    pub fn from_min_score(min: Score) -> Self { ScoreInfo { min, max: ONE } }
-    pub fn from_max_score(max: Score) -> Self { ScoreInfo { min: ZERO, max } }
 }
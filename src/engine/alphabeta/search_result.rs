@@ -7,7 +7,11 @@ pub enum SearchResult {
     /// A score, optionally with a move that leads to that score.
     /// Most of the time, the move will be `None`, but it will be `Some` at the
     /// top level of the search tree.
-    Result(Score, Option<ChessMove>),
+    ///
+    /// The last field is the number of plies until the game ends, if every
+    /// line searched ends the game (e.g. a king capture that can't be
+    /// avoided). It is `None` if any line was cut off by the depth limit.
+    Result(Score, Option<ChessMove>, Option<u8>),
     /// The evaluation of the score is lower than the lower bound
     Low,
     /// The evaluation of the score is higher than the upper bound
@@ -0,0 +1,15 @@
+/// Statistics about the last search, which are useful for tuning the search
+/// parameters
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// The total number of nodes searched
+    pub nodes: u64,
+    /// The deepest depth that was completed
+    pub depth: u8,
+    /// The number of times a depth had to be searched again because its
+    /// score was outside the aspiration window
+    pub aspiration_researches: u32,
+    /// The total number of nodes searched by the helper threads, which
+    /// aren't included in `nodes`
+    pub helper_nodes: u64,
+}
@@ -1,16 +1,22 @@
+use std::sync::Mutex;
+
 use chess::Color;
 
 use super::*;
-use crate::engine::{
-    feature_eval::{FeatureEval, Weights},
-    proportion_count::ProportionCount,
+use crate::{
+    engine::{
+        feature_eval::{FeatureEval, Weights},
+        minimax::Minimax,
+        proportion_count::ProportionCount,
+    },
+    utils::SeededRng,
 };
 
 #[test]
 #[ignore]
 fn test_self_game() {
-    let mut white = AlphaBeta::new(ProportionCount::default(), 2, false, true, 10, 1000);
-    let mut black = AlphaBeta::new(ProportionCount::default(), 2, true, false, 10, 1000);
+    let mut white = AlphaBeta::new(ProportionCount, 2, false, true, 10, 1000);
+    let mut black = AlphaBeta::new(ProportionCount, 2, true, false, 10, 1000);
 
     let mut board = MyBoard::initial_board(Color::White);
 
@@ -34,18 +40,14 @@ fn test_self_game() {
         println!("{}", board);
 
         let ws = Weights {
-            pieces: [[1.0, 3.0, 3.0, 5.0, 9.0, 0.0], [
-                -1.0, -3.0, -3.0, -5.0, -9.0, 0.0,
-            ]],
-            king_danger: [-0.5, 0.5],
             pawn_advancement: [1.0, -1.0],
-            side_to_move: 3.0,
+            ..Weights::standard()
         };
         check_inversions(&board, || {
-            AlphaBeta::new(ProportionCount::default(), 3, false, false, 0, 100000)
+            AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000)
         });
         check_inversions(&board, || {
-            AlphaBeta::new(ProportionCount::default(), 4, false, true, 0, 100000)
+            AlphaBeta::new(ProportionCount, 4, false, true, 0, 100000)
         });
         check_inversions(&board, || {
             AlphaBeta::new(FeatureEval::new(ws, 20.0), 3, false, false, 0, 100000)
@@ -79,7 +81,7 @@ fn check_inversions(board: &MyBoard, engine_creator: impl Fn() -> AlphaBeta) {
         .enumerate()
         .map(|(i, b)| {
             let mut engine = engine_creator();
-            let Result(sc1, _) = engine.get_scored_best_move(
+            let Result(sc1, ..) = engine.get_scored_best_move(
                 b, Bounds::widest(), engine.max_lookahead, false, Deadline::from_now(100000)
             )
             else { panic!("widest bounds should return a result"); };
@@ -111,3 +113,1165 @@ fn check_inversions(board: &MyBoard, engine_creator: impl Fn() -> AlphaBeta) {
         );
     }
 }
+
+#[test]
+fn test_analyze() {
+    let board = MyBoard::initial_board(Color::White);
+
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    let depth_infos = engine.analyze(&board, 1_000_000);
+
+    assert_eq!(
+        depth_infos
+            .iter()
+            .map(|info| info.depth)
+            .collect::<Vec<_>>(),
+        vec![2, 3]
+    );
+    for (prev, next) in depth_infos.iter().zip(depth_infos.iter().skip(1)) {
+        assert!(prev.nodes <= next.nodes);
+    }
+    for info in depth_infos.iter() {
+        assert_eq!(info.pv[0], info.best_move);
+        assert!(info.pv.len() <= info.depth as usize);
+    }
+
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    assert_eq!(
+        depth_infos.last().unwrap().best_move,
+        engine.get_move(&board)
+    );
+
+    // A tiny node budget should stop the search before all depths complete
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    assert!(engine.analyze(&board, 100).len() < 2);
+}
+
+#[test]
+fn test_on_depth() {
+    let board = MyBoard::initial_board(Color::White);
+    let reported = Arc::new(Mutex::new(Vec::new()));
+
+    let sink = Arc::clone(&reported);
+    let mut engine = AlphaBeta::new(ProportionCount, 4, false, false, 0, 100000)
+        .with_on_depth(move |info| sink.lock().unwrap().push(info));
+    let depth_infos = engine.analyze(&board, u64::MAX);
+
+    let reported = reported.lock().unwrap();
+    assert_eq!(
+        reported.iter().map(|info| info.depth).collect::<Vec<_>>(),
+        vec![2, 3, 4]
+    );
+    for (reported, info) in reported.iter().zip(&depth_infos) {
+        assert_eq!(reported.best_move, info.best_move);
+        assert_eq!(reported.nodes, info.nodes);
+    }
+}
+
+#[test]
+fn test_max_nodes() {
+    let board = scandinavian();
+    let unlimited = || AlphaBeta::new(ProportionCount, 5, false, false, 0, 100000);
+
+    let mut engine = unlimited().with_max_nodes(5000);
+    let mv = engine.get_move(&board);
+    let stats = engine.last_search_stats();
+    assert!(stats.nodes <= 5000);
+    assert!(stats.depth < 5);
+
+    // The same limit always gives the same search
+    let mut again = unlimited().with_max_nodes(5000);
+    assert_eq!(again.get_move(&board), mv);
+    assert_eq!(again.last_search_stats(), stats);
+
+    // The smaller of the two node limits applies to `analyze`
+    let mut engine = unlimited().with_max_nodes(5000);
+    assert!(engine.analyze(&board, 1_000_000).last().unwrap().nodes <= 5000);
+    assert!(engine.analyze(&board, 100).is_empty());
+}
+
+#[test]
+fn test_bonus_order_consistency() {
+    let b_chance = crate::bonus_chance();
+    let nb_chance = crate::no_bonus_chance();
+    for i in 0..1000u32 {
+        let b_score = Score::from_bits(i.wrapping_mul(2654435761) % (1 << 31));
+        let nb_score = Score::from_bits(i.wrapping_mul(40503) % (1 << 31));
+        let forwards = b_score * b_chance + nb_score * nb_chance;
+        let backwards = nb_score * nb_chance + b_score * b_chance;
+        assert!(forwards.abs_diff(backwards) <= crate::DELTA);
+        assert_eq!(
+            weighted_score(b_score, b_chance, nb_score, nb_chance),
+            forwards
+        );
+    }
+
+    // Minimax and AlphaBeta should agree exactly on the score of a position
+    let board = scandinavian();
+
+    for depth in 1..=2 {
+        let mut minimax = Minimax::new(ProportionCount, depth + 1);
+        let mut alphabeta = AlphaBeta::new(ProportionCount, depth, false, false, 0, 100000);
+        let Result(ab_score, ..) = alphabeta.get_scored_best_move(
+            &board,
+            Bounds::widest(),
+            depth,
+            false,
+            Deadline::from_now(100000),
+        ) else {
+            panic!("widest bounds should return a result");
+        };
+        assert_eq!(minimax.evaluate(&board), ab_score);
+    }
+}
+
+#[test]
+fn test_bonus_chance() {
+    let board = scandinavian();
+    let default = AlphaBeta::new(ProportionCount, 2, false, false, 0, 100000).evaluate(&board);
+
+    for chance in [ONE / 3, ONE / 5] {
+        let mut minimax = Minimax::new(ProportionCount, 3).with_bonus_chance(chance);
+        let mut alphabeta =
+            AlphaBeta::new(ProportionCount, 2, false, false, 0, 100000).with_bonus_chance(chance);
+        assert_eq!(alphabeta.bonus_chance(), chance);
+        let score = alphabeta.evaluate(&board);
+        assert_eq!(minimax.evaluate(&board), score);
+        assert_ne!(score, default);
+    }
+
+    // The pessimistic adjustment mustn't push a small chance below 0
+    let mut pessimistic =
+        AlphaBeta::new(ProportionCount, 3, true, false, 0, 100000).with_bonus_chance(ONE / 20);
+    pessimistic.get_move(&board);
+}
+
+/// The position after 1. e4 d5, where white can win a pawn with exd5
+fn scandinavian() -> MyBoard {
+    let mut board = MyBoard::initial_board(Color::White);
+    board.apply_move(ChessMove::new(chess::Square::E2, chess::Square::E4, None));
+    board.apply_bonus(false);
+    board.apply_move(ChessMove::new(chess::Square::D7, chess::Square::D5, None));
+    board.apply_bonus(false);
+    board
+}
+
+#[test]
+fn test_rank_moves() {
+    let board = scandinavian();
+    let quiet = ChessMove::new(chess::Square::A2, chess::Square::A3, None);
+    let capture = ChessMove::new(chess::Square::E4, chess::Square::D5, None);
+
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    let ranked = engine.rank_moves(&board, &[quiet, capture]).unwrap();
+
+    assert_eq!(ranked.iter().map(|(mv, _)| *mv).collect::<Vec<_>>(), vec![
+        capture, quiet
+    ]);
+    assert!(ranked[0].1 > ranked[1].1);
+}
+
+#[test]
+fn test_rank_moves_illegal() {
+    let board = scandinavian();
+    let legal = ChessMove::new(chess::Square::A2, chess::Square::A3, None);
+    let illegal = ChessMove::new(chess::Square::E4, chess::Square::E6, None);
+    let empty = ChessMove::new(chess::Square::E3, chess::Square::E4, None);
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    assert_eq!(
+        engine.rank_moves(&board, &[legal, illegal]),
+        Err(MoveError::Illegal)
+    );
+    assert_eq!(
+        engine.rank_moves(&board, &[empty]),
+        Err(MoveError::NotYourPiece)
+    );
+}
+
+#[test]
+fn test_last_move_was_forced() {
+    // Black's queen is hanging, and everything else loses material
+    let board = MyBoard::from_piece_list(Color::White, &[
+        (chess::Square::A1, chess::Piece::King, Color::White),
+        (chess::Square::E4, chess::Piece::Pawn, Color::White),
+        (chess::Square::H8, chess::Piece::King, Color::Black),
+        (chess::Square::D5, chess::Piece::Queen, Color::Black),
+    ]);
+    let capture = ChessMove::new(chess::Square::E4, chess::Square::D5, None);
+
+    let mut engine = AlphaBeta::new(ProportionCount, 2, false, false, 0, 100000)
+        .with_forced_margin(Score::from_num(0.1));
+    assert_eq!(engine.get_move(&board), capture);
+    assert!(engine.last_move_was_forced());
+
+    // The check shares the search's deadline, so nothing is forced once it
+    // has passed
+    let margin = Score::from_num(0.1);
+    let (score, _) = engine
+        .score_move(&board, capture, 2, Deadline::from_now(100000))
+        .unwrap();
+    assert!(engine.is_forced(&board, capture, score, 2, margin));
+    engine.search_deadline = Some(Deadline::from_now(0));
+    assert!(!engine.is_forced(&board, capture, score, 2, margin));
+
+    // There are plenty of reasonable moves at the start of the game
+    engine.get_move(&MyBoard::initial_board(Color::White));
+    assert!(!engine.last_move_was_forced());
+}
+
+#[test]
+fn test_prefers_faster_win() {
+    // Among equal winning scores, the result that ends the game sooner is
+    // better, and a known result is better than one that isn't forced
+    let win_in = |plies| (ONE, Some(plies));
+    assert_eq!(
+        compare_results(true, win_in(1), win_in(3)),
+        std::cmp::Ordering::Greater
+    );
+    assert_eq!(
+        compare_results(true, win_in(3), (ONE, None)),
+        std::cmp::Ordering::Greater
+    );
+    assert_eq!(
+        compare_results(false, (crate::ZERO, Some(5)), (crate::ZERO, Some(2))),
+        std::cmp::Ordering::Less
+    );
+    // Among equal losing scores, the result that ends the game later is better
+    assert_eq!(
+        compare_results(true, (crate::ZERO, Some(4)), (crate::ZERO, Some(2))),
+        std::cmp::Ordering::Greater
+    );
+    assert_eq!(
+        compare_results(false, (ONE, None), (ONE, Some(6))),
+        std::cmp::Ordering::Greater
+    );
+    // Scores still take priority over the number of plies
+    assert_eq!(
+        compare_results(true, (ONE - crate::DELTA, Some(1)), win_in(3)),
+        std::cmp::Ordering::Less
+    );
+
+    // The queen can capture the king straight away, but the rooks and queen
+    // have the king trapped anyway
+    let board = MyBoard::from_piece_list(Color::White, &[
+        (chess::Square::A1, chess::Piece::King, Color::White),
+        (chess::Square::A2, chess::Piece::Pawn, Color::White),
+        (chess::Square::F1, chess::Piece::Rook, Color::White),
+        (chess::Square::G1, chess::Piece::Rook, Color::White),
+        (chess::Square::H1, chess::Piece::Queen, Color::White),
+        (chess::Square::H8, chess::Piece::King, Color::Black),
+    ]);
+    let slow = ChessMove::new(chess::Square::A2, chess::Square::A3, None);
+    let fast = ChessMove::new(chess::Square::H1, chess::Square::H8, None);
+
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    let deadline = Deadline::from_now(100000);
+    assert_eq!(
+        engine.score_move(&board, fast, 3, deadline),
+        Some((ONE, Some(1)))
+    );
+    // Black can keep moving its king with bonus moves, so the slow move
+    // doesn't lead to a result within the search depth
+    assert_eq!(
+        engine.score_move(&board, slow, 3, deadline).unwrap().1,
+        None
+    );
+
+    let ranked = engine.rank_moves(&board, &[slow, fast]).unwrap();
+    assert_eq!(ranked[0], (fast, ONE));
+
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    assert_eq!(engine.get_move(&board), fast);
+}
+
+#[test]
+fn test_best_defensive_move() {
+    // Black's king is trapped in the corner, so every move loses
+    let board = MyBoard::from_piece_list(Color::Black, &[
+        (chess::Square::E4, chess::Piece::King, Color::White),
+        (chess::Square::F1, chess::Piece::Rook, Color::White),
+        (chess::Square::G1, chess::Piece::Rook, Color::White),
+        (chess::Square::H1, chess::Piece::Queen, Color::White),
+        (chess::Square::H8, chess::Piece::King, Color::Black),
+        (chess::Square::A7, chess::Piece::Pawn, Color::Black),
+    ]);
+    let new_engine = || AlphaBeta::new(ProportionCount, 5, false, false, 0, 100000);
+    let result_of = |mv| {
+        new_engine()
+            .score_move(&board, mv, 5, Deadline::from_now(100000))
+            .unwrap()
+    };
+
+    let defensive = new_engine().best_defensive_move(&board).unwrap();
+    // No move scores better for black, or scores the same but ends the game
+    // later
+    let defensive_result = result_of(defensive);
+    for mv in board.all_moves() {
+        assert_ne!(
+            compare_results(false, result_of(mv), defensive_result),
+            std::cmp::Ordering::Greater
+        );
+    }
+
+    // White isn't losing after 1. e4 d5
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    assert_eq!(engine.best_defensive_move(&scandinavian()), None);
+}
+
+#[test]
+fn test_drawish_cutoff() {
+    let hanging_queen = MyBoard::from_piece_list(Color::White, &[
+        (chess::Square::A1, chess::Piece::King, Color::White),
+        (chess::Square::E4, chess::Piece::Pawn, Color::White),
+        (chess::Square::H8, chess::Piece::King, Color::Black),
+        (chess::Square::D5, chess::Piece::Queen, Color::Black),
+    ]);
+    let with_cutoff = || {
+        AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000)
+            .with_drawish_cutoff(Score::from_num(0.05), 0)
+    };
+
+    // The evaluations are far from a draw, so the cutoff shouldn't matter
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    assert_eq!(
+        with_cutoff().get_move(&hanging_queen),
+        engine.get_move(&hanging_queen)
+    );
+
+    // The start of the game is level, so the cutoff should save nodes
+    let initial = MyBoard::initial_board(Color::White);
+    let full = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000)
+        .analyze(&initial, 1_000_000)
+        .last()
+        .unwrap()
+        .nodes;
+    let cut = with_cutoff()
+        .analyze(&initial, 1_000_000)
+        .last()
+        .unwrap()
+        .nodes;
+    assert!(cut < full);
+}
+
+#[test]
+fn test_estimate_next_depth_time() {
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    assert_eq!(engine.estimate_next_depth_time(), None);
+
+    engine.analyze(&MyBoard::initial_board(Color::White), 1_000_000);
+    assert!(engine.estimate_next_depth_time().unwrap() > std::time::Duration::ZERO);
+
+    // Only one depth isn't enough to extrapolate from
+    assert_eq!(extrapolate_depth_time(&[100], 10), None);
+
+    // A larger branching factor means a longer estimate
+    let slow = extrapolate_depth_time(&[100, 2000], 21).unwrap();
+    let fast = extrapolate_depth_time(&[100, 1000], 21).unwrap();
+    assert!(slow > fast);
+    assert!(fast > std::time::Duration::ZERO);
+}
+
+#[test]
+fn test_get_move_timed() {
+    assert_eq!(move_time_budget(3000, 0), 100);
+    assert_eq!(move_time_budget(60000, 2000), 4000);
+    // The increment can't be spent before it has been gained
+    assert_eq!(move_time_budget(100, 5000), 50);
+
+    let board = scandinavian();
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    assert_eq!(engine.last_time_budget(), None);
+    let mv = engine.get_move_timed(&board, 10 * 60 * 1000, 0);
+    assert_eq!(engine.last_time_budget(), Some(20000));
+    assert_eq!(engine.max_time, 100000);
+    assert!(!engine.soft_deadline);
+    assert_eq!(
+        mv,
+        AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000).get_move(&board)
+    );
+
+    // With little time left, the search stops early but still finds a move
+    let mut engine = AlphaBeta::new(ProportionCount, 20, false, false, 0, 100000);
+    let mv = engine.get_move_timed(&board, 3000, 0);
+    assert!(board.all_moves().any(|legal| legal == mv));
+    assert!(engine.last_search_stats().depth < 20);
+}
+
+#[test]
+fn test_reset() {
+    let board = scandinavian();
+    let mut engine =
+        AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000).with_table_capacity(1 << 16);
+    engine.get_move(&MyBoard::initial_board(Color::White));
+    assert!(engine.position_table.items() > 0);
+
+    engine.reset();
+    assert_eq!(engine.position_table.items(), 0);
+    assert_eq!(engine.estimate_next_depth_time(), None);
+
+    let mut fresh =
+        AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000).with_table_capacity(1 << 16);
+    assert_eq!(engine.get_move(&board), fresh.get_move(&board));
+    assert_eq!(engine.last_search_stats(), fresh.last_search_stats());
+}
+
+#[test]
+fn test_stop_handle() {
+    let board = scandinavian();
+    let mut engine = AlphaBeta::new(ProportionCount, 20, false, false, 0, 24 * 60 * 60 * 1000);
+    let stop = engine.stop_handle();
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        stop.store(true, Relaxed);
+    });
+    let mv = engine.get_move(&board);
+    canceller.join().unwrap();
+
+    // The move comes from the last depth completed before the cancellation
+    assert!(board.all_moves().any(|legal| legal == mv));
+    assert!(engine.last_search_stats().depth < 20);
+
+    // The stop was cleared once the search was stopped by it
+    assert!(!engine.stop_handle().load(Relaxed));
+
+    // A stop that arrives before the search starts still cancels it, and a
+    // legal move is returned even though no depth completed
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    engine.stop_handle().store(true, Relaxed);
+    let mv = engine.get_move(&board);
+    assert!(board.all_moves().any(|legal| legal == mv));
+    assert_eq!(engine.last_search_stats().depth, 0);
+    assert!(!engine.stop_handle().load(Relaxed));
+
+    // The next search isn't affected
+    engine.get_move(&board);
+    assert_eq!(engine.last_search_stats().depth, 3);
+}
+
+#[test]
+fn test_set_limits() {
+    let board = scandinavian();
+    let mut engine = AlphaBeta::new(ProportionCount, 2, false, false, 0, 100000);
+    engine.get_move(&board);
+    assert_eq!(engine.last_search_stats().depth, 2);
+
+    // The table from the shallower search is kept and reused
+    engine.set_max_lookahead(3);
+    engine.set_max_time(50000);
+    assert_eq!(engine.max_time, 50000);
+    let mut fresh = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    assert_eq!(engine.get_move(&board), fresh.get_move(&board));
+    assert_eq!(engine.last_search_stats().depth, 3);
+    assert!(engine.last_search_stats().nodes < fresh.last_search_stats().nodes);
+
+    let mut minimax = Minimax::new(ProportionCount, 3);
+    minimax.set_max_lookahead(2);
+    assert_eq!(
+        minimax.evaluate(&board),
+        Minimax::new(ProportionCount, 2).evaluate(&board)
+    );
+}
+
+#[test]
+#[should_panic(expected = "greater than 1 if focussed")]
+fn test_set_lookahead_focussed() {
+    AlphaBeta::new(ProportionCount, 3, false, true, 0, 100000).set_max_lookahead(1);
+}
+
+#[test]
+fn test_small_table() {
+    let board = scandinavian();
+    let full =
+        AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000).analyze(&board, 1_000_000);
+    let small = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000)
+        .with_table_capacity(64)
+        .analyze(&board, 1_000_000);
+    assert_eq!(small.len(), full.len());
+    assert_eq!(small.last().unwrap().score, full.last().unwrap().score);
+
+    // Passing the capacity to the constructor gives the same table
+    let mut constructed = AlphaBeta::with_capacity(ProportionCount, 3, false, false, 0, 100000, 64);
+    assert_eq!(constructed.position_table.capacity(), 64);
+    let constructed = constructed.analyze(&board, 1_000_000);
+    assert_eq!(constructed.len(), small.len());
+    assert_eq!(
+        constructed.last().unwrap().score,
+        small.last().unwrap().score
+    );
+}
+
+#[test]
+fn test_best_move_per_depth() {
+    let board = scandinavian();
+    let new_engine = || AlphaBeta::new(ProportionCount, 4, false, false, 0, 100000);
+
+    let per_depth = new_engine().best_move_per_depth(&board, 4);
+    let depths: Vec<_> = per_depth.iter().map(|(depth, ..)| *depth).collect();
+    assert_eq!(depths, vec![2, 3, 4]);
+
+    let (_, last_move, _) = per_depth.last().unwrap();
+    assert_eq!(*last_move, new_engine().get_move(&board));
+
+    // Searching deeper than the engine's maximum lookahead is allowed
+    let mut shallow = AlphaBeta::new(ProportionCount, 2, false, false, 0, 100000);
+    assert_eq!(shallow.best_move_per_depth(&board, 3).len(), 2);
+}
+
+#[test]
+fn test_table_statistics() {
+    // The opening has lots of transpositions, since most pairs of moves can
+    // be played in either order
+    let mut engine = AlphaBeta::new(ProportionCount, 4, false, false, 0, 100000);
+    engine.get_move(&scandinavian());
+
+    let layers: Vec<_> = (0..=4).map(|depth| engine.branch_info[depth]).collect();
+    assert!(layers.iter().map(|l| l.table_cutoffs).sum::<u64>() > 0);
+    for layer in layers {
+        assert_eq!(
+            layer.not_pruned,
+            layer.table_cutoffs + layer.table_exact + layer.expanded
+        );
+    }
+}
+
+#[test]
+fn test_early_commit() {
+    // White can capture the king with the queen
+    let board = MyBoard::from_piece_list(Color::White, &[
+        (chess::Square::E1, chess::Piece::King, Color::White),
+        (chess::Square::H5, chess::Piece::Queen, Color::White),
+        (chess::Square::E8, chess::Piece::King, Color::Black),
+        (chess::Square::A7, chess::Piece::Pawn, Color::Black),
+    ]);
+    let capture = ChessMove::new(chess::Square::H5, chess::Square::E8, None);
+
+    let mut full = AlphaBeta::new(ProportionCount, 6, false, false, 0, 100000);
+    assert_eq!(full.get_move(&board), capture);
+    assert_eq!(full.last_search_stats().depth, 6);
+
+    let mut early = AlphaBeta::new(ProportionCount, 6, false, false, 0, 100000)
+        .with_early_commit(Score::from_num(0.95));
+    assert_eq!(early.get_move(&board), capture);
+    assert_eq!(early.last_search_stats().depth, 3);
+
+    // The opening isn't clearly winning, so there's no early commit
+    let mut early = AlphaBeta::new(ProportionCount, 4, false, false, 0, 100000)
+        .with_early_commit(Score::from_num(0.95));
+    early.get_move(&scandinavian());
+    assert_eq!(early.last_search_stats().depth, 4);
+}
+
+#[test]
+fn test_describe() {
+    let engine = AlphaBeta::new(ProportionCount, 5, true, false, 0, 250);
+    let description = engine.describe();
+    assert!(description.starts_with("AlphaBeta"));
+    for setting in [
+        "evaluator: ProportionCount",
+        "lookahead: 5",
+        "time: 250ms",
+        "pessimistic: true",
+        "focussed: false",
+    ] {
+        assert!(
+            description.contains(setting),
+            "{} doesn't contain {}",
+            description,
+            setting
+        );
+    }
+
+    let minimax = Minimax::new(ProportionCount, 3);
+    assert_eq!(
+        minimax.describe(),
+        "Minimax(evaluator: ProportionCount, lookahead: 3)"
+    );
+}
+
+#[test]
+fn test_warm() {
+    let board = scandinavian();
+    let best = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000).get_move(&board);
+    let mut next = board;
+    next.apply_move(best);
+    next.apply_bonus(false);
+
+    let cold = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000)
+        .analyze(&next, u64::MAX)
+        .last()
+        .unwrap()
+        .nodes;
+
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    engine.warm(&[board, next], 2);
+    let warm = engine.analyze(&next, u64::MAX).last().unwrap().nodes;
+    assert!(
+        warm < cold,
+        "warm search used {} nodes, cold used {}",
+        warm,
+        cold
+    );
+}
+
+#[test]
+fn test_ordering_entries_match_horizon() {
+    // The queen can capture the king, which ends the game
+    let board = MyBoard::from_fen("7k/8/8/8/8/8/8/Q6K w - - 0 1").unwrap();
+    let mut engine = AlphaBeta::new(ProportionCount, 2, false, false, 0, 100000);
+
+    // Stop straight after the children are ordered, so their entries are left
+    engine.node_limit = Some(1);
+    let result = engine.get_scored_best_move(
+        &board,
+        Bounds::widest(),
+        2,
+        true,
+        Deadline::from_now(100000),
+    );
+    assert_eq!(result, Timeout);
+
+    for mv in board.all_moves() {
+        let (_, nb_board) = engine.next_boards(&board, mv, false);
+        let info = engine.position_table.get(&nb_board, 0).unwrap();
+        let expected = (!nb_board.get_status().is_in_progress()).then_some(0);
+        assert_eq!(info.plies_to_result, expected, "{}", mv);
+    }
+
+    // Near a draw by the fifty move rule, the entries are blended like the
+    // horizon evaluations
+    let board = MyBoard::from_fen("7k/8/8/8/8/8/8/Q6K w - - 40 80").unwrap();
+    let mut engine =
+        AlphaBeta::new(ProportionCount, 2, false, false, 0, 100000).with_draw_blending(20);
+    engine.node_limit = Some(1);
+    let result = engine.get_scored_best_move(
+        &board,
+        Bounds::widest(),
+        2,
+        true,
+        Deadline::from_now(100000),
+    );
+    assert_eq!(result, Timeout);
+
+    let mut blended = false;
+    for mv in board.all_moves() {
+        let (_, nb_board) = engine.next_boards(&board, mv, false);
+        let (expected, _) = engine.leaf_evaluation(&nb_board, false);
+        let info = engine.position_table.get(&nb_board, 0).unwrap();
+        assert_eq!(info.actual_score(), Some(expected), "{}", mv);
+        blended |= expected != engine.static_evaluator.evaluate(&nb_board);
+    }
+    assert!(blended);
+}
+
+#[test]
+fn test_no_moves_draw_at_horizon() {
+    // Black is losing, but Rg1+ forces Kxg1, after which black has no legal
+    // moves, so the game is drawn
+    let board = MyBoard::from_fen("7k/5Q2/8/8/8/8/6rP/7K b - - 0 1")
+        .unwrap()
+        .with_variant(crate::my_board::Variant::Standard);
+    let sacrifice = ChessMove::new(chess::Square::G2, chess::Square::G1, None);
+
+    let mut engine = AlphaBeta::new(ProportionCount, 2, false, false, 0, 100000);
+    let (_, mut stalemate) = engine.next_boards(&board, sacrifice, false);
+    stalemate.apply_move(ChessMove::new(chess::Square::H1, chess::Square::G1, None));
+    stalemate.apply_bonus_unchecked(false);
+    assert!(stalemate.get_status().is_in_progress());
+    assert_eq!(
+        engine.get_scored_best_move(
+            &stalemate,
+            Bounds::widest(),
+            0,
+            false,
+            Deadline::from_now(100000)
+        ),
+        Result(ONE / 2, None, Some(0))
+    );
+
+    let mut engine = AlphaBeta::new(ProportionCount, 2, false, false, 0, 100000);
+    assert_eq!(engine.get_move(&board), sacrifice);
+}
+
+/// A deterministic source of numbers in `[0, 1)` for sampling
+fn test_rng() -> impl FnMut() -> f32 {
+    let mut rng = SeededRng::new(12345);
+    move || rng.next_f32()
+}
+
+#[test]
+fn test_temperature() {
+    let board = scandinavian();
+    let best = AlphaBeta::new(ProportionCount, 2, false, false, 0, 100000).get_move(&board);
+
+    let mut cold = AlphaBeta::new(ProportionCount, 2, false, false, 0, 100000)
+        .with_temperature(0.0, test_rng());
+    for _ in 0..5 {
+        assert_eq!(cold.get_move(&board), best);
+    }
+
+    let mut hot = AlphaBeta::new(ProportionCount, 2, false, false, 0, 100000)
+        .with_temperature(1.0, test_rng());
+    let moves: Vec<_> = (0..20).map(|_| hot.get_move(&board)).collect();
+    assert!(moves.iter().any(|&mv| mv != best));
+    assert!(moves.iter().all(|mv| board.all_moves().any(|m| m == *mv)));
+
+    // Sampling shares the search's deadline, so once it has passed, the best
+    // move is played
+    let info = hot.analyze(&board, 100000).pop().unwrap();
+    hot.search_deadline = Some(Deadline::from_now(0));
+    for _ in 0..5 {
+        assert_eq!(hot.sample_move(&board, &info, 1.0), info.best_move);
+    }
+}
+
+#[test]
+fn test_evaluate_game() {
+    // White brings the queen out with a bonus move and captures the king
+    let moves = [
+        (chess::Square::E2, chess::Square::E4, true),
+        (chess::Square::D1, chess::Square::H5, false),
+        (chess::Square::F7, chess::Square::F6, false),
+        (chess::Square::H5, chess::Square::E8, false),
+    ];
+    let mut board = MyBoard::initial_board(Color::White);
+    let mut boards = vec![board];
+    for (from, to, is_bonus) in moves {
+        board.apply_move(ChessMove::new(from, to, None));
+        board.apply_bonus(is_bonus);
+        boards.push(board);
+    }
+    assert_eq!(
+        board.get_status(),
+        crate::my_board::Status::Win(Color::White)
+    );
+
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    let trajectory = engine.evaluate_game(&boards, 100_000);
+
+    assert_eq!(trajectory.len(), boards.len() - 1);
+    assert!(trajectory[trajectory.len() - 1] > trajectory[0]);
+    assert!(trajectory[trajectory.len() - 1] > Score::from_num(0.9));
+}
+
+#[test]
+fn test_aspiration_researches() {
+    let board = scandinavian();
+
+    let mut narrow = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000)
+        .with_aspiration_window(crate::DELTA);
+    let narrow_move = narrow.get_move(&board);
+    assert!(narrow.last_search_stats().aspiration_researches >= 1);
+
+    let mut wide =
+        AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000).with_aspiration_window(ONE);
+    let wide_move = wide.get_move(&board);
+    assert_eq!(wide.last_search_stats().aspiration_researches, 0);
+    assert_eq!(wide.last_search_stats().depth, 3);
+
+    // The window shouldn't change the result
+    assert_eq!(narrow_move, wide_move);
+}
+
+#[test]
+fn test_best_by_distance() {
+    // Black's king is in check and can't escape, so under the standard rules
+    // any quiet move mates, but the queen can capture the king straight away.
+    // The mate takes a ply longer, since a bonus lets white move again.
+    let board = MyBoard::from_piece_list(Color::White, &[
+        (chess::Square::A3, chess::Piece::King, Color::White),
+        (chess::Square::F1, chess::Piece::Rook, Color::White),
+        (chess::Square::G1, chess::Piece::Rook, Color::White),
+        (chess::Square::H1, chess::Piece::Queen, Color::White),
+        (chess::Square::H8, chess::Piece::King, Color::Black),
+    ])
+    .with_variant(crate::my_board::Variant::Standard);
+    let fast = ChessMove::new(chess::Square::H1, chess::Square::H8, None);
+    let slow = ChessMove::new(chess::Square::A3, chess::Square::A4, None);
+
+    let new_engine = || AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    let result_of = |mv| {
+        new_engine()
+            .score_move(&board, mv, 3, Deadline::from_now(100000))
+            .unwrap()
+    };
+    assert_eq!(result_of(fast), (ONE, Some(1)));
+    // Once a win is found, other wins are pruned, so this is only a bound
+    let (slow_score, slow_plies) = result_of(slow);
+    assert_eq!(slow_score, ONE);
+    assert!(slow_plies.unwrap() > 1);
+
+    // The search only guarantees a winning move, so whichever it finds, the
+    // quickest win is played
+    let mut engine = new_engine();
+    assert_eq!(engine.get_move(&board), fast);
+    assert_eq!(
+        engine.last_forced_result(),
+        Some((Status::Win(Color::White), 1))
+    );
+}
+
+#[test]
+fn test_aspiration_near_result() {
+    // White is close to winning, so the window around each depth's score
+    // saturates at 1
+    let board = MyBoard::from_fen("k7/8/1Q6/8/8/8/8/R3K3 w - - 0 1").unwrap();
+
+    let mut plain = AlphaBeta::new(ProportionCount, 4, false, false, 0, 100000);
+    let mut narrow = AlphaBeta::new(ProportionCount, 4, false, false, 0, 100000)
+        .with_aspiration_window(ONE / 32);
+
+    let expected = plain.best_move_per_depth(&board, 4);
+    assert!(expected.last().unwrap().2 > ONE - ONE / 32);
+    assert_eq!(narrow.best_move_per_depth(&board, 4), expected);
+}
+
+#[test]
+fn test_last_forced_result() {
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    engine.get_move(&scandinavian());
+    assert_eq!(engine.last_forced_result(), None);
+
+    // White's queen can capture the king straight away
+    let board = MyBoard::from_fen("4k3/8/8/8/8/8/8/4QK2 w - - 0 1").unwrap();
+    engine.get_move(&board);
+    assert_eq!(
+        engine.last_forced_result(),
+        Some((Status::Win(Color::White), 1))
+    );
+
+    let board = MyBoard::from_fen("4kq2/8/8/8/8/8/8/5K2 b - - 0 1").unwrap();
+    engine.get_move(&board);
+    assert_eq!(
+        engine.last_forced_result(),
+        Some((Status::Win(Color::Black), 1))
+    );
+
+    assert_eq!(forced_status(ONE - DELTA), Some(Status::Win(Color::White)));
+    assert_eq!(forced_status(ONE / 2), Some(Status::Draw));
+    assert_eq!(forced_status(ONE / 4), None);
+}
+
+#[test]
+fn test_compare_focussed() {
+    // White can capture the king with the queen
+    let white_captures = MyBoard::from_piece_list(Color::White, &[
+        (chess::Square::E1, chess::Piece::King, Color::White),
+        (chess::Square::H5, chess::Piece::Queen, Color::White),
+        (chess::Square::E8, chess::Piece::King, Color::Black),
+        (chess::Square::A7, chess::Piece::Pawn, Color::Black),
+    ]);
+    // Black can capture the king with the knight
+    let black_captures = MyBoard::from_piece_list(Color::Black, &[
+        (chess::Square::E1, chess::Piece::King, Color::White),
+        (chess::Square::A2, chess::Piece::Pawn, Color::White),
+        (chess::Square::E8, chess::Piece::King, Color::Black),
+        (chess::Square::D3, chess::Piece::Knight, Color::Black),
+    ]);
+
+    let comparison = compare_focussed(
+        || ProportionCount,
+        &[white_captures, black_captures],
+        3,
+        100000,
+    );
+    assert_eq!(comparison.agreement, 1.0);
+    assert_eq!(comparison.mean_score_difference, 0.0);
+}
+
+#[test]
+fn test_avoids_repetition_when_winning() {
+    let board = MyBoard::from_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1").unwrap();
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    let tempting = engine.get_move(&board);
+
+    // The position after the engine's preferred move has already occurred
+    // twice, so playing it again would draw a won game
+    let mut after = board;
+    after.apply_move(tempting);
+    after.apply_bonus(false);
+    let game = Game::with_history(vec![after, after, board]);
+
+    let mv = engine.get_move_in_game(&game);
+    assert_ne!(mv, tempting);
+    for is_bonus in [false, true] {
+        let mut next = game.clone();
+        next.play(mv, is_bonus);
+        assert_eq!(next.status(), Status::InProgress);
+    }
+
+    // Without the repetition, the preferred move is still played
+    assert_eq!(
+        engine.get_move_in_game(&Game::with_history(vec![board])),
+        tempting
+    );
+}
+
+#[test]
+fn test_repetition_forced_result() {
+    // Either rook mates on the back rank, but the a-rook is quicker
+    let board = MyBoard::from_fen("6k1/5ppp/8/8/8/8/8/RR4K1 w - - 0 1")
+        .unwrap()
+        .with_variant(crate::my_board::Variant::Standard);
+    let quick = ChessMove::new(chess::Square::A1, chess::Square::A8, None);
+    let slow = ChessMove::new(chess::Square::B1, chess::Square::B8, None);
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    assert_eq!(engine.get_move(&board), quick);
+    let (_, slow_plies) = engine
+        .score_move(&board, slow, 3, Deadline::from_now(100000))
+        .unwrap();
+
+    // When a bonus after the quick mate would repeat the position, the
+    // result is the slow mate's
+    let mut after = board;
+    after.apply_move(quick);
+    after.apply_bonus(true);
+    let game = Game::with_history(vec![after, after, board]);
+    assert_eq!(engine.get_move_in_game(&game), slow);
+    assert_eq!(
+        engine.last_forced_result(),
+        Some((Status::Win(Color::White), slow_plies.unwrap()))
+    );
+    assert!(!engine.last_move_was_forced());
+}
+
+#[test]
+fn test_verify_tt() {
+    let boards = [
+        MyBoard::initial_board(Color::White),
+        scandinavian(),
+        MyBoard::from_fen("r3k3/1P6/8/3pP3/8/8/8/4K2R w K d6 0 1").unwrap(),
+    ];
+    for board in boards {
+        let mut engine =
+            AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000).with_verify_tt();
+        engine.get_move(&board);
+    }
+}
+
+#[test]
+#[should_panic(expected = "without it")]
+fn test_verify_tt_bad_entry() {
+    let board = MyBoard::initial_board(Color::White);
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000).with_verify_tt();
+
+    // Claim that every move loses for white at a high depth, so that the
+    // entries are trusted by the search
+    for mv in board.all_moves() {
+        let (b_board, nb_board) = engine.next_boards(&board, mv, true);
+        for child in [b_board, nb_board] {
+            engine
+                .position_table
+                .insert(&child, 10, ScoreInfo::from_score(ZERO));
+        }
+    }
+    engine.get_move(&board);
+}
+
+#[test]
+fn test_verify_tt_leaves_table() {
+    let board = scandinavian();
+    let mut scorer = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    let deadline = Deadline::from_now(100000);
+    let Result(score, _, _) =
+        scorer.get_scored_best_move(&board, Bounds::widest(), 3, true, deadline)
+    else {
+        panic!("the search should finish");
+    };
+
+    // Verifying searches without the table, so it mustn't fill it either
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    engine.verify_table_result(&board, 3, score, Deadline::from_now(100000));
+    assert_eq!(engine.position_table.items(), 0);
+}
+
+#[test]
+fn test_bonus_swing() {
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 10000);
+
+    // Moving the rook to the b-file attacks the king, so a bonus lets white
+    // capture it straight away
+    let decisive = MyBoard::from_fen("1k5r/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+    let decisive_swing = engine.bonus_swing(&decisive).unwrap();
+
+    let quiet = MyBoard::initial_board(Color::White);
+    let quiet_swing = engine.bonus_swing(&quiet).unwrap();
+
+    assert!(decisive_swing > ONE / 4, "{}", decisive_swing);
+    assert!(quiet_swing < ONE / 10);
+
+    // Running out of nodes gives no answer rather than panicking
+    let mut limited = AlphaBeta::new(ProportionCount, 3, false, false, 0, 10000).with_max_nodes(10);
+    assert_eq!(limited.bonus_swing(&quiet), None);
+}
+
+#[test]
+fn test_reset_stats() {
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 10000);
+    let board = scandinavian();
+    engine.get_move(&board);
+    let items = engine.position_table.items();
+    assert!(items > 0);
+    assert!(engine.last_search_stats().nodes > 0);
+
+    engine.reset_stats();
+    assert_eq!(engine.last_search_stats(), SearchStats::default());
+    assert_eq!(engine.iter_deep_lookups, 0);
+    #[cfg(feature = "table_stats")]
+    assert!(engine
+        .position_table
+        .info()
+        .contains("Total get attempts: 0\n"));
+    assert_eq!(engine.position_table.items(), items);
+}
+
+#[test]
+fn test_quiescence() {
+    // Black's pawn can take white's queen, which a static evaluation of the
+    // horizon doesn't see
+    let board = MyBoard::from_fen("3qk3/8/8/8/4p3/3Q4/8/4K3 b - - 0 1").unwrap();
+    let deadline = Deadline::from_now(10000);
+
+    let mut plain = AlphaBeta::new(ProportionCount, 2, false, false, 0, 10000);
+    let Result(static_score, _, _) =
+        plain.get_scored_best_move(&board, Bounds::widest(), 0, false, deadline)
+    else {
+        panic!("the horizon should be evaluated");
+    };
+    assert!(static_score.abs_diff(ONE / 2) < ONE / 10);
+
+    let mut quiescent =
+        AlphaBeta::new(ProportionCount, 2, false, false, 0, 10000).with_quiescence(4);
+    let Result(quiescent_score, _, _) =
+        quiescent.get_scored_best_move(&board, Bounds::widest(), 0, false, deadline)
+    else {
+        panic!("the horizon should be searched");
+    };
+    assert!(quiescent_score < ONE / 5, "{}", quiescent_score);
+
+    // The full search still works with bounds pruning in the quiescence
+    // search, and agrees with itself when the table is checked
+    let mut verified = AlphaBeta::new(ProportionCount, 3, false, false, 0, 10000)
+        .with_quiescence(2)
+        .with_verify_tt();
+    verified.get_move(&scandinavian());
+}
+
+#[test]
+fn test_ponder() {
+    let reported = Arc::new(Mutex::new(0));
+    let sink = Arc::clone(&reported);
+    let mut engine = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000)
+        .with_on_depth(move |_| *sink.lock().unwrap() += 1);
+    let mut board = scandinavian();
+    board.apply_move(engine.get_move(&board));
+    board.apply_bonus(false);
+    let stats = engine.last_search_stats();
+    let reported_depths = *reported.lock().unwrap();
+    let depth_nodes = engine.depth_nodes.clone();
+
+    engine.start_ponder(&board);
+    assert!(engine.is_pondering());
+    assert_eq!(engine.ponder_boards.len(), 2);
+    let predicted = engine.ponder_boards[0];
+    assert_eq!(predicted.get_side_to_move(), !board.get_side_to_move());
+    // Pondering with a small budget works, and a large enough one searches
+    // the positions fully
+    engine.ponder(1000);
+    engine.ponder(u64::MAX);
+    assert_eq!(engine.last_search_stats(), stats);
+    // Pondering isn't reported, and leaves the timing of the last search
+    assert_eq!(*reported.lock().unwrap(), reported_depths);
+    assert_eq!(engine.depth_nodes, depth_nodes);
+
+    // A stop isn't cleared by pondering, so it cancels the next search
+    engine.stop_handle().store(true, Relaxed);
+    engine.ponder(1000);
+    assert!(engine.stop_handle().load(Relaxed));
+    engine.stop_handle().store(false, Relaxed);
+
+    // The prediction was right, so the table from pondering is reused
+    engine.stop_ponder();
+    assert!(!engine.is_pondering());
+    let mut fresh = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    assert_eq!(engine.get_move(&predicted), fresh.get_move(&predicted));
+    assert!(engine.last_search_stats().nodes < fresh.last_search_stats().nodes);
+}
+
+#[test]
+fn test_threads() {
+    let board = scandinavian();
+    let mut single = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    let expected = single.evaluate(&board);
+
+    let mut threaded = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000)
+        .with_table_capacity(1 << 16)
+        .with_threads(3);
+    assert_eq!(threaded.position_table.capacity(), 1 << 16);
+    assert_eq!(threaded.evaluate(&board), expected);
+    assert_eq!(threaded.last_search_stats().depth, 3);
+    assert!(threaded.last_search_stats().helper_nodes > 0);
+    assert_eq!(single.last_search_stats().helper_nodes, 0);
+}
+
+#[test]
+fn test_root_threads() {
+    let board = scandinavian();
+    let mut single = AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000);
+    let expected = single.evaluate(&board);
+
+    let root_threaded = || {
+        AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000)
+            .with_table_capacity(1 << 16)
+            .with_root_threads(3)
+    };
+    let mut engine = root_threaded();
+    assert!(engine.evaluate(&board).abs_diff(expected) <= DELTA);
+    assert_eq!(engine.last_search_stats().depth, 3);
+    assert_eq!(engine.depth_nodes.len(), 2);
+
+    // The capacity is shared between the threads' tables
+    assert_eq!(engine.root_tables.len(), 2);
+    assert_eq!(engine.position_table.capacity(), 1 << 14);
+    assert_eq!(engine.root_tables[0].capacity(), 1 << 14);
+    assert!(engine.table_capacity() <= 1 << 16);
+    assert!(engine.position_table.items() > 0);
+
+    // The moves and node counts don't depend on the scheduling
+    let mv = engine.get_move(&board);
+    let mut other = root_threaded();
+    other.get_move(&board);
+    assert_eq!(other.get_move(&board), mv);
+    assert_eq!(other.last_search_stats(), engine.last_search_stats());
+
+    engine.reset();
+    assert_eq!(engine.root_tables.len(), 2);
+    assert_eq!(engine.root_tables[0].items(), 0);
+}
+
+#[test]
+#[should_panic(expected = "already uses several threads")]
+fn test_root_threads_with_threads() {
+    AlphaBeta::new(ProportionCount, 3, false, false, 0, 100000)
+        .with_threads(2)
+        .with_root_threads(2);
+}
+
+#[test]
+fn test_null_move_pruning() {
+    let board = scandinavian();
+    let mut plain = AlphaBeta::new(ProportionCount, 4, false, false, 0, 100000);
+    let mut pruned =
+        AlphaBeta::new(ProportionCount, 4, false, false, 0, 100000).with_null_move_pruning(1);
+    assert_eq!(plain.get_move(&board), pruned.get_move(&board));
+    assert!(pruned.last_search_stats().nodes < plain.last_search_stats().nodes);
+
+    let prunes: u64 = (0..=4)
+        .map(|d| pruned.branch_info[d].null_move_prunes)
+        .sum();
+    assert!(prunes > 0);
+    assert!(pruned
+        .branch_info
+        .statistics()
+        .contains("were cut off by a null move"));
+    assert!((0..=4).all(|d| plain.branch_info[d].null_move_prunes == 0));
+}
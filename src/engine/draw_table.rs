@@ -0,0 +1,95 @@
+use std::collections::HashSet;
+
+use chess::{Color, Piece};
+
+use crate::my_board::MyBoard;
+
+/// The number of each type of piece (other than the king) that each player
+/// has, indexed by color and then piece index
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MaterialSignature([[u8; 5]; 2]);
+
+impl MaterialSignature {
+    /// The signature for the given non-king pieces of each player
+    pub fn new(white: &[Piece], black: &[Piece]) -> MaterialSignature {
+        let mut counts = [[0; 5]; 2];
+        for (col, pieces) in [(Color::White, white), (Color::Black, black)] {
+            for &piece in pieces {
+                assert_ne!(piece, Piece::King, "kings aren't part of a signature");
+                counts[col.to_index()][piece.to_index()] += 1;
+            }
+        }
+        MaterialSignature(counts)
+    }
+
+    pub fn of(board: &MyBoard) -> MaterialSignature {
+        let mut counts = [[0; 5]; 2];
+        for sq in board.get_white_pieces() | board.get_black_pieces() {
+            if let Some((piece, col)) = board[sq] {
+                if piece != Piece::King {
+                    counts[col.to_index()][piece.to_index()] += 1;
+                }
+            }
+        }
+        MaterialSignature(counts)
+    }
+
+    /// The same signature with the colors swapped
+    fn mirrored(self) -> MaterialSignature { MaterialSignature([self.0[1], self.0[0]]) }
+}
+
+/// A set of material configurations which can't be won in this variant, e.g.
+/// because the stronger side can't coordinate its pieces to force a king
+/// capture. Evaluators can consult it to score such positions as draws
+/// rather than trying to win them.
+#[derive(Clone, Debug, Default)]
+pub struct DrawTable {
+    signatures: HashSet<MaterialSignature>,
+}
+
+impl DrawTable {
+    pub fn new() -> DrawTable { DrawTable::default() }
+
+    /// Marks the position where one player has only `stronger` (and their
+    /// king) and the other has only `weaker` as drawn, whichever color each
+    /// player is
+    pub fn with_draw(mut self, stronger: &[Piece], weaker: &[Piece]) -> DrawTable {
+        let signature = MaterialSignature::new(stronger, weaker);
+        self.signatures.insert(signature);
+        self.signatures.insert(signature.mirrored());
+        self
+    }
+
+    /// Whether the material on `board` is marked as drawn
+    pub fn is_drawn(&self, board: &MyBoard) -> bool {
+        self.signatures.contains(&MaterialSignature::of(board))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chess::Square;
+
+    use super::*;
+
+    #[test]
+    fn test_is_drawn() {
+        let table = DrawTable::new().with_draw(&[Piece::Bishop], &[]);
+
+        let white_bishop = MyBoard::from_piece_list(Color::White, &[
+            (Square::E1, Piece::King, Color::White),
+            (Square::C1, Piece::Bishop, Color::White),
+            (Square::E8, Piece::King, Color::Black),
+        ]);
+        let black_bishop = MyBoard::from_piece_list(Color::White, &[
+            (Square::E1, Piece::King, Color::White),
+            (Square::C8, Piece::Bishop, Color::Black),
+            (Square::E8, Piece::King, Color::Black),
+        ]);
+        assert!(table.is_drawn(&white_bishop));
+        assert!(table.is_drawn(&black_bishop));
+
+        assert!(!table.is_drawn(&MyBoard::initial_board(Color::White)));
+        assert!(!DrawTable::new().is_drawn(&white_bishop));
+    }
+}
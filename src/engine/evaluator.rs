@@ -1,11 +1,12 @@
 use chess::Color;
+use serde::Serialize;
 
 use crate::{
     my_board::{MyBoard, Status},
     Score,
 };
 
-pub trait StaticEvaluator {
+pub trait StaticEvaluator: Send {
     /// Evaluates a given game state represented by `board`.
     /// Returns a float between 0 and 1, which should be equal to
     /// `0 * P(B) + 0.5 * P(D) + 1 * P(W)`, where:
@@ -17,6 +18,23 @@ pub trait StaticEvaluator {
     /// given that the value of a win is 1 and the value of a draw is 0.5.
     fn evaluate(&self, board: &MyBoard) -> Score;
 
+    /// Returns true if the evaluation never depends on whose turn it is to
+    /// move. Evaluations from such evaluators can be stored for both colors
+    /// in the position table.
+    fn ignores_side_to_move(&self) -> bool { false }
+
+    /// Returns a copy of the evaluator with the same configuration, for use
+    /// by a search on another thread, or None if it can't be copied. Any
+    /// caches the evaluator has are left empty in the copy.
+    fn clone_for_thread(&self) -> Option<Box<dyn StaticEvaluator>> { None }
+
+    /// A summary of any statistics the evaluator keeps, such as cache hit
+    /// rates, for logging alongside the search statistics
+    fn statistics(&self) -> Option<String> { None }
+
+    /// A human readable name for the evaluator, for describing engines
+    fn describe(&self) -> String { super::short_type_name::<Self>().to_string() }
+
     /// Returns the evaluation of a terminal game state, or None if the game
     /// is still in progress.
     fn evaluate_terminal(&self, board: &MyBoard) -> Option<Score> {
@@ -28,3 +46,172 @@ pub trait StaticEvaluator {
         }
     }
 }
+
+/// Statistics about how closely two evaluators agree over a set of positions
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct EvaluatorComparison {
+    /// The Pearson correlation of the two evaluations. This is NaN if either
+    /// evaluator gave the same score for every position.
+    pub correlation: f32,
+    /// The average absolute difference between the two evaluations
+    pub mean_abs_error: f32,
+    /// The proportion of positions where one evaluator thinks white is better
+    /// and the other thinks black is better (i.e. they are on opposite sides
+    /// of 0.5)
+    pub sign_disagreement: f32,
+}
+
+/// Evaluates each of `boards` with both evaluators and compares the results.
+/// This is the main thing to look at when deciding whether a new feature
+/// actually changes the evaluation in a useful way.
+pub fn compare_evaluators(
+    a: &dyn StaticEvaluator, b: &dyn StaticEvaluator, boards: &[MyBoard],
+) -> EvaluatorComparison {
+    assert!(!boards.is_empty(), "need at least one board to compare");
+
+    let scores: Vec<(f32, f32)> = boards
+        .iter()
+        .map(|board| (a.evaluate(board).to_num(), b.evaluate(board).to_num()))
+        .collect();
+    let n = scores.len() as f32;
+
+    let mean_a = scores.iter().map(|(a, _)| a).sum::<f32>() / n;
+    let mean_b = scores.iter().map(|(_, b)| b).sum::<f32>() / n;
+
+    let mut covariance = 0.0;
+    let mut variance_a = 0.0;
+    let mut variance_b = 0.0;
+    let mut total_error = 0.0;
+    let mut disagreements = 0;
+
+    for &(a, b) in scores.iter() {
+        covariance += (a - mean_a) * (b - mean_b);
+        variance_a += (a - mean_a) * (a - mean_a);
+        variance_b += (b - mean_b) * (b - mean_b);
+        total_error += (a - b).abs();
+        if (a - 0.5) * (b - 0.5) < 0.0 {
+            disagreements += 1;
+        }
+    }
+
+    EvaluatorComparison {
+        correlation: covariance / (variance_a * variance_b).sqrt(),
+        mean_abs_error: total_error / n,
+        sign_disagreement: disagreements as f32 / n,
+    }
+}
+
+/// Panics if `eval` doesn't give (within `tolerance`) the same evaluation
+/// for `board` when it is mirrored. Mirroring the ranks and swapping the
+/// colors should invert the evaluation, and mirroring the files shouldn't
+/// change it. Castle rights are stripped before mirroring the files, since
+/// they can't be mirrored.
+///
+/// This is for checking custom evaluators, which should be symmetric.
+#[cfg(any(test, feature = "test-util"))]
+pub fn assert_evaluation_symmetric(eval: &dyn StaticEvaluator, board: &MyBoard, tolerance: Score) {
+    let check = |expected: Score, actual: Score, inversion: &str| {
+        assert!(
+            expected.abs_diff(actual) <= tolerance,
+            "evaluation changed when inverting {}: {} != {}",
+            inversion,
+            expected,
+            actual
+        );
+    };
+
+    let score = eval.evaluate(board);
+    let mut inverted = *board;
+    inverted.invert_ranks_and_colors();
+    check(
+        score,
+        Score::ONE - eval.evaluate(&inverted),
+        "ranks and colors",
+    );
+
+    let mut stripped = *board;
+    stripped.strip_castle_rights();
+    let stripped_score = eval.evaluate(&stripped);
+    let mut inverted = stripped;
+    inverted.invert_files();
+    check(stripped_score, eval.evaluate(&inverted), "files");
+    inverted.invert_ranks_and_colors();
+    check(
+        stripped_score,
+        Score::ONE - eval.evaluate(&inverted),
+        "files, ranks and colors",
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{
+        feature_eval::{FeatureEval, Weights},
+        proportion_count::ProportionCount,
+    };
+
+    /// The positions from a deterministic but varied sequence of moves
+    fn sample_boards() -> Vec<MyBoard> {
+        let mut board = MyBoard::initial_board(Color::White);
+        let mut boards = vec![board];
+        for i in 0..60 {
+            if !board.get_status().is_in_progress() {
+                break;
+            }
+            let moves: Vec<_> = board.all_moves().collect();
+            board.apply_move(moves[(i * 7) % moves.len()]);
+            board.apply_bonus(i % 4 == 0);
+            boards.push(board);
+        }
+        boards
+    }
+
+    #[test]
+    fn test_compare_scaled_evaluator() {
+        let boards = sample_boards();
+        let comparison = compare_evaluators(
+            &FeatureEval::new(Weights::standard(), 15.0),
+            &FeatureEval::new(Weights::standard(), 30.0),
+            &boards,
+        );
+        assert_eq!(comparison.sign_disagreement, 0.0);
+        assert!(comparison.correlation > 0.95);
+        assert!(comparison.mean_abs_error > 0.0);
+
+        let identical = compare_evaluators(
+            &FeatureEval::new(Weights::standard(), 15.0),
+            &FeatureEval::new(Weights::standard(), 15.0),
+            &boards,
+        );
+        assert_eq!(identical.mean_abs_error, 0.0);
+    }
+
+    /// Prefers white's king to be on the queenside, which isn't symmetric
+    struct QueensideKing;
+
+    impl StaticEvaluator for QueensideKing {
+        fn evaluate(&self, board: &MyBoard) -> Score {
+            let file = board.king_square(Color::White).unwrap().get_file();
+            Score::from_num(0.25 + file.to_index() as f32 / 16.0)
+        }
+    }
+
+    #[test]
+    fn test_assert_evaluation_symmetric() {
+        // Allow for rounding in the floating point division
+        let tolerance = Score::from_num(1e-6);
+        for board in sample_boards() {
+            if board.get_status().is_in_progress() {
+                assert_evaluation_symmetric(&ProportionCount, &board, tolerance);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "evaluation changed when inverting")]
+    fn test_assert_evaluation_symmetric_asymmetric() {
+        let board = MyBoard::initial_board(Color::White);
+        assert_evaluation_symmetric(&QueensideKing, &board, Score::from_num(0.01));
+    }
+}
@@ -1,77 +1,230 @@
-use chess::{Color::*, Piece::*};
+use std::cell::{Cell, RefCell};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs, io, path::Path};
+
+use chess::{BitBoard, Color, Color::*, Piece::*, EMPTY};
 use serde::{Deserialize, Serialize};
 
-use crate::{MyBoard, Score, StaticEvaluator};
+use super::{draw_table::DrawTable, proportion_count::decisive_material_score};
+use crate::{my_board, MyBoard, Score, StaticEvaluator, ONE};
 
 /// Weights that are designed to be multiplied by corresponding features
 /// using a dot product
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Weights {
     pub pieces: [[f32; 6]; 2],
     pub king_danger: [f32; 2],
     pub pawn_advancement: [f32; 2],
+    pub king_escapes: [f32; 2],
+    /// Weights saved before this feature was added don't have it, so they
+    /// load with it ignored
+    #[serde(default)]
+    pub bishop_pair: [f32; 2],
     pub side_to_move: f32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// A pair of weights for `FeatureEval::with_endgame_weights`, which are
+/// tapered from `opening` to `endgame` as pieces are traded
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TaperedWeights {
+    pub opening: Weights,
+    pub endgame: Weights,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Features {
     /// The number of pieces of each type for each player
     pub pieces: [[f32; 6]; 2],
     /// The number of squares that each players' king could be attacked from
     pub king_danger: [f32; 2],
-    /// The average rank of each players' pawns
+    /// The average rank of each players' pawns, where blocked pawns count
+    /// for less
     pub pawn_advancement: [f32; 2],
+    /// The number of squares each players' king could move to without being
+    /// attacked. A king with nowhere safe to go is close to being captured.
+    pub king_escapes: [f32; 2],
+    /// Whether each player has at least two bishops (1 if so, 0 if not).
+    /// Features saved before this was added fail to load, rather than
+    /// claiming that neither player has the pair.
+    pub bishop_pair: [f32; 2],
     /// Whose turn it is to move (1 for white, -1 for black)
     pub side_to_move: f32,
 }
 
+impl Weights {
+    /// Hand-picked weights, with the usual piece values, which go with a
+    /// `scale_down` of 15. These are what the engine uses unless it is given
+    /// tuned weights. The bishop pair isn't weighted, so that the standard
+    /// evaluation is the same as before it was added.
+    pub fn standard() -> Weights {
+        Weights {
+            pieces: [[1.0, 3.0, 3.0, 5.0, 9.0, 0.0], [
+                -1.0, -3.0, -3.0, -5.0, -9.0, 0.0,
+            ]],
+            king_danger: [-0.5, 0.5],
+            pawn_advancement: [0.5, -0.5],
+            king_escapes: [0.5, -0.5],
+            bishop_pair: [0.0, 0.0],
+            side_to_move: 3.0,
+        }
+    }
+
+    /// The weights as a flat vector, in the same order as `Features::to_vec`
+    pub fn to_vec(&self) -> Vec<f32> {
+        let mut vec = Vec::with_capacity(FEATURE_COUNT);
+        vec.extend(self.pieces.iter().flatten());
+        vec.extend(self.king_danger);
+        vec.extend(self.pawn_advancement);
+        vec.extend(self.king_escapes);
+        vec.extend(self.bishop_pair);
+        vec.push(self.side_to_move);
+        vec
+    }
+
+    /// The inverse of `to_vec`
+    pub fn from_vec(vec: &[f32]) -> Weights {
+        assert_eq!(vec.len(), FEATURE_COUNT, "wrong number of weights");
+        let mut pieces = [[0.0; 6]; 2];
+        pieces[0].copy_from_slice(&vec[0..6]);
+        pieces[1].copy_from_slice(&vec[6..12]);
+        Weights {
+            pieces,
+            king_danger: [vec[12], vec[13]],
+            pawn_advancement: [vec[14], vec[15]],
+            king_escapes: [vec[16], vec[17]],
+            bishop_pair: [vec[18], vec[19]],
+            side_to_move: vec[20],
+        }
+    }
+
+    /// Encodes the weights (in the order of `to_vec`) as little-endian
+    /// IEEE-754 floats, so that they can be stored and loaded bit-exactly on
+    /// any platform
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_vec().iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    /// The inverse of `to_bytes`, or `None` if `bytes` is the wrong length
+    pub fn from_bytes(bytes: &[u8]) -> Option<Weights> {
+        if bytes.len() != FEATURE_COUNT * 4 {
+            return None;
+        }
+        let vec: Vec<f32> = bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect();
+        Some(Weights::from_vec(&vec))
+    }
+
+    /// Interpolates between these weights, for the opening, and `endgame`
+    /// according to `phase` (from `game_phase`), so that the weights change
+    /// gradually as pieces are traded
+    pub fn tapered(&self, endgame: &Weights, phase: u8) -> Weights {
+        let t = phase as f32 / MAX_PHASE as f32;
+        let mix = |opening: f32, endgame: f32| opening * t + endgame * (1.0 - t);
+        let mix_colors = |opening: [f32; 2], endgame: [f32; 2]| {
+            [mix(opening[0], endgame[0]), mix(opening[1], endgame[1])]
+        };
+        Weights {
+            pieces: std::array::from_fn(|color| {
+                std::array::from_fn(|piece| {
+                    mix(self.pieces[color][piece], endgame.pieces[color][piece])
+                })
+            }),
+            king_danger: mix_colors(self.king_danger, endgame.king_danger),
+            pawn_advancement: mix_colors(self.pawn_advancement, endgame.pawn_advancement),
+            king_escapes: mix_colors(self.king_escapes, endgame.king_escapes),
+            bishop_pair: mix_colors(self.bishop_pair, endgame.bishop_pair),
+            side_to_move: mix(self.side_to_move, endgame.side_to_move),
+        }
+    }
+}
+
+/// The game phase with all the pieces on the board
+pub const MAX_PHASE: u8 = 24;
+
+/// How much each piece counts towards the game phase, indexed by
+/// `Piece::to_index`
+const PHASE_VALUES: [u8; 6] = [0, 1, 1, 2, 4, 0];
+
+/// How far from the endgame `board` is, from `MAX_PHASE` with all the
+/// knights, bishops, rooks and queens still on the board down to 0 with only
+/// kings and pawns. Promotions can't push the phase above `MAX_PHASE`.
+pub fn game_phase(board: &MyBoard) -> u8 {
+    let (white, black) = board.material_value(&PHASE_VALUES);
+    (white + black).min(MAX_PHASE as u32) as u8
+}
+
+/// The number of values in `Features::to_vec`
+const FEATURE_COUNT: usize = 21;
+
 impl Features {
+    /// The features as a flat vector: the piece counts (white then black, in
+    /// piece index order), then king danger, pawn advancement, king escapes,
+    /// the bishop pair and the side to move
+    pub fn to_vec(&self) -> Vec<f32> {
+        let mut vec = Vec::with_capacity(FEATURE_COUNT);
+        vec.extend(self.pieces.iter().flatten());
+        vec.extend(self.king_danger);
+        vec.extend(self.pawn_advancement);
+        vec.extend(self.king_escapes);
+        vec.extend(self.bishop_pair);
+        vec.push(self.side_to_move);
+        vec
+    }
+
+    /// Human readable names for each value in `to_vec`
+    pub fn names() -> Vec<String> {
+        let mut names = Vec::with_capacity(FEATURE_COUNT);
+        for col in ["white", "black"] {
+            for piece in ["pawns", "knights", "bishops", "rooks", "queens", "kings"] {
+                names.push(format!("{} {}", col, piece));
+            }
+        }
+        for feature in [
+            "king danger",
+            "pawn advancement",
+            "king escapes",
+            "bishop pair",
+        ] {
+            for col in ["white", "black"] {
+                names.push(format!("{} {}", col, feature));
+            }
+        }
+        names.push("side to move".to_string());
+        names
+    }
+
     pub fn from_board(board: &MyBoard) -> Features {
         assert!(board.get_status().is_in_progress());
 
-        let mut pieces = [[0.0; 6]; 2];
+        let pieces = [White, Black].map(|col| board.material(col).map(f32::from));
+        let bishop_pair = pieces.map(|counts| {
+            if counts[Bishop.to_index()] >= 2.0 {
+                1.0
+            } else {
+                0.0
+            }
+        });
+        let pawn_advancement = Self::pawn_advancement(board);
         let mut king_danger = [0.0; 2];
-        let mut pawn_advancement = [0.0; 2];
+        let mut king_escapes = [0.0; 2];
 
-        for col in [White, Black] {
+        for (sq, _, col) in board.pieces().filter(|&(_, piece, _)| piece == King) {
             let my_pieces = if col == White {
                 board.get_white_pieces()
             } else {
                 board.get_black_pieces()
             };
             let not_my_pieces = !my_pieces;
-
-            for sq in if col == White {
-                board.get_white_pieces()
-            } else {
-                board.get_black_pieces()
-            } {
-                let Some((piece, _)) = board[sq]
-                    else { panic!("piece not found on square {:?}", sq); };
-                pieces[col.to_index()][piece.to_index()] += 1.0;
-
-                if piece == King {
-                    king_danger[col.to_index()] += ((chess::get_knight_moves(sq)
-                        | chess::get_bishop_moves(sq, my_pieces)
-                        | chess::get_rook_moves(sq, my_pieces))
-                        & not_my_pieces)
-                        .popcnt() as f32;
-                }
-
-                if piece == Pawn {
-                    pawn_advancement[col.to_index()] += if col == White {
-                        sq.get_rank().to_index() as f32 - 1.0
-                    } else {
-                        6.0 - sq.get_rank().to_index() as f32
-                    }
-                }
-            }
-            let pawns = pieces[col.to_index()][Pawn.to_index()];
-            pawn_advancement[col.to_index()] = if pawns != 0.0 {
-                pawn_advancement[col.to_index()] / pawns
-            } else {
-                0.0
-            };
+            king_danger[col.to_index()] += ((chess::get_knight_moves(sq)
+                | chess::get_bishop_moves(sq, my_pieces)
+                | chess::get_rook_moves(sq, my_pieces))
+                & not_my_pieces)
+                .popcnt() as f32;
+            king_escapes[col.to_index()] +=
+                (chess::get_king_moves(sq) & not_my_pieces & !board.attacked_by(!col)).popcnt()
+                    as f32;
         }
 
         let side_to_move = if board.get_side_to_move() == White {
@@ -84,50 +237,841 @@ impl Features {
             pieces,
             king_danger,
             pawn_advancement,
+            king_escapes,
+            bishop_pair,
             side_to_move,
         }
     }
+
+    /// The average advancement of each players' pawns, where a pawn with a
+    /// piece (of either color) directly in front of it only counts for
+    /// `BLOCKED_PAWN_DISCOUNT` of its advancement. This starts from the
+    /// board's running total of advancement, so only the blocked pawns are
+    /// looked at.
+    fn pawn_advancement(board: &MyBoard) -> [f32; 2] {
+        let occupied = board.get_white_pieces() | board.get_black_pieces();
+        let mut pawn_advancement = [0.0; 2];
+
+        for col in [White, Black] {
+            let my_pawns = board.get_pawns(col);
+            if my_pawns == EMPTY {
+                continue;
+            }
+            let blocked = match col {
+                White => BitBoard::new(my_pawns.0 & (occupied.0 >> 8)),
+                Black => BitBoard::new(my_pawns.0 & (occupied.0 << 8)),
+            };
+            let mut total = board.pawn_advancement(col) as f32;
+            for sq in blocked {
+                total -= my_board::pawn_advancement(sq, col) as f32 * (1.0 - BLOCKED_PAWN_DISCOUNT);
+            }
+            pawn_advancement[col.to_index()] = total / my_pawns.popcnt() as f32;
+        }
+        pawn_advancement
+    }
+}
+
+/// The proportion of its advancement that a blocked pawn counts for in
+/// `Features::pawn_advancement`, since it can't advance any further until the
+/// blocker moves
+const BLOCKED_PAWN_DISCOUNT: f32 = 0.5;
+
+/// Ranks the features by how much they influence the score over `samples`,
+/// most influential first. The influence of a feature is the standard
+/// deviation of its contribution to the score, i.e. the magnitude of its
+/// weight times its standard deviation across the samples. Features with
+/// little influence are candidates for removal after tuning.
+pub fn feature_importance(weights: &Weights, samples: &[Features]) -> Vec<(String, f32)> {
+    assert!(!samples.is_empty(), "need at least one sample");
+
+    let vecs: Vec<_> = samples.iter().map(Features::to_vec).collect();
+    let n = vecs.len() as f32;
+
+    let mut importance: Vec<_> = Features::names()
+        .into_iter()
+        .zip(weights.to_vec())
+        .enumerate()
+        .map(|(i, (name, weight))| {
+            let mean = vecs.iter().map(|v| v[i]).sum::<f32>() / n;
+            let variance = vecs.iter().map(|v| (v[i] - mean).powi(2)).sum::<f32>() / n;
+            (name, weight.abs() * variance.sqrt())
+        })
+        .collect();
+
+    importance.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    importance
+}
+
+/// The features of recently evaluated positions, indexed by zobrist hash,
+/// so that transpositions don't have to compute them again
+struct FeatureCache {
+    entries: RefCell<Vec<Option<(u64, Features)>>>,
+    lookups: Cell<u64>,
+    hits: Cell<u64>,
+}
+
+impl FeatureCache {
+    fn new(capacity: usize) -> FeatureCache {
+        assert!(capacity > 0, "the cache must have space for an entry");
+        FeatureCache {
+            entries: RefCell::new(vec![None; capacity]),
+            lookups: Cell::new(0),
+            hits: Cell::new(0),
+        }
+    }
+
+    fn capacity(&self) -> usize { self.entries.borrow().len() }
+
+    /// Returns the cached features of `board`, or computes them with
+    /// `compute` and stores them, replacing whatever was in their slot
+    fn get_or_insert(&self, board: &MyBoard, compute: impl FnOnce() -> Features) -> Features {
+        let hash = board.get_zobrist_hash();
+        let mut entries = self.entries.borrow_mut();
+        let index = hash as usize % entries.len();
+        self.lookups.set(self.lookups.get() + 1);
+        match &entries[index] {
+            Some((entry_hash, features)) if *entry_hash == hash => {
+                self.hits.set(self.hits.get() + 1);
+                features.clone()
+            }
+            _ => {
+                let features = compute();
+                entries[index] = Some((hash, features.clone()));
+                features
+            }
+        }
+    }
+
+    fn clear(&self) {
+        self.entries.borrow_mut().fill(None);
+        self.lookups.set(0);
+        self.hits.set(0);
+    }
 }
 
 pub struct FeatureEval {
     weights: Weights,
+    /// If set, the weights are tapered from `weights` in the opening to these
+    /// in the endgame
+    endgame_weights: Option<Weights>,
     scale_down: f32,
+    decisive_ratio: Option<f32>,
+    full_activity: Option<f32>,
+    draw_table: Option<DrawTable>,
+    /// What the side to move feature is multiplied by, which depends on the
+    /// bonus chance the evaluator is configured for
+    tempo_scale: f32,
+    feature_cache: Option<FeatureCache>,
 }
 
 impl StaticEvaluator for FeatureEval {
     fn evaluate(&self, board: &MyBoard) -> Score {
+        self.evaluate_as(board, board.get_side_to_move())
+    }
+
+    fn clone_for_thread(&self) -> Option<Box<dyn StaticEvaluator>> {
+        Some(Box::new(FeatureEval {
+            draw_table: self.draw_table.clone(),
+            feature_cache: self
+                .feature_cache
+                .as_ref()
+                .map(|cache| FeatureCache::new(cache.capacity())),
+            ..*self
+        }))
+    }
+
+    fn statistics(&self) -> Option<String> {
+        self.feature_cache_hit_rate()
+            .map(|rate| format!("feature cache hit rate: {:.1}%", rate * 100.0))
+    }
+}
+
+impl FeatureEval {
+    /// Evaluates `board` as if it were `side` to move, without having to
+    /// build a board with the side to move switched. This is useful for
+    /// building symmetric training data.
+    pub fn evaluate_as(&self, board: &MyBoard, side: Color) -> Score {
         if !board.get_status().is_in_progress() {
             return self.evaluate_terminal(board).unwrap();
         }
 
-        let features = Features::from_board(board);
+        if self
+            .draw_table
+            .as_ref()
+            .is_some_and(|table| table.is_drawn(board))
+        {
+            return ONE / 2;
+        }
+
+        if let Some(ratio) = self.decisive_ratio {
+            if let Some(score) = decisive_material_score(board, ratio) {
+                return score;
+            }
+        }
+
+        let compute = || Features::from_board(board);
+        let mut features = match &self.feature_cache {
+            Some(cache) => cache.get_or_insert(board, compute),
+            None => compute(),
+        };
+        features.side_to_move = if side == White {
+            self.tempo_scale
+        } else {
+            -self.tempo_scale
+        };
+
+        let weights = match &self.endgame_weights {
+            Some(endgame) => self.weights.tapered(endgame, game_phase(board)),
+            None => self.weights,
+        };
 
         let mut score: f32 = 0.0;
 
         for col in [White, Black] {
             for piece in [Pawn, Knight, Bishop, Rook, Queen, King] {
-                score += self.weights.pieces[col.to_index()][piece.to_index()]
+                score += weights.pieces[col.to_index()][piece.to_index()]
                     * features.pieces[col.to_index()][piece.to_index()];
             }
-            score +=
-                self.weights.king_danger[col.to_index()] * features.king_danger[col.to_index()];
-            score += self.weights.pawn_advancement[col.to_index()]
+            score += weights.king_danger[col.to_index()] * features.king_danger[col.to_index()];
+            score += weights.pawn_advancement[col.to_index()]
                 * features.pawn_advancement[col.to_index()];
+            score += weights.king_escapes[col.to_index()] * features.king_escapes[col.to_index()];
+            score += weights.bishop_pair[col.to_index()] * features.bishop_pair[col.to_index()];
         }
+        score += weights.side_to_move * features.side_to_move;
 
-        let adjusted = Self::sigmoid(score / self.scale_down);
+        if let Some(full_activity) = self.full_activity {
+            score *= Self::activity_scale(board, &features, score, full_activity);
+        }
+
+        let adjusted = sigmoid(score / self.scale_down);
 
         Score::from_num(adjusted)
     }
-}
 
-impl FeatureEval {
+    /// The gradient of the squared error between the evaluation of `board`
+    /// and `target` with respect to each weight. This is for the plain
+    /// weighted evaluation, so it ignores the decisive ratio, mobility scaling,
+    /// draw table and endgame weights. Stepping the weights against the
+    /// gradient moves the evaluation toward `target`.
+    ///
+    /// Panics if the game is over.
+    pub fn weight_gradient(&self, board: &MyBoard, target: f32) -> Weights {
+        let mut features = Features::from_board(board);
+        features.side_to_move *= self.tempo_scale;
+        let features = features.to_vec();
+        let weighted: f32 = features
+            .iter()
+            .zip(self.weights.to_vec())
+            .map(|(f, w)| f * w)
+            .sum();
+        let prediction = sigmoid(weighted / self.scale_down);
+
+        // d/dw (p - t)^2 = 2 (p - t) p (1 - p) f / scale_down
+        let common =
+            2.0 * (prediction - target) * prediction * (1.0 - prediction) / self.scale_down;
+        Weights::from_vec(&features.iter().map(|f| common * f).collect::<Vec<_>>())
+    }
+
     pub fn new(weights: Weights, scale_down: f32) -> FeatureEval {
         FeatureEval {
             weights,
+            endgame_weights: None,
             scale_down,
+            decisive_ratio: None,
+            full_activity: None,
+            draw_table: None,
+            tempo_scale: 1.0,
+            feature_cache: None,
         }
     }
 
-    fn sigmoid(x: f32) -> f32 { 1.0 / (1.0 + (-x).exp()) }
+    /// Creates an evaluator with weights read from a JSON file (in the format
+    /// `Weights` or `TaperedWeights` serializes to), so that tuned weights can
+    /// be tried without recompiling. Returns an `InvalidData` error saying what
+    /// is wrong if the file isn't valid weights, such as when an array has the
+    /// wrong length.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_weights_file(path: impl AsRef<Path>, scale_down: f32) -> io::Result<FeatureEval> {
+        let path = path.as_ref();
+        let invalid = |e: serde_json::Error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid weights in {}: {}", path.display(), e),
+            )
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(path)?).map_err(invalid)?;
+        // Checking for the field rather than trying both formats keeps the
+        // error about what is wrong with the format that was meant
+        if json.get("opening").is_some() {
+            let tapered: TaperedWeights = serde_json::from_value(json).map_err(invalid)?;
+            Ok(FeatureEval::new(tapered.opening, scale_down).with_endgame_weights(tapered.endgame))
+        } else {
+            Ok(FeatureEval::new(
+                serde_json::from_value(json).map_err(invalid)?,
+                scale_down,
+            ))
+        }
+    }
+
+    /// Makes the evaluator taper its weights towards `endgame` as pieces are
+    /// traded, interpolating by `game_phase`. This lets a feature count
+    /// differently in the endgame, such as an active king being an asset
+    /// once there is little left to attack it with.
+    pub fn with_endgame_weights(mut self, endgame: Weights) -> FeatureEval {
+        self.endgame_weights = Some(endgame);
+        self
+    }
+
+    /// Makes the evaluator return early when one side has at least `ratio`
+    /// times as much material as the other. In that case the proportion of
+    /// material is returned rather than the full weighted evaluation, which
+    /// saves time in won endgames.
+    pub fn with_decisive_ratio(mut self, ratio: f32) -> FeatureEval {
+        assert!(ratio > 1.0, "ratio must be greater than 1");
+        self.decisive_ratio = Some(ratio);
+        self
+    }
+
+    /// Makes the evaluator scale down an advantage when the winning side has
+    /// little activity, so that blocked positions aren't treated as easily
+    /// won. The activity of the winning side is the number of squares its
+    /// pieces attack (other than its own pieces), plus the losing side's king
+    /// danger. Advantages are scaled in proportion to activity, up to
+    /// `full_activity`, above which they are left unchanged.
+    pub fn with_mobility_scaling(mut self, full_activity: f32) -> FeatureEval {
+        assert!(full_activity > 0.0, "full activity must be positive");
+        self.full_activity = Some(full_activity);
+        self
+    }
+
+    /// Makes the evaluator score positions whose material is marked as drawn
+    /// in `table` as exactly 0.5
+    pub fn with_draw_table(mut self, table: DrawTable) -> FeatureEval {
+        self.draw_table = Some(table);
+        self
+    }
+
+    /// Scales the side to move feature for games with the given chance of a
+    /// bonus move. Having the move is worth more when bonuses are more
+    /// likely, since the side to move expects to make `1 / (1 - chance)`
+    /// moves in a row. The scale is relative to `crate::bonus_chance`, so the
+    /// weights are unchanged for the actual game.
+    pub fn with_bonus_chance(mut self, chance: f32) -> FeatureEval {
+        assert!(
+            (0.0..1.0).contains(&chance),
+            "bonus chance must be in [0, 1)"
+        );
+        let default_chance = crate::bonus_chance().to_num::<f32>();
+        self.tempo_scale = (1.0 - default_chance) / (1.0 - chance);
+        self
+    }
+
+    /// Makes the evaluator keep the features of up to `capacity` recently
+    /// evaluated positions, indexed by zobrist hash, so that evaluating a
+    /// position again (such as when a search reaches it by transposition, or
+    /// evaluates it for move ordering and then at the horizon) doesn't
+    /// recompute them
+    pub fn with_feature_cache(mut self, capacity: usize) -> FeatureEval {
+        self.feature_cache = Some(FeatureCache::new(capacity));
+        self
+    }
+
+    /// Empties the feature cache and resets its hit rate
+    pub fn clear_feature_cache(&self) {
+        if let Some(cache) = &self.feature_cache {
+            cache.clear();
+        }
+    }
+
+    /// The proportion of feature cache lookups since it was last cleared
+    /// that found the position, or `None` if there is no cache
+    pub fn feature_cache_hit_rate(&self) -> Option<f32> {
+        let cache = self.feature_cache.as_ref()?;
+        Some(cache.hits.get() as f32 / cache.lookups.get().max(1) as f32)
+    }
+
+    /// The factor (between 0 and 1) to scale an unscaled `score` by
+    fn activity_scale(board: &MyBoard, features: &Features, score: f32, full_activity: f32) -> f32 {
+        let (winner, own_pieces) = if score >= 0.0 {
+            (White, board.get_white_pieces())
+        } else {
+            (Black, board.get_black_pieces())
+        };
+        let mobility = (board.attacked_by(winner) & !own_pieces).popcnt() as f32;
+        let loser_danger = features.king_danger[(!winner).to_index()];
+        ((mobility + loser_danger) / full_activity).min(1.0)
+    }
+}
+
+/// Maps a weighted sum onto `(0, 1)`, so that it can be used as a score
+pub(crate) fn sigmoid(x: f32) -> f32 { 1.0 / (1.0 + (-x).exp()) }
+
+#[cfg(test)]
+mod tests {
+    use chess::{Color, Piece, Square};
+
+    use super::*;
+    use crate::{engine::proportion_count::ProportionCount, utils::SeededRng, DELTA};
+
+    #[test]
+    fn test_decisive_material() {
+        let board = MyBoard::from_piece_list(Color::White, &[
+            (Square::E1, Piece::King, Color::White),
+            (Square::D1, Piece::Queen, Color::White),
+            (Square::E8, Piece::King, Color::Black),
+        ]);
+
+        let early = FeatureEval::new(Weights::standard(), 15.0).with_decisive_ratio(8.0);
+        let early_score = early.evaluate(&board);
+        assert!(early_score > Score::from_num(0.9));
+
+        let full_score = ProportionCount.evaluate(&board);
+        assert!(early_score.abs_diff(full_score) <= DELTA);
+
+        // The early-out shouldn't apply to balanced positions
+        let initial = MyBoard::initial_board(Color::White);
+        assert_eq!(
+            early.evaluate(&initial),
+            FeatureEval::new(Weights::standard(), 15.0).evaluate(&initial)
+        );
+    }
+
+    #[test]
+    fn test_evaluate_as() {
+        let eval = FeatureEval::new(Weights::standard(), 15.0);
+        let mut board = MyBoard::initial_board(Color::White);
+        board.apply_move(chess::ChessMove::new(Square::E2, Square::E4, None));
+        board.apply_bonus(false);
+
+        assert_eq!(
+            eval.evaluate_as(&board, Color::Black),
+            eval.evaluate(&board)
+        );
+        assert_eq!(
+            eval.evaluate_as(&board, Color::White),
+            eval.evaluate(&board.with_side_switched())
+        );
+    }
+
+    #[test]
+    fn test_weight_bytes() {
+        let mut rng = SeededRng::new(755);
+        for _ in 0..20 {
+            let vec: Vec<f32> = (0..FEATURE_COUNT)
+                .map(|_| f32::from_bits(rng.next_u32()))
+                .collect();
+            let weights = Weights::from_vec(&vec);
+
+            let bytes = weights.to_bytes();
+            assert_eq!(bytes.len(), FEATURE_COUNT * 4);
+            let decoded = Weights::from_bytes(&bytes).unwrap();
+            let bits = |w: &Weights| w.to_vec().iter().map(|f| f.to_bits()).collect::<Vec<_>>();
+            assert_eq!(bits(&decoded), bits(&weights));
+        }
+
+        assert!(Weights::from_bytes(&[0; 4]).is_none());
+    }
+
+    #[test]
+    fn test_weight_json() {
+        let weights = Weights {
+            king_escapes: [0.25, -0.75],
+            ..Weights::standard()
+        };
+        let json = serde_json::to_string(&weights).unwrap();
+        assert_eq!(serde_json::from_str::<Weights>(&json).unwrap(), weights);
+        assert!(serde_json::from_str::<Weights>("{\"side_to_move\": 3.0}").is_err());
+    }
+
+    #[test]
+    fn test_from_weights_file() {
+        // The process id keeps concurrent test runs from sharing the file
+        let path = std::env::temp_dir().join(format!(
+            "random_chess_{}_test_from_weights_file.json",
+            std::process::id()
+        ));
+        let board = MyBoard::from_fen("4k3/pppp4/8/8/8/8/PPP5/4K3 w - - 0 1").unwrap();
+
+        fs::write(&path, serde_json::to_string(&Weights::standard()).unwrap()).unwrap();
+        let eval = FeatureEval::from_weights_file(&path, 15.0).unwrap();
+        assert_eq!(
+            eval.evaluate(&board),
+            FeatureEval::new(Weights::standard(), 15.0).evaluate(&board)
+        );
+
+        let endgame = Weights {
+            king_escapes: [2.0, -2.0],
+            ..Weights::standard()
+        };
+        let tapered = TaperedWeights {
+            opening: Weights::standard(),
+            endgame,
+        };
+        fs::write(&path, serde_json::to_string(&tapered).unwrap()).unwrap();
+        let eval = FeatureEval::from_weights_file(&path, 15.0).unwrap();
+        assert_eq!(
+            eval.evaluate(&board),
+            FeatureEval::new(endgame, 15.0).evaluate(&board)
+        );
+        assert_ne!(
+            eval.evaluate(&board),
+            FeatureEval::new(Weights::standard(), 15.0).evaluate(&board)
+        );
+
+        let json = serde_json::to_string(&tapered)
+            .unwrap()
+            .replacen("[2.0,-2.0]", "[2.0]", 1);
+        fs::write(&path, json).unwrap();
+        let error = FeatureEval::from_weights_file(&path, 15.0).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("invalid length 1"));
+
+        let json = serde_json::to_string(&Weights::standard())
+            .unwrap()
+            .replace("[-0.5,0.5]", "[-0.5]");
+        fs::write(&path, json).unwrap();
+        let error = FeatureEval::from_weights_file(&path, 15.0).err().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("invalid length 1"));
+
+        let error = FeatureEval::from_weights_file(&path, 15.0).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_feature_cache() {
+        let eval = FeatureEval::new(Weights::standard(), 15.0).with_feature_cache(1 << 8);
+        let plain = FeatureEval::new(Weights::standard(), 15.0);
+        assert_eq!(plain.feature_cache_hit_rate(), None);
+        assert_eq!(eval.feature_cache_hit_rate(), Some(0.0));
+
+        let mut board = MyBoard::initial_board(White);
+        for (from, to) in [(Square::G1, Square::F3), (Square::G8, Square::F6)] {
+            assert_eq!(eval.evaluate(&board), plain.evaluate(&board));
+            board.apply_move(chess::ChessMove::new(from, to, None));
+            board.apply_bonus(false);
+        }
+        assert_eq!(eval.feature_cache_hit_rate(), Some(0.0));
+
+        // The features are cached regardless of who is to move
+        assert_eq!(
+            eval.evaluate_as(&board, Black),
+            plain.evaluate_as(&board, Black)
+        );
+        assert_eq!(
+            eval.evaluate_as(&board, White),
+            plain.evaluate_as(&board, White)
+        );
+        assert_eq!(eval.feature_cache_hit_rate(), Some(0.25));
+        assert_eq!(eval.statistics().unwrap(), "feature cache hit rate: 25.0%");
+
+        eval.clear_feature_cache();
+        assert_eq!(eval.feature_cache_hit_rate(), Some(0.0));
+        eval.evaluate(&board);
+        assert_eq!(eval.feature_cache_hit_rate(), Some(0.0));
+    }
+
+    #[test]
+    fn test_bishop_pair() {
+        // White has both bishops, black has a bishop and a knight
+        let board =
+            MyBoard::from_fen("2b1k1n1/pppppppp/8/8/8/8/PPPPPPPP/2B1KB2 w - - 0 1").unwrap();
+        let features = Features::from_board(&board);
+        assert_eq!(features.bishop_pair, [1.0, 0.0]);
+
+        let with_pair = FeatureEval::new(
+            Weights {
+                bishop_pair: [0.5, -0.5],
+                ..Weights::standard()
+            },
+            15.0,
+        );
+        assert!(
+            with_pair.evaluate(&board)
+                > FeatureEval::new(Weights::standard(), 15.0).evaluate(&board)
+        );
+        assert_eq!(
+            Features::from_board(&MyBoard::initial_board(White)).bishop_pair,
+            [1.0, 1.0]
+        );
+
+        // Weights saved without the bishop pair still load
+        let json = r#"{"pieces": [[1, 3, 3, 5, 9, 0], [-1, -3, -3, -5, -9, 0]],
+            "king_danger": [-0.5, 0.5], "pawn_advancement": [0.5, -0.5],
+            "king_escapes": [0.5, -0.5], "side_to_move": 3.0}"#;
+        let weights: Weights = serde_json::from_str(json).unwrap();
+        assert_eq!(weights.bishop_pair, [0.0; 2]);
+
+        // Features saved without it don't, since they would be wrong
+        let mut json = serde_json::to_value(&features).unwrap();
+        json.as_object_mut().unwrap().remove("bishop_pair");
+        assert!(serde_json::from_value::<Features>(json).is_err());
+    }
+
+    #[test]
+    fn test_blocked_pawn_advancement() {
+        let mobile = MyBoard::from_piece_list(Color::White, &[
+            (Square::E1, Piece::King, Color::White),
+            (Square::D5, Piece::Pawn, Color::White),
+            (Square::E8, Piece::King, Color::Black),
+            (Square::A7, Piece::Pawn, Color::Black),
+        ]);
+        let blocked_by_enemy = MyBoard::from_piece_list(Color::White, &[
+            (Square::E1, Piece::King, Color::White),
+            (Square::D5, Piece::Pawn, Color::White),
+            (Square::D6, Piece::Knight, Color::Black),
+            (Square::E8, Piece::King, Color::Black),
+            (Square::A7, Piece::Pawn, Color::Black),
+        ]);
+        let blocked_by_friend = MyBoard::from_piece_list(Color::White, &[
+            (Square::E1, Piece::King, Color::White),
+            (Square::D5, Piece::Pawn, Color::White),
+            (Square::D6, Piece::Knight, Color::White),
+            (Square::E8, Piece::King, Color::Black),
+            (Square::A7, Piece::Pawn, Color::Black),
+        ]);
+
+        let mobile_advancement = Features::pawn_advancement(&mobile);
+        assert_eq!(mobile_advancement, [3.0, 0.0]);
+        for blocked in [blocked_by_enemy, blocked_by_friend] {
+            let advancement = Features::pawn_advancement(&blocked);
+            assert!(advancement[0] < mobile_advancement[0]);
+            assert_eq!(advancement[1], mobile_advancement[1]);
+        }
+
+        // A black pawn is blocked by a piece below it
+        let mut blocked_black = mobile;
+        blocked_black.invert_ranks_and_colors();
+        let mut enemy_blocked_black = blocked_by_enemy;
+        enemy_blocked_black.invert_ranks_and_colors();
+        assert!(
+            Features::pawn_advancement(&enemy_blocked_black)[1]
+                < Features::pawn_advancement(&blocked_black)[1]
+        );
+
+        // A blocked pawn behind its starting rank hasn't advanced at all, so
+        // the discount can't take it below zero
+        let behind = MyBoard::from_piece_list(Color::White, &[
+            (Square::E1, Piece::King, Color::White),
+            (Square::D1, Piece::Pawn, Color::White),
+            (Square::D2, Piece::Knight, Color::White),
+            (Square::E8, Piece::King, Color::Black),
+        ]);
+        assert_eq!(Features::pawn_advancement(&behind), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_king_escapes() {
+        let position = |bishop| {
+            MyBoard::from_piece_list(Color::White, &[
+                (Square::E1, Piece::King, Color::White),
+                (bishop, Piece::Bishop, Color::White),
+                (Square::H8, Piece::King, Color::Black),
+                (Square::G7, Piece::Pawn, Color::Black),
+                (Square::H7, Piece::Pawn, Color::Black),
+            ])
+        };
+        let free = position(Square::A3);
+        // The bishop attacks g8, the black king's only escape
+        let trapped = position(Square::C4);
+
+        assert_eq!(Features::from_board(&free).king_escapes[1], 1.0);
+        assert_eq!(Features::from_board(&trapped).king_escapes[1], 0.0);
+
+        let eval = FeatureEval::new(Weights::standard(), 15.0);
+        assert!(eval.evaluate(&trapped) > eval.evaluate(&free));
+    }
+
+    #[test]
+    fn test_mobility_scaling() {
+        // White is a rook up in both positions, but in the first the rook is
+        // stuck in the corner
+        let with_rook_on = |rook_square| {
+            MyBoard::from_piece_list(Color::White, &[
+                (Square::G1, Piece::King, Color::White),
+                (rook_square, Piece::Rook, Color::White),
+                (Square::F2, Piece::Pawn, Color::White),
+                (Square::G2, Piece::Pawn, Color::White),
+                (Square::H2, Piece::Pawn, Color::White),
+                (Square::E8, Piece::King, Color::Black),
+                (Square::D7, Piece::Pawn, Color::Black),
+                (Square::E7, Piece::Pawn, Color::Black),
+                (Square::F7, Piece::Pawn, Color::Black),
+            ])
+        };
+        let blocked = with_rook_on(Square::H1);
+        let open = with_rook_on(Square::D4);
+
+        let plain = FeatureEval::new(Weights::standard(), 15.0);
+        assert!(plain.evaluate(&blocked) >= plain.evaluate(&open));
+
+        let scaled = FeatureEval::new(Weights::standard(), 15.0).with_mobility_scaling(30.0);
+        assert!(scaled.evaluate(&blocked) < scaled.evaluate(&open));
+        assert!(scaled.evaluate(&blocked) > Score::from_num(0.5));
+        assert_eq!(scaled.evaluate(&open), plain.evaluate(&open));
+    }
+
+    #[test]
+    fn test_draw_table() {
+        let board = MyBoard::from_piece_list(Color::White, &[
+            (Square::E1, Piece::King, Color::White),
+            (Square::B1, Piece::Knight, Color::White),
+            (Square::E8, Piece::King, Color::Black),
+        ]);
+
+        let eval = FeatureEval::new(Weights::standard(), 15.0);
+        assert!(eval.evaluate(&board) > ONE / 2);
+
+        let table = DrawTable::new().with_draw(&[Piece::Knight], &[]);
+        let eval = eval.with_draw_table(table);
+        assert_eq!(eval.evaluate(&board), ONE / 2);
+        assert_ne!(
+            eval.evaluate(&MyBoard::initial_board(Color::White)),
+            ONE / 2
+        );
+    }
+
+    #[test]
+    fn test_weight_gradient() {
+        let mut board = MyBoard::initial_board(Color::White);
+        for (from, to) in [
+            (Square::E2, Square::E4),
+            (Square::D7, Square::D5),
+            (Square::E4, Square::D5),
+            (Square::G8, Square::F6),
+        ] {
+            board.apply_move(chess::ChessMove::new(from, to, None));
+            board.apply_bonus(false);
+        }
+        let target = 0.8;
+        let weights = Weights::standard().to_vec();
+
+        let loss = |weights: &[f32]| {
+            let eval = FeatureEval::new(Weights::from_vec(weights), 15.0);
+            (eval.evaluate(&board).to_num::<f32>() - target).powi(2)
+        };
+
+        let gradient = FeatureEval::new(Weights::standard(), 15.0)
+            .weight_gradient(&board, target)
+            .to_vec();
+
+        let h = 0.01;
+        for (i, analytic) in gradient.into_iter().enumerate() {
+            let (mut above, mut below) = (weights.clone(), weights.clone());
+            above[i] += h;
+            below[i] -= h;
+            let numeric = (loss(&above) - loss(&below)) / (2.0 * h);
+            assert!(
+                (numeric - analytic).abs() < 1e-4,
+                "weight {}: {} != {}",
+                i,
+                numeric,
+                analytic
+            );
+        }
+    }
+
+    #[test]
+    fn test_feature_importance() {
+        let mut weights = Weights {
+            pieces: [[1.0; 6]; 2],
+            king_danger: [1.0; 2],
+            pawn_advancement: [1.0; 2],
+            king_escapes: [1.0; 2],
+            bishop_pair: [1.0; 2],
+            side_to_move: 0.0,
+        };
+        weights.pieces[0][Queen.to_index()] = 9.0;
+
+        // Every feature varies in the same way, except the white queens which
+        // vary twice as much
+        let samples: Vec<_> = (0..10)
+            .map(|i| {
+                let x = i as f32;
+                let mut pieces = [[x; 6]; 2];
+                pieces[0][Queen.to_index()] = 2.0 * x;
+                Features {
+                    pieces,
+                    king_danger: [x; 2],
+                    pawn_advancement: [x; 2],
+                    king_escapes: [x; 2],
+                    bishop_pair: [x; 2],
+                    side_to_move: x,
+                }
+            })
+            .collect();
+
+        assert_eq!(samples[3].to_vec().len(), Features::names().len());
+        assert_eq!(weights.to_vec().len(), Features::names().len());
+
+        let importance = feature_importance(&weights, &samples);
+        assert_eq!(importance.len(), Features::names().len());
+        assert_eq!(importance[0].0, "white queens");
+        assert_eq!(
+            importance.last().unwrap(),
+            &("side to move".to_string(), 0.0)
+        );
+        assert!(importance[1].1 > 0.0 && importance[1].1 < importance[0].1);
+    }
+
+    #[test]
+    fn test_bonus_chance_tempo() {
+        let board = MyBoard::from_fen("4k3/pppp4/8/8/8/8/PPP5/4K3 w - - 0 1").unwrap();
+        let side_to_move_contribution = |chance: f32| {
+            let eval = FeatureEval::new(Weights::standard(), 15.0).with_bonus_chance(chance);
+            let as_white = eval.evaluate_as(&board, White).to_num::<f32>();
+            let as_black = eval.evaluate_as(&board, Black).to_num::<f32>();
+            as_white - as_black
+        };
+
+        // The default chance leaves the evaluation unchanged
+        let default_chance = crate::bonus_chance().to_num::<f32>();
+        let plain = FeatureEval::new(Weights::standard(), 15.0);
+        assert_eq!(
+            FeatureEval::new(Weights::standard(), 15.0)
+                .with_bonus_chance(default_chance)
+                .evaluate(&board),
+            plain.evaluate(&board)
+        );
+
+        let low = side_to_move_contribution(0.1);
+        let default = side_to_move_contribution(default_chance);
+        let high = side_to_move_contribution(0.5);
+        assert!(0.0 < low && low < default && default < high);
+    }
+
+    #[test]
+    fn test_endgame_weights() {
+        let initial = MyBoard::initial_board(White);
+        let pawns = MyBoard::from_fen("4k3/pppp4/8/8/8/8/PPP5/4K3 w - - 0 1").unwrap();
+        let rooks = MyBoard::from_fen("r3k3/pppp4/8/8/8/8/PPP5/R3K3 w - - 0 1").unwrap();
+        assert_eq!(game_phase(&initial), MAX_PHASE);
+        assert_eq!(game_phase(&pawns), 0);
+        assert_eq!(game_phase(&rooks), 4);
+
+        let opening = Weights::standard();
+        let endgame = Weights {
+            king_escapes: [2.0, -2.0],
+            ..opening
+        };
+        assert_eq!(
+            opening.tapered(&endgame, MAX_PHASE).to_vec(),
+            opening.to_vec()
+        );
+        assert_eq!(opening.tapered(&endgame, 0).to_vec(), endgame.to_vec());
+        assert_eq!(opening.tapered(&endgame, 12).king_escapes, [1.25, -1.25]);
+
+        let tapered = FeatureEval::new(opening, 15.0).with_endgame_weights(endgame);
+        assert_eq!(
+            tapered.evaluate(&initial),
+            FeatureEval::new(opening, 15.0).evaluate(&initial)
+        );
+        assert_eq!(
+            tapered.evaluate(&pawns),
+            FeatureEval::new(endgame, 15.0).evaluate(&pawns)
+        );
+    }
 }
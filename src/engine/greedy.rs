@@ -1,6 +1,7 @@
 use super::{Engine, StaticEvaluator};
 use crate::{logger::Logger, my_board::MyBoard, Score};
 
+#[allow(dead_code)]
 pub struct Greedy {
     static_evaluator: Box<dyn StaticEvaluator>,
     logger: Logger,
@@ -16,5 +17,9 @@ impl Engine for Greedy {
 
     fn evaluate(&mut self, board: &MyBoard) -> Score { self.static_evaluator.evaluate(board) }
 
+    fn describe(&self) -> String {
+        format!("Greedy(evaluator: {})", self.static_evaluator.describe())
+    }
+
     fn get_logger(&self) -> &Logger { &self.logger }
 }
@@ -1,26 +1,41 @@
 use chess::Color;
 
-use super::{position_table::PositionTable, Engine, StaticEvaluator};
-use crate::{logger::Logger, my_board::MyBoard, Score};
+use super::{position_table::PositionTable, weighted_score, Engine, StaticEvaluator};
+use crate::{logger::Logger, my_board::MyBoard, Score, ONE};
 
+#[allow(dead_code)]
 pub struct Minimax {
     static_evaluator: Box<dyn StaticEvaluator>,
     lookahead: u8,
+    bonus_chance: Score,
     position_table: PositionTable<Score>,
     logger: Logger,
 }
 
+#[allow(dead_code)]
 impl Minimax {
     pub fn new(static_evaluator: impl StaticEvaluator + 'static, lookahead: u8) -> Self {
         let logger = Logger::new(0);
         Minimax {
             static_evaluator: Box::new(static_evaluator),
             lookahead,
+            bonus_chance: crate::bonus_chance(),
             position_table: PositionTable::new(&logger),
             logger,
         }
     }
 
+    /// Makes the engine assume a bonus happens with probability `chance`,
+    /// rather than `crate::bonus_chance`, for playing other variants
+    pub fn with_bonus_chance(mut self, chance: Score) -> Self {
+        assert!(
+            chance > Score::ZERO && chance < ONE,
+            "bonus chance must be between 0 and 1"
+        );
+        self.bonus_chance = chance;
+        self
+    }
+
     fn evaluate_with_cutoff(&mut self, board: &MyBoard, cutoff: u8) -> Score {
         if let Some(score) = self.position_table.get(board, cutoff) {
             return score;
@@ -28,8 +43,16 @@ impl Minimax {
 
         if cutoff == 0 || !board.get_status().is_in_progress() {
             let evaluation = self.static_evaluator.evaluate(board);
-            self.position_table
-                .insert_both_colors(board, cutoff, evaluation);
+            if self.static_evaluator.ignores_side_to_move() {
+                self.position_table.insert_both_colors(
+                    board,
+                    cutoff,
+                    evaluation,
+                    self.static_evaluator.as_ref(),
+                );
+            } else {
+                self.position_table.insert(board, cutoff, evaluation);
+            }
             return evaluation;
         }
 
@@ -38,9 +61,12 @@ impl Minimax {
             // rare and also the most expensive part
             let (bonus_board, no_bonus_board) = self.next_boards(board, mv, cutoff != 1);
 
-            // Assumes the chance of bonus and chance of no bonus
-            self.evaluate_with_cutoff(&bonus_board, cutoff - 1) * crate::bonus_chance()
-                + self.evaluate_with_cutoff(&no_bonus_board, cutoff - 1) * crate::no_bonus_chance()
+            weighted_score(
+                self.evaluate_with_cutoff(&bonus_board, cutoff - 1),
+                self.bonus_chance,
+                self.evaluate_with_cutoff(&no_bonus_board, cutoff - 1),
+                ONE - self.bonus_chance,
+            )
         });
 
         let score = if board.get_side_to_move() == Color::White {
@@ -64,5 +90,20 @@ impl Engine for Minimax {
         self.evaluate_with_cutoff(board, self.lookahead - 1)
     }
 
+    fn bonus_chance(&self) -> Score { self.bonus_chance }
+
+    fn set_max_lookahead(&mut self, max_lookahead: u8) {
+        assert!(max_lookahead > 0, "lookahead must be positive");
+        self.lookahead = max_lookahead;
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "Minimax(evaluator: {}, lookahead: {})",
+            self.static_evaluator.describe(),
+            self.lookahead
+        )
+    }
+
     fn get_logger(&self) -> &Logger { &self.logger }
 }
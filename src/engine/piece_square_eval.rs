@@ -0,0 +1,212 @@
+use chess::Color::*;
+
+use super::feature_eval::sigmoid;
+use crate::{MyBoard, Score, StaticEvaluator};
+
+/// The value of each piece on each square for white, indexed by the piece's
+/// index and then the square's index (so `a1` is first). Black's values are
+/// the same with the ranks mirrored.
+pub type PieceSquareTables = [[i16; 64]; 6];
+
+/// Adds `value` to every square of `rows`, which are written with rank 8
+/// first so that they look like the board from white's side
+const fn table(value: i16, rows: [i16; 64]) -> [i16; 64] {
+    let mut table = [0; 64];
+    let mut i = 0;
+    while i < 64 {
+        table[i ^ 56] = value + rows[i];
+        i += 1;
+    }
+    table
+}
+
+/// Tables in centipawns which value the pieces as usual and prefer knights
+/// and bishops in the centre, advanced pawns, rooks on the seventh rank, and
+/// the king behind its pawns. They go with a `scale_down` of 1500, which
+/// matches `FeatureEval` with pawns worth 1.
+#[rustfmt::skip]
+pub const DEFAULT_TABLES: PieceSquareTables = [
+    table(100, [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         50,  50,  50,  50,  50,  50,  50,  50,
+         10,  10,  20,  30,  30,  20,  10,  10,
+          5,   5,  10,  25,  25,  10,   5,   5,
+          0,   0,   0,  20,  20,   0,   0,   0,
+          5,  -5, -10,   0,   0, -10,  -5,   5,
+          5,  10,  10, -20, -20,  10,  10,   5,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ]),
+    table(320, [
+        -50, -40, -30, -30, -30, -30, -40, -50,
+        -40, -20,   0,   0,   0,   0, -20, -40,
+        -30,   0,  10,  15,  15,  10,   0, -30,
+        -30,   5,  15,  20,  20,  15,   5, -30,
+        -30,   0,  15,  20,  20,  15,   0, -30,
+        -30,   5,  10,  15,  15,  10,   5, -30,
+        -40, -20,   0,   5,   5,   0, -20, -40,
+        -50, -40, -30, -30, -30, -30, -40, -50,
+    ]),
+    table(330, [
+        -20, -10, -10, -10, -10, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,  10,  10,   5,   0, -10,
+        -10,   5,   5,  10,  10,   5,   5, -10,
+        -10,   0,  10,  10,  10,  10,   0, -10,
+        -10,  10,  10,  10,  10,  10,  10, -10,
+        -10,   5,   0,   0,   0,   0,   5, -10,
+        -20, -10, -10, -10, -10, -10, -10, -20,
+    ]),
+    table(500, [
+          0,   0,   0,   0,   0,   0,   0,   0,
+          5,  10,  10,  10,  10,  10,  10,   5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+          0,   0,   0,   5,   5,   0,   0,   0,
+    ]),
+    table(900, [
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+         -5,   0,   5,   5,   5,   5,   0,  -5,
+         -5,   0,   5,   5,   5,   5,   0,  -5,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+    ]),
+    table(0, [
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -20, -30, -30, -40, -40, -30, -30, -20,
+        -10, -20, -20, -20, -20, -20, -20, -10,
+         20,  20,   0,   0,   0,   0,  20,  20,
+         20,  30,  10,   0,   0,  10,  30,  20,
+    ]),
+];
+
+/// Evaluates a board by summing a value for each piece depending on the
+/// square it is on, which gives some positional sense (such as centralising
+/// knights and keeping the king safe) that counting material misses
+#[derive(Clone)]
+pub struct PieceSquareEval {
+    /// The tables for each color, where black's are white's mirrored and
+    /// negated
+    tables: [PieceSquareTables; 2],
+    scale_down: f32,
+}
+
+impl StaticEvaluator for PieceSquareEval {
+    fn evaluate(&self, board: &MyBoard) -> Score {
+        if !board.get_status().is_in_progress() {
+            return self.evaluate_terminal(board).unwrap();
+        }
+
+        let total: i32 = board
+            .pieces()
+            .map(|(square, piece, color)| {
+                self.tables[color.to_index()][piece.to_index()][square.to_index()] as i32
+            })
+            .sum();
+
+        Score::from_num(sigmoid(total as f32 / self.scale_down))
+    }
+
+    fn ignores_side_to_move(&self) -> bool { true }
+
+    fn clone_for_thread(&self) -> Option<Box<dyn StaticEvaluator>> { Some(Box::new(self.clone())) }
+}
+
+impl PieceSquareEval {
+    /// Creates an evaluator from white's tables, where the sum of the values
+    /// is divided by `scale_down` before being mapped onto a score with the
+    /// same sigmoid as `FeatureEval`
+    pub fn new(tables: PieceSquareTables, scale_down: f32) -> PieceSquareEval {
+        assert!(scale_down > 0.0, "scale_down must be positive");
+        let mut black = [[0; 64]; 6];
+        for (black, white) in black.iter_mut().zip(&tables) {
+            for (square, value) in black.iter_mut().enumerate() {
+                *value = -white[square ^ 56];
+            }
+        }
+        let mut both = [[[0; 64]; 6]; 2];
+        both[White.to_index()] = tables;
+        both[Black.to_index()] = black;
+        PieceSquareEval {
+            tables: both,
+            scale_down,
+        }
+    }
+}
+
+impl Default for PieceSquareEval {
+    fn default() -> Self { PieceSquareEval::new(DEFAULT_TABLES, 1500.0) }
+}
+
+#[cfg(test)]
+mod tests {
+    use chess::{ChessMove, Color, Piece, Square};
+
+    use super::*;
+    use crate::{engine::assert_evaluation_symmetric, my_board::Status};
+
+    #[test]
+    fn test_piece_square_eval() {
+        let eval = PieceSquareEval::default();
+        let initial = MyBoard::initial_board(Color::White);
+        assert_eq!(eval.evaluate(&initial), Score::from_num(0.5));
+        assert_eq!(
+            eval.evaluate(&initial),
+            eval.evaluate(&initial.with_side_switched())
+        );
+
+        // A knight is better in the centre than in the corner
+        let knight_on = |square| {
+            MyBoard::from_piece_list(Color::White, &[
+                (Square::E1, Piece::King, Color::White),
+                (Square::E8, Piece::King, Color::Black),
+                (square, Piece::Knight, Color::White),
+            ])
+        };
+        let centre = eval.evaluate(&knight_on(Square::E4));
+        assert!(centre > eval.evaluate(&knight_on(Square::A1)));
+        assert!(centre > Score::from_num(0.5));
+
+        // Black's tables are white's mirrored
+        let mut mirrored = knight_on(Square::E4);
+        mirrored.invert_ranks_and_colors();
+        let tolerance = Score::from_num(1e-6);
+        assert!(eval.evaluate(&mirrored).abs_diff(Score::ONE - centre) <= tolerance);
+
+        let mut board = initial;
+        for i in 0..20 {
+            if !board.get_status().is_in_progress() {
+                break;
+            }
+            assert_evaluation_symmetric(&eval, &board, tolerance);
+            let moves: Vec<_> = board.all_moves().collect();
+            board.apply_move(moves[(i * 5) % moves.len()]);
+            board.apply_bonus(false);
+        }
+    }
+
+    #[test]
+    fn test_piece_square_eval_terminal() {
+        let eval = PieceSquareEval::default();
+        let mut board = MyBoard::initial_board(Color::White);
+        for (mv, is_bonus) in [
+            (ChessMove::new(Square::E2, Square::E4, None), true),
+            (ChessMove::new(Square::D1, Square::H5, None), false),
+            (ChessMove::new(Square::F7, Square::F6, None), false),
+            (ChessMove::new(Square::H5, Square::E8, None), false),
+        ] {
+            board.apply_move(mv);
+            board.apply_bonus(is_bonus);
+        }
+        assert_eq!(board.get_status(), Status::Win(Color::White));
+        assert_eq!(eval.evaluate(&board), Score::ONE);
+    }
+}
@@ -1,3 +1,9 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+#[cfg(any(test, feature = "verify_positions"))]
+use chess::{CastleRights, Color, Piece, Square, ALL_SQUARES};
+
+use super::StaticEvaluator;
 use crate::{logger::Logger, my_board::MyBoard};
 
 // 2^26 is the maximum we can get with Vec's allocation (for 32 bytes)
@@ -5,16 +11,34 @@ use crate::{logger::Logger, my_board::MyBoard};
 // especially with the debug build
 const TABLE_SIZE: usize = 1 << 22;
 
+/// If even a table of this size can't be allocated, we give up
+const MIN_TABLE_SIZE: usize = 1 << 10;
+
+/// Adds one to a debug counter. The counters are only kept with the
+/// `table_stats` feature, since they would otherwise be updated on every
+/// probe of the table just to be logged at the end of the search.
+macro_rules! count {
+    ($counter:expr) => {
+        #[cfg(feature = "table_stats")]
+        {
+            $counter += 1;
+        }
+    };
+}
+
 #[derive(Clone, Copy)]
 struct Parameters {
     pub depth: u8,
     pub dead_moves: u8,
+    pub dead_move_limit: u8,
 }
 
 #[derive(Clone, Copy)]
 struct Evaluation<S> {
     pub position: Position,
     pub parameters: Parameters,
+    /// The generation of the table when this was inserted
+    pub generation: u8,
     pub score: S,
 }
 
@@ -22,19 +46,40 @@ struct Evaluation<S> {
 /// represent the board state. We ignore the possibility of hash collisions
 /// since it's unlikely, as per https://craftychess.com/hyatt/collisions.html.
 ///
-/// The type could be extended with extra information to allow more thorough
-/// checking.
-///
-/// Note that en passant is not implemented, so it isn't included in the state
+/// With the `verify_positions` feature, the rest of the board state is
+/// stored too, so that collisions are detected and rejected. This makes each
+/// entry much larger.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 struct Position {
     zobrist_hash: u64,
+    #[cfg(feature = "verify_positions")]
+    full: FullPosition,
+}
+
+/// Everything about a board that its zobrist hash represents, so that two
+/// boards are the same position exactly when these are equal
+#[cfg(any(test, feature = "verify_positions"))]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct FullPosition {
+    pieces: [Option<(Piece, Color)>; 64],
+    castle_rights: [CastleRights; 2],
+    side_to_move: Color,
+    en_passant: Option<Square>,
 }
 
 pub struct PositionTable<S: Copy> {
     table: Box<[Option<Evaluation<S>>]>,
+    /// Advanced by `new_generation`, so that entries from earlier searches
+    /// can be told apart from those of the current one
+    generation: u8,
     // Debug info
     items: usize,
+    debug: DebugInfo,
+}
+
+/// Counts of how the insertions into and lookups from a table went
+#[derive(Clone, Copy, Default)]
+struct DebugInfo {
     insert_attempts: u64,
     insert_additions: u64,
     insert_ignores: u64,
@@ -43,11 +88,42 @@ pub struct PositionTable<S: Copy> {
     get_blanks: u64,
     get_hits: u64,
     get_incorrects: u64,
+    /// Entries with the same hash but a different position. These can only
+    /// be detected with the `verify_positions` feature.
+    collisions_detected: u64,
 }
 
 impl<S: Copy> PositionTable<S> {
-    pub fn new(logger: &Logger) -> PositionTable<S> {
-        let table = vec![None; TABLE_SIZE].into_boxed_slice();
+    pub fn new(logger: &Logger) -> PositionTable<S> { Self::with_capacity(TABLE_SIZE, logger) }
+
+    /// Creates a table with space for `capacity` entries, rounded down to a
+    /// power of two so that positions can be indexed with a mask. Capacities
+    /// larger than `TABLE_SIZE` are reduced to it, and if the allocation
+    /// fails, the capacity is halved until it succeeds. A smaller table makes
+    /// the search slower, but it still works correctly.
+    pub fn with_capacity(capacity: usize, logger: &Logger) -> PositionTable<S> {
+        let mut capacity = 1 << capacity.clamp(1, TABLE_SIZE).ilog2();
+        let table = loop {
+            let mut table = Vec::new();
+            if table.try_reserve_exact(capacity).is_ok() {
+                table.resize(capacity, None);
+                break table.into_boxed_slice();
+            }
+            assert!(
+                capacity > MIN_TABLE_SIZE,
+                "could not allocate a position table of {} elements",
+                capacity
+            );
+            logger.log(
+                1,
+                &format!(
+                    "Could not allocate a position table of {} elements, trying {}",
+                    capacity,
+                    capacity / 2
+                ),
+            );
+            capacity /= 2;
+        };
         logger.log(
             4,
             &format!(
@@ -59,25 +135,16 @@ impl<S: Copy> PositionTable<S> {
         );
         PositionTable {
             table,
+            generation: 0,
             items: 0,
-            insert_attempts: 0,
-            insert_additions: 0,
-            insert_ignores: 0,
-            insert_overwrites: 0,
-            get_attempts: 0,
-            get_blanks: 0,
-            get_hits: 0,
-            get_incorrects: 0,
+            debug: DebugInfo::default(),
         }
     }
 
     /// Insert a board into the position table if we don't already have
     /// something better
     pub fn insert(&mut self, board: &MyBoard, depth: u8, score: S) {
-        let new_params = Parameters {
-            depth,
-            dead_moves: board.get_dead_moves(),
-        };
+        let new_params = Parameters::new(board, depth);
         let position = Position::from_board(board);
         self.insert_position(position, new_params, score);
     }
@@ -85,25 +152,56 @@ impl<S: Copy> PositionTable<S> {
     /// Insert a board into the position table for both colors if we don't
     /// already have something better. This might be useful when the depth
     /// is 0 and so the evaluation is known to be the same for both colors.
-    pub fn insert_both_colors(&mut self, board: &MyBoard, depth: u8, score: S) {
-        let new_params = Parameters {
-            depth,
-            dead_moves: board.get_dead_moves(),
-        };
+    ///
+    /// This is only valid if `evaluator` produced the score and ignores the
+    /// side to move, which is checked in debug builds.
+    pub fn insert_both_colors(
+        &mut self, board: &MyBoard, depth: u8, score: S, evaluator: &dyn StaticEvaluator,
+    ) {
+        debug_assert!(
+            evaluator.evaluate(board) == evaluator.evaluate(&board.with_side_switched()),
+            "insert_both_colors called with an evaluation that depends on the side to move"
+        );
+        let new_params = Parameters::new(board, depth);
         let mut position = Position::from_board(board);
         self.insert_position(position, new_params, score);
         position.switch_side_to_move();
         self.insert_position(position, new_params, score);
     }
 
-    /// Insert a position and score into the table if the new parameters are
-    /// `not_worse_than` the existing parameters.
+    /// Removes every entry, leaving the table as it was when it was created
+    /// without reallocating it. This is for starting a new game, where the
+    /// old entries are very unlikely to be useful.
+    pub fn clear(&mut self) {
+        self.table.fill(None);
+        self.generation = 0;
+        self.items = 0;
+        self.reset_debug_info();
+    }
+
+    /// Starts a new generation, which makes the entries inserted so far the
+    /// first to be overwritten. This should be called for each new search,
+    /// so that deep entries from positions earlier in the game don't take up
+    /// space that the current search could use.
+    pub fn new_generation(&mut self) { self.generation = self.generation.wrapping_add(1); }
+
+    /// Insert a position and score into the table, unless the entry already
+    /// there is better. For the same position, that is when the new
+    /// parameters shouldn't replace the existing ones, whatever generation
+    /// they are from, since a deeper result for the position is still valid.
+    /// For a different position, it is when the existing entry is from the
+    /// current generation and is deeper.
     fn insert_position(&mut self, position: Position, params: Parameters, score: S) {
-        self.insert_attempts += 1;
+        count!(self.debug.insert_attempts);
 
-        if match self.table[position.as_index()] {
+        if match self.table[position.as_index(self.table.len())] {
+            Some(evaluation) if position.collides_with(&evaluation.position) => {
+                count!(self.debug.collisions_detected);
+                count!(self.debug.insert_overwrites);
+                true
+            }
             None => {
-                self.insert_additions += 1;
+                count!(self.debug.insert_additions);
                 self.items += 1;
                 true
             }
@@ -111,17 +209,29 @@ impl<S: Copy> PositionTable<S> {
                 if evaluation.position == position
                     && !params.should_replace(&evaluation.parameters) =>
             {
-                self.insert_ignores += 1;
+                count!(self.debug.insert_ignores);
+                false
+            }
+            Some(evaluation) if evaluation.generation != self.generation => {
+                count!(self.debug.insert_overwrites);
+                true
+            }
+            Some(evaluation)
+                if evaluation.position != position
+                    && params.depth < evaluation.parameters.depth =>
+            {
+                count!(self.debug.insert_ignores);
                 false
             }
             Some(_) => {
-                self.insert_overwrites += 1;
+                count!(self.debug.insert_overwrites);
                 true
             }
         } {
-            self.table[position.as_index()] = Some(Evaluation {
+            self.table[position.as_index(self.table.len())] = Some(Evaluation {
                 position,
                 parameters: params,
+                generation: self.generation,
                 score,
             });
         }
@@ -130,30 +240,30 @@ impl<S: Copy> PositionTable<S> {
     /// Get the score of a board if we have an existing evaluation of this
     /// board. Needs to be mutable to update the debug info
     pub fn get(&mut self, board: &MyBoard, depth: u8) -> Option<S> {
-        self.get_attempts += 1;
+        count!(self.debug.get_attempts);
 
-        let params = Parameters {
-            depth,
-            dead_moves: board.get_dead_moves(),
-        };
+        let params = Parameters::new(board, depth);
 
         let pos = Position::from_board(board);
 
-        match self.table[pos.as_index()] {
+        match self.table[pos.as_index(self.table.len())] {
             // The position is different, so we can't use the evaluation
             Some(evaluation) if evaluation.position != pos => {
-                self.get_incorrects += 1;
+                if pos.collides_with(&evaluation.position) {
+                    count!(self.debug.collisions_detected);
+                }
+                count!(self.debug.get_incorrects);
                 None
             }
             // The position is the same and the parameters are the same or
             // better, so we can use the evaluation
             Some(evaluation) if evaluation.parameters.better_than(&params) => {
-                self.get_hits += 1;
+                count!(self.debug.get_hits);
                 Some(evaluation.score)
             }
             // There is nothing in the table
             _ => {
-                self.get_blanks += 1;
+                count!(self.debug.get_blanks);
                 None
             }
         }
@@ -167,13 +277,151 @@ impl<S: Copy> PositionTable<S> {
     /// This board. This version doesn't update the debug info.
     pub fn get_lenient(&self, board: &MyBoard) -> Option<S> {
         let pos = Position::from_board(board);
-        match self.table[pos.as_index()] {
+        match self.table[pos.as_index(self.table.len())] {
             Some(evaluation) if evaluation.position == pos => Some(evaluation.score),
             _ => None,
         }
     }
 
+    pub fn reset_debug_info(&mut self) { self.debug = DebugInfo::default(); }
+}
+
+/// A position table split into shards behind their own locks, so that it can
+/// be shared between searches running on several threads. Cloning it gives
+/// another handle to the same table. Positions are assigned to shards by
+/// their hash, so threads only wait for each other when they happen to use
+/// the same shard at the same time. While there is only one handle, such as
+/// for a search on a single thread, the shards are used without locking.
+pub struct ShardedPositionTable<S: Copy> {
+    shards: Arc<[Mutex<PositionTable<S>>]>,
+}
+
+impl<S: Copy> Clone for ShardedPositionTable<S> {
+    fn clone(&self) -> Self {
+        ShardedPositionTable {
+            shards: Arc::clone(&self.shards),
+        }
+    }
+}
+
+/// A table without any shards, which can't be used. This is only a
+/// placeholder for a table that has been lent out.
+impl<S: Copy> Default for ShardedPositionTable<S> {
+    fn default() -> Self {
+        ShardedPositionTable {
+            shards: Arc::new([]),
+        }
+    }
+}
+
+impl<S: Copy> ShardedPositionTable<S> {
+    pub fn new(shards: usize, logger: &Logger) -> ShardedPositionTable<S> {
+        Self::with_capacity(TABLE_SIZE, shards, logger)
+    }
+
+    /// Creates a table with space for about `capacity` entries in total,
+    /// split evenly between `shards` shards. See
+    /// `PositionTable::with_capacity` for how the capacity is limited.
+    pub fn with_capacity(
+        capacity: usize, shards: usize, logger: &Logger,
+    ) -> ShardedPositionTable<S> {
+        assert!(shards > 0, "a position table needs at least one shard");
+        let shard_capacity = capacity.clamp(1, TABLE_SIZE) / shards;
+        ShardedPositionTable {
+            shards: (0..shards)
+                .map(|_| Mutex::new(PositionTable::with_capacity(shard_capacity, logger)))
+                .collect(),
+        }
+    }
+
+    pub fn shards(&self) -> usize { self.shards.len() }
+
+    /// The total number of entries over all the shards
+    pub fn capacity(&self) -> usize { self.map_shards(|shard| shard.table.len()).sum() }
+
+    pub fn insert(&mut self, board: &MyBoard, depth: u8, score: S) {
+        self.with_shard(board, |shard| shard.insert(board, depth, score));
+    }
+
+    pub fn get(&mut self, board: &MyBoard, depth: u8) -> Option<S> {
+        self.with_shard(board, |shard| shard.get(board, depth))
+    }
+
+    pub fn get_lenient(&mut self, board: &MyBoard) -> Option<S> {
+        self.with_shard(board, |shard| shard.get_lenient(board))
+    }
+
+    /// The number of entries in the table that are occupied
+    #[cfg(test)]
+    pub fn items(&self) -> usize { self.map_shards(|shard| shard.items).sum() }
+
+    /// The debug info of all the shards added together
     pub fn info(&self) -> String {
+        let mut debug = DebugInfo::default();
+        for shard_debug in self.map_shards(|shard| shard.debug) {
+            debug += shard_debug;
+        }
+        debug.describe(self.map_shards(|shard| shard.items).sum(), self.capacity())
+    }
+
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            lock(shard).clear();
+        }
+    }
+
+    pub fn new_generation(&self) {
+        for shard in self.shards.iter() {
+            lock(shard).new_generation();
+        }
+    }
+
+    pub fn reset_debug_info(&self) {
+        for shard in self.shards.iter() {
+            lock(shard).reset_debug_info();
+        }
+    }
+
+    /// Runs `f` on the shard that `board` belongs to. The low bits of the
+    /// hash choose the index within a shard, so the high bits are used to
+    /// choose the shard. The shard is only locked if there are other handles
+    /// to the table.
+    fn with_shard<T>(&mut self, board: &MyBoard, f: impl FnOnce(&mut PositionTable<S>) -> T) -> T {
+        let index = (board.get_zobrist_hash() >> 32) as usize % self.shards.len();
+        match Arc::get_mut(&mut self.shards) {
+            Some(shards) => f(shards[index]
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner)),
+            None => f(&mut lock(&self.shards[index])),
+        }
+    }
+
+    fn map_shards<'a, T: 'a>(
+        &'a self, f: impl Fn(&PositionTable<S>) -> T + 'a,
+    ) -> impl Iterator<Item = T> + 'a {
+        self.shards.iter().map(move |shard| f(&lock(shard)))
+    }
+}
+
+/// Locks a shard. A thread can only panic while holding the lock if the table
+/// itself has a bug, so the entries are still usable after a panic elsewhere.
+fn lock<S: Copy>(shard: &Mutex<PositionTable<S>>) -> MutexGuard<'_, PositionTable<S>> {
+    shard.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl DebugInfo {
+    /// Formats the counts for a table with `items` of its `capacity`
+    /// entries occupied. Without the `table_stats` feature, there are no
+    /// counts, so only the occupancy is given.
+    fn describe(&self, items: usize, capacity: usize) -> String {
+        if !cfg!(feature = "table_stats") {
+            return format!(
+                "Position table with {}/{} entries ({}% full)\n",
+                items,
+                capacity,
+                (100 * items) / capacity,
+            );
+        }
         format!(
             "Position table with {}/{} entries ({}% full):\n\
             \tTotal insert attempts: {}\n\
@@ -183,10 +431,11 @@ impl<S: Copy> PositionTable<S> {
             \tTotal get attempts: {}\n\
             \t\tHits: {} ({}%)\n\
             \t\tBlanks: {} ({}%)\n\
-            \t\tIncorrects: {} ({}%)\n",
-            self.items,
-            self.table.len(),
-            (100 * self.items) / self.table.len(),
+            \t\tIncorrects: {} ({}%)\n\
+            \tCollisions detected: {}\n",
+            items,
+            capacity,
+            (100 * items) / capacity,
             self.insert_attempts,
             self.insert_additions,
             (100 * self.insert_additions)
@@ -213,36 +462,55 @@ impl<S: Copy> PositionTable<S> {
             (100 * self.get_incorrects)
                 .checked_div(self.get_attempts)
                 .unwrap_or(0),
+            self.collisions_detected,
         )
     }
+}
 
-    pub fn reset_debug_info(&mut self) {
-        self.insert_attempts = 0;
-        self.insert_additions = 0;
-        self.insert_ignores = 0;
-        self.insert_overwrites = 0;
-        self.get_attempts = 0;
-        self.get_hits = 0;
-        self.get_blanks = 0;
-        self.get_incorrects = 0;
+impl std::ops::AddAssign for DebugInfo {
+    fn add_assign(&mut self, other: DebugInfo) {
+        self.insert_attempts += other.insert_attempts;
+        self.insert_additions += other.insert_additions;
+        self.insert_ignores += other.insert_ignores;
+        self.insert_overwrites += other.insert_overwrites;
+        self.get_attempts += other.get_attempts;
+        self.get_blanks += other.get_blanks;
+        self.get_hits += other.get_hits;
+        self.get_incorrects += other.get_incorrects;
+        self.collisions_detected += other.collisions_detected;
     }
 }
 
 impl Parameters {
-    fn saw_50_move_rule(&self) -> bool { 50 - self.dead_moves <= self.depth }
+    fn new(board: &MyBoard, depth: u8) -> Parameters {
+        Parameters {
+            depth,
+            dead_moves: board.get_dead_moves(),
+            dead_move_limit: board.get_dead_move_limit(),
+        }
+    }
+    /// Whether the search could have reached the dead move limit. This
+    /// saturates, since the limit can be lowered below the dead moves
+    /// already played.
+    fn saw_50_move_rule(&self) -> bool {
+        self.dead_move_limit.saturating_sub(self.dead_moves) <= self.depth
+    }
+    /// Whether the dead move clocks are the same, so that the dead move
+    /// limit is the same distance away
+    fn same_clock(&self, other: &Self) -> bool {
+        self.dead_moves == other.dead_moves && self.dead_move_limit == other.dead_move_limit
+    }
     /// Returns true if an evaluation for `self` should replace one for `other`
     /// in the table
     pub fn should_replace(&self, other: &Self) -> bool {
         self.depth >= other.depth
-            || (self.dead_moves != other.dead_moves
-                && (self.saw_50_move_rule() || other.saw_50_move_rule()))
+            || (!self.same_clock(other) && (self.saw_50_move_rule() || other.saw_50_move_rule()))
     }
     /// Returns true if an evaluation for `self` can be trusted instead of
     /// one for `other`.
     pub fn better_than(&self, other: &Self) -> bool {
         self.depth >= other.depth
-            && (self.dead_moves == other.dead_moves
-                || (!self.saw_50_move_rule() && !other.saw_50_move_rule()))
+            && (self.same_clock(other) || (!self.saw_50_move_rule() && !other.saw_50_move_rule()))
     }
 }
 
@@ -250,8 +518,323 @@ impl Position {
     pub fn from_board(board: &MyBoard) -> Position {
         Position {
             zobrist_hash: board.get_zobrist_hash(),
+            #[cfg(feature = "verify_positions")]
+            full: FullPosition::from_board(board),
+        }
+    }
+    pub fn switch_side_to_move(&mut self) {
+        self.zobrist_hash ^= crate::zobrist::Zobrist::color();
+        #[cfg(feature = "verify_positions")]
+        {
+            self.full.side_to_move = !self.full.side_to_move;
+        }
+    }
+    /// Whether the positions have the same hash but are different, which
+    /// can only be the case with the `verify_positions` feature
+    pub fn collides_with(&self, other: &Position) -> bool {
+        self.zobrist_hash == other.zobrist_hash && self != other
+    }
+    /// The index of the position in a table of `table_size` entries, which
+    /// must be a power of two
+    pub fn as_index(&self, table_size: usize) -> usize {
+        debug_assert!(table_size.is_power_of_two());
+        self.zobrist_hash as usize & (table_size - 1)
+    }
+}
+
+#[cfg(any(test, feature = "verify_positions"))]
+impl FullPosition {
+    pub fn from_board(board: &MyBoard) -> FullPosition {
+        FullPosition {
+            pieces: ALL_SQUARES.map(|sq| board[sq]),
+            castle_rights: [
+                board.get_castle_rights(Color::White),
+                board.get_castle_rights(Color::Black),
+            ],
+            side_to_move: board.get_side_to_move(),
+            en_passant: board.get_en_passant(),
+        }
+    }
+}
+
+/// A position table keyed by the full position, which never evicts entries
+/// or confuses positions. This is much slower and larger than
+/// `PositionTable`, but gives the ground truth to test it against.
+#[cfg(test)]
+struct ExactPositionTable<S: Copy> {
+    table: std::collections::HashMap<FullPosition, (Parameters, S)>,
+}
+
+#[cfg(test)]
+impl<S: Copy> ExactPositionTable<S> {
+    pub fn new() -> ExactPositionTable<S> {
+        ExactPositionTable {
+            table: std::collections::HashMap::new(),
+        }
+    }
+
+    /// Inserts with the same replacement rule as `PositionTable::insert`
+    pub fn insert(&mut self, board: &MyBoard, depth: u8, score: S) {
+        let params = Parameters::new(board, depth);
+        let position = FullPosition::from_board(board);
+        let replace = match self.table.get(&position) {
+            Some((existing, _)) => params.should_replace(existing),
+            None => true,
+        };
+        if replace {
+            self.table.insert(position, (params, score));
+        }
+    }
+
+    /// Looks up with the same rule as `PositionTable::get`
+    pub fn get(&self, board: &MyBoard, depth: u8) -> Option<S> {
+        let params = Parameters::new(board, depth);
+        match self.table.get(&FullPosition::from_board(board)) {
+            Some((existing, score)) if existing.better_than(&params) => Some(*score),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chess::Color;
+
+    use super::*;
+    use crate::{engine::proportion_count::ProportionCount, utils::SeededRng, Score};
+
+    /// An evaluator which (incorrectly) favours the side to move
+    #[cfg(debug_assertions)]
+    struct SideToMoveEval;
+
+    #[cfg(debug_assertions)]
+    impl StaticEvaluator for SideToMoveEval {
+        fn evaluate(&self, board: &MyBoard) -> Score {
+            match board.get_side_to_move() {
+                Color::White => Score::from_num(0.75),
+                Color::Black => Score::from_num(0.25),
+            }
+        }
+    }
+
+    #[test]
+    fn test_insert_both_colors() {
+        let mut table = PositionTable::new(&Logger::new(0));
+        let board = MyBoard::initial_board(Color::White);
+        let score = ProportionCount.evaluate(&board);
+        table.insert_both_colors(&board, 0, score, &ProportionCount);
+        assert_eq!(table.get(&board, 0), Some(score));
+        assert_eq!(table.get(&board.with_side_switched(), 0), Some(score));
+    }
+
+    #[test]
+    fn test_dead_move_limit() {
+        let board = MyBoard::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 10 1").unwrap();
+        let params = Parameters::new(&board, 1);
+        assert!(!params.saw_50_move_rule());
+        // The limit can be lowered below the dead moves already played
+        let lowered = Parameters::new(&board.with_dead_move_limit(5), 1);
+        assert!(lowered.saw_50_move_rule());
+
+        // Evaluations with different limits aren't interchangeable near them
+        assert!(!lowered.better_than(&params));
+        assert!(lowered.should_replace(&Parameters::new(&board, 3)));
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let logger = Logger::new(0);
+        let board = MyBoard::initial_board(Color::White);
+        let score = ProportionCount.evaluate(&board);
+
+        let mut table = PositionTable::with_capacity(usize::MAX, &logger);
+        assert!(table.table.len() <= TABLE_SIZE);
+        table.insert(&board, 0, score);
+        assert_eq!(table.get(&board, 0), Some(score));
+
+        // Capacities are rounded down to a power of two
+        assert_eq!(
+            PositionTable::<Score>::with_capacity(1000, &logger)
+                .table
+                .len(),
+            512
+        );
+
+        table.clear();
+        assert_eq!(table.items, 0);
+        assert_eq!(table.get(&board, 0), None);
+        assert!(table.table.iter().all(Option::is_none));
+
+        // Even a tiny table should store and retrieve entries
+        let mut tiny = PositionTable::with_capacity(1, &logger);
+        tiny.insert(&board, 0, score);
+        assert_eq!(tiny.get(&board, 0), Some(score));
+        assert_eq!(tiny.get(&board.with_side_switched(), 0), None);
+    }
+
+    #[test]
+    fn test_generations() {
+        let mut table = PositionTable::with_capacity(1, &Logger::new(0));
+        let board = MyBoard::initial_board(Color::White);
+        let other = board.with_side_switched();
+        let score = ProportionCount.evaluate(&board);
+
+        // Within a generation, a shallower entry doesn't replace a deeper one
+        table.insert(&board, 3, score);
+        table.insert(&other, 1, score);
+        assert_eq!(table.get(&board, 3), Some(score));
+        assert_eq!(table.get(&other, 1), None);
+
+        // But it replaces any entry from an earlier generation
+        table.new_generation();
+        table.insert(&other, 1, score);
+        assert_eq!(table.get(&other, 1), Some(score));
+        assert_eq!(table.get(&board, 0), None);
+
+        // The same position is still only replaced by a deeper search
+        table.insert(&other, 0, score);
+        assert_eq!(table.get(&other, 1), Some(score));
+
+        // Even when its entry is from an earlier generation
+        let deeper = Score::from_num(0.25);
+        table.insert(&other, 4, deeper);
+        table.new_generation();
+        table.insert(&other, 2, score);
+        assert_eq!(table.get(&other, 4), Some(deeper));
+        table.insert(&other, 4, score);
+        assert_eq!(table.get(&other, 4), Some(score));
+    }
+
+    #[test]
+    fn test_sharded_table() {
+        let logger = Logger::new(0);
+        let mut table = ShardedPositionTable::with_capacity(1 << 12, 4, &logger);
+        assert_eq!(table.shards(), 4);
+        assert_eq!(table.capacity(), 1 << 12);
+
+        // Every thread writes its own positions, and reads the others' back
+        // through its own handle to the table
+        let mut seen = std::collections::HashSet::new();
+        let mut boards = Vec::new();
+        let mut board = MyBoard::initial_board(Color::White);
+        while boards.len() < 40 {
+            if seen.insert(board.get_zobrist_hash()) {
+                boards.push(board);
+            }
+            let moves: Vec<_> = board.all_moves().collect();
+            board.apply_move(moves[boards.len() * 7 % moves.len()]);
+            board.apply_bonus(false);
+        }
+        std::thread::scope(|scope| {
+            for (t, chunk) in boards.chunks(10).enumerate() {
+                let mut table = table.clone();
+                scope.spawn(move || {
+                    for board in chunk {
+                        table.insert(board, t as u8, ProportionCount.evaluate(board));
+                    }
+                });
+            }
+        });
+        assert_eq!(table.items(), boards.len());
+        for (i, board) in boards.iter().enumerate() {
+            let depth = (i / 10) as u8;
+            assert_eq!(
+                table.get(board, depth),
+                Some(ProportionCount.evaluate(board))
+            );
+            assert_eq!(table.get(board, depth + 1), None);
+        }
+        #[cfg(feature = "table_stats")]
+        {
+            assert!(table.info().contains("Total get attempts: 80"));
+            table.reset_debug_info();
+            assert!(table.info().contains("Total get attempts: 0"));
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "depends on the side to move")]
+    fn test_insert_both_colors_misuse() {
+        let mut table = PositionTable::new(&Logger::new(0));
+        let board = MyBoard::initial_board(Color::White);
+        let score = SideToMoveEval.evaluate(&board);
+        table.insert_both_colors(&board, 0, score, &SideToMoveEval);
+    }
+
+    #[test]
+    #[cfg(feature = "verify_positions")]
+    fn test_verify_positions() {
+        let mut table = PositionTable::new(&Logger::new(0));
+        let board = MyBoard::initial_board(Color::White);
+        let score = ProportionCount.evaluate(&board);
+        table.insert(&board, 0, score);
+
+        // A different position with the same hash is rejected
+        let mut colliding = Position::from_board(&board);
+        colliding.full.pieces[0] = None;
+        let params = Parameters::new(&board, 0);
+        let index = colliding.as_index(table.table.len());
+        assert!(table.table[index]
+            .unwrap()
+            .position
+            .collides_with(&colliding));
+        table.insert_position(colliding, params, score);
+        assert_eq!(table.get(&board, 0), None);
+        #[cfg(feature = "table_stats")]
+        {
+            assert_eq!(table.debug.collisions_detected, 2);
+            assert!(table
+                .debug
+                .describe(table.items, table.table.len())
+                .contains("Collisions detected: 2"));
+        }
+    }
+
+    #[test]
+    fn test_matches_exact_table() {
+        // The positions are random but reproducible
+        let mut rng = SeededRng::new(765);
+
+        // Collect distinct positions from random games, so that each position
+        // is only inserted once
+        let mut seen = std::collections::HashSet::new();
+        let mut boards = Vec::new();
+        while boards.len() < 3000 {
+            let mut board = MyBoard::initial_board(Color::White);
+            while board.get_status().is_in_progress() && boards.len() < 3000 {
+                if seen.insert(board.get_zobrist_hash()) {
+                    boards.push(board);
+                }
+                let moves: Vec<_> = board.all_moves().collect();
+                board.apply_move(moves[rng.below(moves.len())]);
+                board.apply_bonus(rng.below(4) == 0);
+            }
+        }
+
+        let mut fast = PositionTable::with_capacity(1 << 12, &Logger::new(0));
+        let mut exact = ExactPositionTable::new();
+        for (i, board) in boards.iter().enumerate() {
+            let depth = rng.below(4) as u8;
+            fast.insert(board, depth, i);
+            exact.insert(board, depth, i);
+        }
+
+        // The fast table is small, so it loses entries to overwrites, but any
+        // entry it still has must give the same result as the exact table
+        let mut hits = 0;
+        for board in &boards {
+            for depth in 0..4 {
+                let expected = exact.get(board, depth);
+                match fast.get(board, depth) {
+                    Some(score) => {
+                        assert_eq!(Some(score), expected);
+                        hits += 1;
+                    }
+                    None => assert!(expected.is_none() || fast.get_lenient(board).is_none()),
+                }
+            }
         }
+        assert!(hits > 0);
     }
-    pub fn switch_side_to_move(&mut self) { self.zobrist_hash ^= crate::zobrist::Zobrist::color(); }
-    pub fn as_index(&self) -> usize { self.zobrist_hash as usize % TABLE_SIZE }
 }
@@ -1,4 +1,3 @@
-use chess::Color;
 use wasm_bindgen::prelude::wasm_bindgen;
 
 use super::StaticEvaluator;
@@ -16,24 +15,39 @@ impl StaticEvaluator for ProportionCount {
             return self.evaluate_terminal(board).unwrap();
         }
 
-        let mut white_value = 0;
-        let mut black_value = 0;
-
-        for sq in board.get_white_pieces() {
-            let Some((piece, Color::White)) = board[sq]
-                else { panic!("White piece not found on square {:?}", sq); };
-            let value = PIECE_VALUES[piece.to_index()];
-            white_value += value;
-        }
-
-        for sq in board.get_black_pieces() {
-            let Some((piece, Color::Black)) = board[sq]
-                else { panic!("Black piece not found on square {:?}", sq); };
-            let value = PIECE_VALUES[piece.to_index()];
-            black_value += value;
-        }
+        let (white_value, black_value) = material_values(board);
 
         let total_value = white_value + black_value;
         Score::from_num(white_value as f32 / total_value as f32)
     }
+
+    fn ignores_side_to_move(&self) -> bool { true }
+
+    fn clone_for_thread(&self) -> Option<Box<dyn StaticEvaluator>> {
+        Some(Box::new(ProportionCount))
+    }
+}
+
+/// Sums the values of each player's pieces, returning `(white, black)`
+pub(crate) fn material_values(board: &MyBoard) -> (u32, u32) { board.material_value(&PIECE_VALUES) }
+
+/// If one player has at least `ratio` times as much material as the other,
+/// returns the proportion of the material that belongs to white (i.e. what
+/// `ProportionCount` would return). Otherwise returns `None`.
+///
+/// This allows more expensive evaluators to skip their positional terms in
+/// positions where material is overwhelmingly decisive.
+pub(crate) fn decisive_material_score(board: &MyBoard, ratio: f32) -> Option<Score> {
+    let (white_value, black_value) = material_values(board);
+    let (stronger, weaker) = (
+        white_value.max(black_value) as f32,
+        white_value.min(black_value) as f32,
+    );
+    if stronger >= weaker * ratio {
+        Some(Score::from_num(
+            white_value as f32 / (white_value + black_value) as f32,
+        ))
+    } else {
+        None
+    }
 }
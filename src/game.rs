@@ -0,0 +1,446 @@
+use chess::{ChessMove, Color, ALL_PIECES, ALL_SQUARES};
+
+use crate::{
+    engine::proportion_count::material_values,
+    my_board::{MyBoard, Status},
+};
+
+/// The first inconsistency found when replaying a game
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameError {
+    /// The move at `index` wasn't legal in the position it was played in
+    IllegalMove { index: usize, mv: ChessMove },
+    /// The game had already finished before the move at `index`
+    MoveAfterEnd { index: usize },
+    /// All the moves were legal, but the game didn't finish
+    Unfinished,
+}
+
+/// A summary of a finished game, for aggregating statistics over many games
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GameResult {
+    pub status: Status,
+    /// The number of moves played by both players
+    pub num_plies: u32,
+    /// The number of moves which were followed by a bonus move. A bonus
+    /// rolled for the final move isn't counted, since the game is over.
+    pub num_bonuses: u32,
+    pub starting_color: Color,
+    /// The material each player had at the end (using the same piece values
+    /// as `ProportionCount`), indexed by color
+    pub final_material: [u32; 2],
+}
+
+impl GameResult {
+    /// Replays a recorded game (in the format `verify_game` takes) to
+    /// summarise it, or returns the first inconsistency in it
+    pub fn from_moves(
+        moves: &[(ChessMove, bool)], starting_color: Color,
+    ) -> Result<GameResult, GameError> {
+        let (board, num_bonuses) = replay_finished(moves, starting_color)?;
+        let (white, black) = material_values(&board);
+        Ok(GameResult {
+            status: board.get_status(),
+            num_plies: moves.len() as u32,
+            num_bonuses,
+            starting_color,
+            final_material: [white, black],
+        })
+    }
+}
+
+/// Replays a game from the initial board, where each move is given along with
+/// whether a bonus was rolled after it. The bonus for the final move of a
+/// game is ignored, since the game is already over.
+///
+/// Returns the final status if every move was legal and the game finished,
+/// or the first inconsistency otherwise.
+pub fn verify_game(
+    moves: &[(ChessMove, bool)], starting_color: Color,
+) -> Result<Status, GameError> {
+    replay_finished(moves, starting_color).map(|(board, _)| board.get_status())
+}
+
+/// Replays the moves of a (possibly unfinished) game, returning the boards
+/// before the first move and after each move and its bonus, along with the
+/// number of bonuses that took effect
+pub(crate) fn replay(
+    moves: &[(ChessMove, bool)], starting_color: Color,
+) -> Result<(Vec<MyBoard>, u32), GameError> {
+    let mut board = MyBoard::initial_board(starting_color);
+    let mut history = vec![board];
+    let mut num_bonuses = 0;
+
+    for (index, &(mv, is_bonus)) in moves.iter().enumerate() {
+        if !board.get_status().is_in_progress() {
+            return Err(GameError::MoveAfterEnd { index });
+        }
+        if !board.moves_from(mv.get_source()).contains(&mv) {
+            return Err(GameError::IllegalMove { index, mv });
+        }
+        board.apply_move_unchecked(mv);
+        if is_bonus && board.get_status().is_in_progress() {
+            num_bonuses += 1;
+        }
+        board.apply_bonus(is_bonus);
+        history.push(board);
+    }
+
+    Ok((history, num_bonuses))
+}
+
+/// Replays a game which should have finished, returning the final board and
+/// the number of bonuses that took effect
+fn replay_finished(
+    moves: &[(ChessMove, bool)], starting_color: Color,
+) -> Result<(MyBoard, u32), GameError> {
+    let (history, num_bonuses) = replay(moves, starting_color)?;
+    let board = *history.last().unwrap();
+    match board.get_status() {
+        Status::InProgress => Err(GameError::Unfinished),
+        _ => Ok((board, num_bonuses)),
+    }
+}
+
+/// Counts how many of the boards in `history` are the same position as
+/// `board`. Positions are compared with their zobrist hashes, so the side to
+/// move and castle rights have to match as well as the pieces.
+pub fn repetition_count(history: &[MyBoard], board: &MyBoard) -> u32 {
+    history
+        .iter()
+        .filter(|b| b.get_zobrist_hash() == board.get_zobrist_hash())
+        .count() as u32
+}
+
+/// Packs a move into 15 bits: the source square in the lowest 6 bits, then
+/// the destination square, then the promotion piece's index plus 1 (or 0 for
+/// no promotion). The top bit is always clear.
+pub fn encode_move(mv: ChessMove) -> u16 {
+    let promotion = mv.get_promotion().map_or(0, |p| p.to_index() as u16 + 1);
+    mv.get_source().to_index() as u16 | (mv.get_dest().to_index() as u16) << 6 | promotion << 12
+}
+
+/// The inverse of `encode_move`. The top bit is ignored. Returns `None` if
+/// the promotion bits are above 5, since they would then be a promotion to
+/// a king or to no piece at all.
+pub fn decode_move(encoded: u16) -> Option<ChessMove> {
+    let square = |bits: u16| ALL_SQUARES[(bits & 0x3f) as usize];
+    let promotion = match (encoded >> 12) & 0x7 {
+        0 => None,
+        p if p > 5 => return None,
+        p => Some(ALL_PIECES[p as usize - 1]),
+    };
+    Some(ChessMove::new(
+        square(encoded),
+        square(encoded >> 6),
+        promotion,
+    ))
+}
+
+/// Encodes a recorded game (in the format `verify_game` takes) as one `u16`
+/// per move, using the spare top bit of `encode_move` for the bonus
+pub fn encode_game(moves: &[(ChessMove, bool)]) -> Vec<u16> {
+    moves
+        .iter()
+        .map(|&(mv, is_bonus)| encode_move(mv) | (is_bonus as u16) << 15)
+        .collect()
+}
+
+/// The inverse of `encode_game`. Returns `None` if any move can't be decoded.
+pub fn decode_game(encoded: &[u16]) -> Option<Vec<(ChessMove, bool)>> {
+    encoded
+        .iter()
+        .map(|&e| Some((decode_move(e)?, e >> 15 == 1)))
+        .collect()
+}
+
+/// The number of times a position has to occur for the game to be drawn by
+/// repetition
+const REPETITION_LIMIT: u32 = 3;
+
+/// A game along with every position played so far, so that draws by
+/// repetition can be detected. `MyBoard` only knows about the current
+/// position, so it can't detect them itself.
+#[derive(Clone, Debug)]
+pub struct Game {
+    history: Vec<MyBoard>,
+    /// The maximum number of positions kept before the current one, or
+    /// `None` to keep them all
+    history_length: Option<usize>,
+}
+
+impl Game {
+    pub fn new(starting_color: Color) -> Game {
+        Game {
+            history: vec![MyBoard::initial_board(starting_color)],
+            history_length: None,
+        }
+    }
+
+    /// Continues a game from an explicit history of positions, where the
+    /// last board is the current position
+    pub fn with_history(history: Vec<MyBoard>) -> Game {
+        assert!(
+            !history.is_empty(),
+            "history should include the current board"
+        );
+        Game {
+            history,
+            history_length: None,
+        }
+    }
+
+    /// Loads a game part of the way through by replaying its moves (in the
+    /// format `verify_game` takes), so that the dead moves and repetitions
+    /// are counted correctly. The game doesn't need to have finished.
+    pub fn from_moves(
+        moves: &[(ChessMove, bool)], starting_color: Color,
+    ) -> Result<Game, GameError> {
+        let (history, _) = replay(moves, starting_color)?;
+        Ok(Game::with_history(history))
+    }
+
+    /// Only keeps the last `length` positions before the current one, to
+    /// save memory. Repetitions further back than that aren't detected, so a
+    /// length of 0 disables draws by repetition entirely.
+    pub fn with_history_length(mut self, length: usize) -> Game {
+        self.history_length = Some(length);
+        self.trim_history();
+        self
+    }
+
+    pub fn board(&self) -> &MyBoard { self.history.last().unwrap() }
+
+    pub fn history(&self) -> &[MyBoard] { &self.history }
+
+    /// The zobrist hash of each position in the history, oldest first. This
+    /// is enough to compare positions without keeping whole boards around.
+    pub fn hash_history(&self) -> impl Iterator<Item = u64> + '_ {
+        self.history.iter().map(|board| board.get_zobrist_hash())
+    }
+
+    /// Applies a move and the bonus roll after it
+    pub fn play(&mut self, mv: ChessMove, is_bonus: bool) {
+        let mut board = *self.board();
+        board.apply_move(mv);
+        board.apply_bonus(is_bonus);
+        self.history.push(board);
+        self.trim_history();
+    }
+
+    /// Removes the oldest positions beyond the history length
+    fn trim_history(&mut self) {
+        if let Some(length) = self.history_length {
+            let excess = self.history.len().saturating_sub(length + 1);
+            self.history.drain(..excess);
+        }
+    }
+
+    /// The status of the game, which is a draw if the current position has
+    /// occurred `REPETITION_LIMIT` times
+    pub fn status(&self) -> Status {
+        match self.board().get_status() {
+            Status::InProgress
+                if repetition_count(&self.history, self.board()) >= REPETITION_LIMIT =>
+            {
+                Status::Draw
+            }
+            status => status,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chess::{Piece, Square};
+
+    use super::*;
+    use crate::engine::{alphabeta::AlphaBeta, proportion_count::ProportionCount, Engine};
+
+    /// A short game where white captures black's king with the queen
+    fn scholars_game() -> Vec<(ChessMove, bool)> {
+        vec![
+            (ChessMove::new(Square::E2, Square::E4, None), true),
+            (ChessMove::new(Square::D1, Square::H5, None), false),
+            (ChessMove::new(Square::F7, Square::F6, None), false),
+            (ChessMove::new(Square::H5, Square::E8, None), false),
+        ]
+    }
+
+    #[test]
+    fn test_verify_game() {
+        let game = scholars_game();
+        assert_eq!(
+            verify_game(&game, Color::White),
+            Ok(Status::Win(Color::White))
+        );
+
+        let mut illegal = game.clone();
+        illegal[2].0 = ChessMove::new(Square::F7, Square::F4, None);
+        assert_eq!(
+            verify_game(&illegal, Color::White),
+            Err(GameError::IllegalMove {
+                index: 2,
+                mv: illegal[2].0
+            })
+        );
+
+        assert_eq!(
+            verify_game(&game[..3], Color::White),
+            Err(GameError::Unfinished)
+        );
+
+        let mut too_long = game.clone();
+        too_long.push((ChessMove::new(Square::E8, Square::E7, None), false));
+        assert_eq!(
+            verify_game(&too_long, Color::White),
+            Err(GameError::MoveAfterEnd { index: 4 })
+        );
+    }
+
+    #[test]
+    fn test_game_result() {
+        let mut game = scholars_game();
+        // A bonus for the final move doesn't count
+        game[3].1 = true;
+
+        assert_eq!(
+            GameResult::from_moves(&game, Color::White),
+            Ok(GameResult {
+                status: Status::Win(Color::White),
+                num_plies: 4,
+                num_bonuses: 1,
+                starting_color: Color::White,
+                final_material: [40, 39],
+            })
+        );
+        assert_eq!(
+            GameResult::from_moves(&game[..3], Color::White),
+            Err(GameError::Unfinished)
+        );
+    }
+
+    #[test]
+    fn test_game_repetition_draw() {
+        let shuffle = [
+            ChessMove::new(Square::G1, Square::F3, None),
+            ChessMove::new(Square::G8, Square::F6, None),
+            ChessMove::new(Square::F3, Square::G1, None),
+            ChessMove::new(Square::F6, Square::G8, None),
+        ];
+        // The initial position has occurred twice, and is one move from a
+        // third time
+        let moves: Vec<_> = shuffle
+            .iter()
+            .chain(&shuffle[..3])
+            .map(|&mv| (mv, false))
+            .collect();
+
+        let mut game = Game::from_moves(&moves, Color::White).unwrap();
+        assert_eq!(game.history().len(), 8);
+        assert_eq!(game.board().get_dead_moves(), 7);
+        assert_eq!(game.status(), Status::InProgress);
+
+        game.play(shuffle[3], false);
+        assert_eq!(game.status(), Status::Draw);
+
+        // The hashes show the same repetitions as the boards
+        let hashes: Vec<_> = game.hash_history().collect();
+        assert_eq!(hashes.len(), game.history().len());
+        assert_eq!(hashes[0], hashes[4]);
+        assert_eq!(hashes[0], hashes[8]);
+        assert_ne!(hashes[0], hashes[1]);
+
+        // Continuing from the same history gives the same result
+        let continued = Game::with_history(game.history().to_vec());
+        assert_eq!(continued.status(), Status::Draw);
+    }
+
+    #[test]
+    fn test_game_history_length() {
+        let shuffle = [
+            ChessMove::new(Square::G1, Square::F3, None),
+            ChessMove::new(Square::G8, Square::F6, None),
+            ChessMove::new(Square::F3, Square::G1, None),
+            ChessMove::new(Square::F6, Square::G8, None),
+        ];
+
+        // Without any history, shuffling never draws
+        let mut game = Game::new(Color::White).with_history_length(0);
+        for mv in shuffle.iter().cycle().take(12) {
+            game.play(*mv, false);
+            assert_eq!(game.history().len(), 1);
+            assert_eq!(game.status(), Status::InProgress);
+        }
+
+        // The engine can still play from the current board
+        let mut engine = AlphaBeta::new(ProportionCount, 2, false, false, 0, 100000);
+        for _ in 0..4 {
+            let mv = engine.get_move(game.board());
+            game.play(mv, false);
+        }
+        assert_eq!(game.history().len(), 1);
+
+        // Eight positions is enough to see the third occurrence of a
+        // position repeated every four moves
+        let mut game = Game::new(Color::White).with_history_length(8);
+        for mv in shuffle.iter().cycle().take(8) {
+            game.play(*mv, false);
+        }
+        assert_eq!(game.history().len(), 9);
+        assert_eq!(game.status(), Status::Draw);
+    }
+
+    #[test]
+    fn test_repetition_count() {
+        let shuffle = [
+            ChessMove::new(Square::G1, Square::F3, None),
+            ChessMove::new(Square::G8, Square::F6, None),
+            ChessMove::new(Square::F3, Square::G1, None),
+            ChessMove::new(Square::F6, Square::G8, None),
+        ];
+
+        let initial = MyBoard::initial_board(Color::White);
+        let mut board = initial;
+        let mut history = vec![board];
+
+        for _ in 0..2 {
+            for mv in shuffle {
+                board.apply_move(mv);
+                board.apply_bonus(false);
+                history.push(board);
+            }
+        }
+
+        assert_eq!(repetition_count(&history, &initial), 3);
+        assert_eq!(repetition_count(&history, &history[1]), 2);
+        assert_eq!(repetition_count(&history, &initial.with_side_switched()), 0);
+    }
+
+    #[test]
+    fn test_move_encoding() {
+        let promotions = [
+            None,
+            Some(Piece::Knight),
+            Some(Piece::Bishop),
+            Some(Piece::Rook),
+            Some(Piece::Queen),
+        ];
+        for source in ALL_SQUARES {
+            for dest in ALL_SQUARES {
+                for promotion in promotions {
+                    let mv = ChessMove::new(source, dest, promotion);
+                    let encoded = encode_move(mv);
+                    assert!(encoded < 1 << 15);
+                    assert_eq!(decode_move(encoded), Some(mv));
+                }
+            }
+        }
+        assert_eq!(decode_move(6 << 12), None);
+        assert_eq!(decode_move(7 << 12), None);
+
+        let game = scholars_game();
+        assert_eq!(decode_game(&encode_game(&game)), Some(game));
+        assert_eq!(decode_game(&[0x0c4a, 0x7000]), None);
+    }
+}
@@ -1,58 +1,89 @@
-use chess::{ChessMove, Color::*, Square, ALL_COLORS, ALL_PIECES};
+use chess::{ChessMove, Color, Color::*, Square, ALL_COLORS, ALL_PIECES};
 use js_sys::{Array, JsString};
 use wasm_bindgen::prelude::*;
 
 use crate::{
-    engine::Engine,
+    engine::{weighted_score, Engine},
+    game::repetition_count,
     my_board::{MyBoard, Status},
+    Score, ONE,
 };
 
+/// How many dead moves before a draw the UI should start warning players
+const DRAW_WARNING_MOVES: u8 = 10;
+
+/// The maximum lookahead and the time limit (in milliseconds) for each
+/// difficulty level, from weakest to strongest
+const DIFFICULTY_PRESETS: [(u8, u64); 5] = [(2, 100), (3, 200), (4, 400), (6, 700), (10, 1000)];
+
+/// The strongest difficulty level, which is what both sides start with
+const MAX_LEVEL: u8 = DIFFICULTY_PRESETS.len() as u8;
+
+/// The difficulty level used for the evaluation graph, which is kept weak
+/// so that evaluating a whole game is quick
+const ANALYSIS_LEVEL: u8 = 1;
+
+/// The number of entries in each engine's position table. A full-sized table
+/// would take up a large share of the memory available to WASM.
+const ENGINE_TABLE_SIZE: usize = 1 << 18;
+
 // TODO: Persist the current game (and possibly other state) between page loads
 
 #[wasm_bindgen]
 pub struct JSInterface {
     board: MyBoard,
+    /// The board the game started from, which isn't in `board_history`
+    initial_board: MyBoard,
     engine_black: Box<dyn Engine>,
     engine_white: Box<dyn Engine>,
     board_history: Vec<MyBoard>,
     move_history: Vec<ChessMove>,
+    /// The difficulty level of each side's engine, indexed by color
+    levels: [u8; 2],
+    /// The key (from `eval_cache_key`) and evaluation of each board in
+    /// `board_history` that has been evaluated
+    eval_cache: Vec<((u64, bool), Score)>,
 }
 
 #[wasm_bindgen]
 impl JSInterface {
     pub fn js_initial_interface(white_starts: bool) -> Self {
         crate::utils::set_panic_hook();
-        let weights = crate::engine::feature_eval::Weights {
-            pieces: [[1.0, 3.0, 3.0, 5.0, 9.0, 0.0], [
-                -1.0, -3.0, -3.0, -5.0, -9.0, 0.0,
-            ]],
-            king_danger: [-0.5, 0.5],
-            pawn_advancement: [0.5, -0.5],
-            side_to_move: 3.0,
-        };
+        let initial_board = MyBoard::initial_board(if white_starts { White } else { Black });
         JSInterface {
-            board: MyBoard::initial_board(if white_starts { White } else { Black }),
-            engine_black: Box::new(crate::engine::alphabeta::AlphaBeta::new(
-                crate::engine::feature_eval::FeatureEval::new(weights, 15.0),
-                10,
-                true,
-                false,
-                3,
-                1000,
-            )),
-            engine_white: Box::new(crate::engine::alphabeta::AlphaBeta::new(
-                crate::engine::feature_eval::FeatureEval::new(weights, 15.0),
-                10,
-                true,
-                false,
-                3,
-                1000,
-            )),
+            board: initial_board,
+            initial_board,
+            engine_black: engine_for_level(MAX_LEVEL),
+            engine_white: engine_for_level(MAX_LEVEL),
             board_history: Vec::new(),
             move_history: Vec::new(),
+            levels: [MAX_LEVEL; 2],
+            eval_cache: Vec::new(),
         }
     }
 
+    /// Sets the engine for `side` ("white" or "black") to the given
+    /// difficulty level, from 1 (weakest) to `MAX_LEVEL` (strongest).
+    /// Levels outside that range are clamped to it. Any other side is an
+    /// error, which is thrown in JS.
+    pub fn js_configure_side(&mut self, side: &str, level: u8) -> Result<(), JsValue> {
+        let color = parse_side(side).ok_or_else(|| {
+            JsValue::from_str(&format!(
+                "side should be \"white\" or \"black\", not {:?}",
+                side
+            ))
+        })?;
+        let engine = match color {
+            White => self.engine_white.as_mut(),
+            Black => self.engine_black.as_mut(),
+        };
+        // The engine is kept rather than replaced, so that its position
+        // table isn't allocated again
+        set_level(engine, level);
+        self.levels[color.to_index()] = level;
+        Ok(())
+    }
+
     pub fn js_piece(&self, file: usize, rank: usize) -> Option<JsString> {
         let square = make_square(file, rank);
         match self.board[square] {
@@ -91,6 +122,22 @@ impl JSInterface {
         history
     }
 
+    /// The evaluation of each board in the history at `ANALYSIS_LEVEL`, from
+    /// 0 (black is winning) to 1 (white is winning). Boards which were
+    /// already evaluated aren't searched again.
+    pub fn js_eval_history(&mut self) -> Array {
+        // White's engine is borrowed for the analysis rather than keeping a
+        // separate engine, which would need a position table of its own
+        let engine = self.engine_white.as_mut();
+        set_level(engine, ANALYSIS_LEVEL);
+        let scores = cached_evaluations(engine, &self.board_history, &mut self.eval_cache);
+        set_level(engine, self.levels[White.to_index()]);
+        scores
+            .into_iter()
+            .map(|score| JsValue::from(score.to_num::<f64>()))
+            .collect()
+    }
+
     pub fn js_piece_color(&self, file: usize, rank: usize) -> JsString {
         let square = make_square(file, rank);
         match self.board[square] {
@@ -161,7 +208,28 @@ impl JSInterface {
         self.move_history.push(m);
     }
 
-    pub fn js_apply_bonus(&mut self, is_bonus: bool) { self.board.apply_bonus(is_bonus); }
+    pub fn js_apply_bonus(&mut self, is_bonus: bool) {
+        self.board.apply_bonus(is_bonus);
+        // Store the board after the bonus, so that the side to move is correct
+        // for repetition detection
+        if let Some(last) = self.board_history.last_mut() {
+            *last = self.board;
+        }
+    }
+
+    /// The number of times the current position occurred earlier in the
+    /// game, including the starting position
+    pub fn js_repetition_count(&self) -> u32 {
+        // The last board in the history is the current one
+        let Some((_, earlier)) = self.board_history.split_last() else {
+            return 0;
+        };
+        repetition_count(&[self.initial_board], &self.board)
+            + repetition_count(earlier, &self.board)
+    }
+
+    /// Whether the game is close to being drawn by the dead moves rule
+    pub fn js_is_draw_imminent(&self) -> bool { self.board.is_near_draw(DRAW_WARNING_MOVES) }
 
     pub fn js_get_side_to_move(&self) -> JsString {
         if self.board.get_side_to_move().to_index() == 0 {
@@ -171,6 +239,9 @@ impl JSInterface {
         }
     }
 
+    /// Whether the UI should ask for the bonus roll rather than a move
+    pub fn js_is_awaiting_bonus(&self) -> bool { self.board.is_awaiting_bonus() }
+
     pub fn js_status(&self) -> JsString { self.board.get_status().into() }
 
     pub fn js_get_engine_move(&mut self) -> Array {
@@ -192,6 +263,83 @@ impl From<Status> for JsString {
     }
 }
 
+/// Returns the `(max_lookahead, max_time)` for a difficulty level
+fn difficulty_preset(level: u8) -> (u8, u64) {
+    DIFFICULTY_PRESETS[level.clamp(1, MAX_LEVEL) as usize - 1]
+}
+
+fn engine_for_level(level: u8) -> Box<dyn Engine> {
+    let weights = crate::engine::feature_eval::Weights::standard();
+    let (max_lookahead, max_time) = difficulty_preset(level);
+    Box::new(crate::engine::alphabeta::AlphaBeta::with_capacity(
+        crate::engine::feature_eval::FeatureEval::new(weights, 15.0),
+        max_lookahead,
+        true,
+        false,
+        3,
+        max_time,
+        ENGINE_TABLE_SIZE,
+    ))
+}
+
+/// Changes the limits of `engine` to those of a difficulty level
+fn set_level(engine: &mut dyn Engine, level: u8) {
+    let (max_lookahead, max_time) = difficulty_preset(level);
+    engine.set_max_lookahead(max_lookahead);
+    engine.set_max_time(max_time);
+}
+
+/// Evaluates each of `boards`, reusing the scores in `cache` for the boards
+/// at the start which haven't changed since they were cached, and updates
+/// `cache` to match `boards`
+fn cached_evaluations(
+    engine: &mut dyn Engine, boards: &[MyBoard], cache: &mut Vec<((u64, bool), Score)>,
+) -> Vec<Score> {
+    let unchanged = boards
+        .iter()
+        .zip(cache.iter())
+        .take_while(|(board, (key, _))| eval_cache_key(board) == *key)
+        .count();
+    cache.truncate(unchanged);
+    for board in &boards[unchanged..] {
+        cache.push((eval_cache_key(board), evaluate_position(engine, board)));
+    }
+    cache.iter().map(|&(_, score)| score).collect()
+}
+
+/// Identifies a board in the evaluation cache. The zobrist hash doesn't
+/// include whether the board is awaiting a bonus, so that is added to it.
+fn eval_cache_key(board: &MyBoard) -> (u64, bool) {
+    (board.get_zobrist_hash(), board.is_awaiting_bonus())
+}
+
+/// Evaluates a board, which may be waiting for the bonus roll after the last
+/// move. In that case, the two possible rolls are weighted by the chances
+/// that the engine assumes.
+fn evaluate_position(engine: &mut dyn Engine, board: &MyBoard) -> Score {
+    if !board.is_awaiting_bonus() || !board.get_status().is_in_progress() {
+        return engine.evaluate(board);
+    }
+    let (mut b_board, mut nb_board) = (*board, *board);
+    b_board.apply_bonus(true);
+    nb_board.apply_bonus(false);
+    let bonus_chance = engine.bonus_chance();
+    weighted_score(
+        engine.evaluate(&b_board),
+        bonus_chance,
+        engine.evaluate(&nb_board),
+        ONE - bonus_chance,
+    )
+}
+
+fn parse_side(side: &str) -> Option<Color> {
+    match side {
+        "white" => Some(White),
+        "black" => Some(Black),
+        _ => None,
+    }
+}
+
 fn square_to_array(s: Square) -> Array {
     let js_square = Array::new();
     js_square.push(&s.get_file().to_index().into());
@@ -212,3 +360,112 @@ fn move_to_array(m: ChessMove) -> Array {
 fn make_square(file: usize, rank: usize) -> Square {
     Square::make_square(chess::Rank::from_index(rank), chess::File::from_index(file))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{alphabeta::AlphaBeta, proportion_count::ProportionCount};
+
+    #[test]
+    fn test_configure_side_helpers() {
+        assert_eq!(parse_side("white"), Some(White));
+        assert_eq!(parse_side("black"), Some(Black));
+        assert_eq!(parse_side("red"), None);
+
+        // The strongest level matches the engines used before levels existed
+        assert_eq!(difficulty_preset(MAX_LEVEL), (10, 1000));
+        assert_eq!(difficulty_preset(1), (2, 100));
+        assert_eq!(difficulty_preset(0), difficulty_preset(1));
+        assert_eq!(difficulty_preset(u8::MAX), difficulty_preset(MAX_LEVEL));
+        for level in 1..MAX_LEVEL {
+            let (lookahead, time) = difficulty_preset(level);
+            let (next_lookahead, next_time) = difficulty_preset(level + 1);
+            assert!(lookahead < next_lookahead && time < next_time);
+        }
+    }
+
+    #[test]
+    fn test_repetition_count() {
+        let mut interface = JSInterface::js_initial_interface(true);
+        assert_eq!(interface.js_repetition_count(), 0);
+
+        // Both knights go out and back, returning to the starting position
+        let shuffle = [(6, 0, 5, 2), (6, 7, 5, 5), (5, 2, 6, 0), (5, 5, 6, 7)];
+        for round in 1..=2 {
+            for (from_file, from_rank, to_file, to_rank) in shuffle {
+                interface.js_apply_move(from_file, from_rank, to_file, to_rank, None);
+                interface.js_apply_bonus(false);
+            }
+            assert_eq!(interface.js_repetition_count(), round);
+        }
+
+        // After a knight moves out again, that position occurred once before
+        interface.js_apply_move(6, 0, 5, 2, None);
+        interface.js_apply_bonus(false);
+        assert_eq!(interface.js_repetition_count(), 2);
+    }
+
+    #[test]
+    fn test_cached_evaluations() {
+        let mut engine = AlphaBeta::new(ProportionCount, 2, false, false, 0, 100000);
+        let mut board = MyBoard::initial_board(White);
+        let mut boards = Vec::new();
+        for is_bonus in [false, true, false, false] {
+            board.apply_move(engine.get_move(&board));
+            board.apply_bonus(is_bonus);
+            boards.push(board);
+        }
+
+        let mut cache = Vec::new();
+        let scores = cached_evaluations(&mut engine, &boards, &mut cache);
+        assert_eq!(scores.len(), boards.len());
+        assert!(scores.iter().all(|&score| score <= ONE));
+        // Requesting again returns the cached scores rather than searching
+        // again, which could give different results with the table filled
+        assert_eq!(cached_evaluations(&mut engine, &boards, &mut cache), scores);
+
+        // A board awaiting its bonus is evaluated from the possible rolls,
+        // and replacing it afterwards only changes the last score
+        let mut awaiting = board;
+        awaiting.apply_move(engine.get_move(&board));
+        boards.push(awaiting);
+        let with_awaiting = cached_evaluations(&mut engine, &boards, &mut cache);
+        assert_eq!(with_awaiting.len(), boards.len());
+        assert_eq!(with_awaiting[..scores.len()], scores[..]);
+
+        boards.last_mut().unwrap().apply_bonus(false);
+        let rolled = cached_evaluations(&mut engine, &boards, &mut cache);
+        assert_eq!(rolled.len(), boards.len());
+        assert_eq!(rolled[..scores.len()], scores[..]);
+        assert_eq!(
+            cache.last().unwrap().0,
+            (boards.last().unwrap().get_zobrist_hash(), false)
+        );
+    }
+
+    #[test]
+    fn test_evaluate_position_bonus_chance() {
+        // White's bonus would let the queen capture the king
+        let mut board = MyBoard::from_fen("7k/8/8/8/8/8/8/R3K2Q w - - 0 1").unwrap();
+        board.apply_move(ChessMove::new(Square::A1, Square::A2, None));
+        assert!(board.is_awaiting_bonus());
+
+        // The rolls are weighted by the engine's own bonus chance
+        let rolls = |chance| {
+            let mut engine = AlphaBeta::new(ProportionCount, 2, false, false, 0, 100000)
+                .with_bonus_chance(chance);
+            let (mut b_board, mut nb_board) = (board, board);
+            b_board.apply_bonus(true);
+            nb_board.apply_bonus(false);
+            let expected = weighted_score(
+                engine.evaluate(&b_board),
+                chance,
+                engine.evaluate(&nb_board),
+                ONE - chance,
+            );
+            assert_eq!(evaluate_position(&mut engine, &board), expected);
+            expected
+        };
+        assert!(rolls(ONE / 2) > rolls(ONE / 8));
+    }
+}
@@ -1,10 +1,14 @@
 use fixed::{types::extra::U31, FixedU32};
 
+mod benchmark;
+mod dataset;
 mod deadline;
 mod engine;
+mod game;
 mod js_interface;
 mod logger;
 mod my_board;
+mod uci;
 mod utils;
 mod zobrist;
 
@@ -13,14 +17,31 @@ pub(crate) const ONE: Score = Score::ONE;
 pub(crate) const ZERO: Score = Score::ZERO;
 pub(crate) const DELTA: Score = Score::DELTA;
 
+pub use benchmark::{benchmark_games, Benchmark};
+pub use dataset::{label_games, parse_games, DatasetError, LabelledPosition, RecordedGame};
+#[cfg(any(test, feature = "test-util"))]
+pub use engine::assert_evaluation_symmetric;
 pub use engine::{
-    alphabeta::AlphaBeta,
-    feature_eval::{FeatureEval, Features, Weights},
+    alphabeta::{compare_focussed, AlphaBeta, DepthInfo, FocusComparison, SearchStats},
+    compare_evaluators,
+    draw_table::{DrawTable, MaterialSignature},
+    feature_eval::{
+        feature_importance, game_phase, FeatureEval, Features, TaperedWeights, Weights, MAX_PHASE,
+    },
+    piece_square_eval::{PieceSquareEval, PieceSquareTables, DEFAULT_TABLES},
     proportion_count::ProportionCount,
-    Engine, StaticEvaluator,
+    Engine, EvaluatorComparison, StaticEvaluator,
+};
+pub use game::{
+    decode_game, decode_move, encode_game, encode_move, repetition_count, verify_game, Game,
+    GameError, GameResult,
 };
 pub use logger::Logger;
-pub use my_board::{MyBoard, Status};
+pub use my_board::{
+    BoardDiff, Event, FenError, MoveError, MoveKind, MyBoard, PerftStats, PositionError, Status,
+    UndoBoard, Variant,
+};
+pub use uci::{score_to_pawns, score_to_uci};
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
 // allocator.
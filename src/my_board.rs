@@ -8,20 +8,47 @@ use chess::{
 
 use crate::zobrist::Zobrist;
 
+#[cfg(test)] mod tests;
+
+mod undo_board;
+pub use undo_board::UndoBoard;
+
+/// The number of dead moves (non-pawn non-capture moves) in a row that end
+/// the game in a draw
+const DEAD_MOVE_LIMIT: u8 = 50;
+
 #[derive(Copy, Clone, Debug)]
 pub struct MyBoard {
     pieces: [Option<(Piece, Color)>; 64],
     side_to_move: Color,
     castle_rights: [CastleRights; 2],
+    /// The square a pawn passed over with a double push on the last move, if
+    /// an enemy pawn could capture it en passant
+    en_passant: Option<Square>,
     dead_moves: u8,
+    /// The number of dead moves after which the game is drawn
+    dead_move_limit: u8,
     status: Status,
     awaiting_bonus: bool, // TODO: refactor into side_to_move
+    variant: Variant,
+    /// Whether the king may castle out of, through or into an attacked
+    /// square. This is allowed in the king capture variant, since the king
+    /// can just be captured on the next move.
+    castle_through_attack: bool,
     white_pieces: BitBoard,
     black_pieces: BitBoard,
     zobrist_hash: u64,
+    /// The number of each kind of piece each player has, indexed by color
+    /// and then `Piece::to_index`, kept up to date by `set_piece`
+    material: [[u8; 6]; 2],
+    /// The sum of how many ranks each player's pawns have advanced from
+    /// their starting rank, kept up to date by `set_piece`
+    pawn_advancement: [u16; 2],
+    /// The squares of each player's pawns, kept up to date by `set_piece`
+    pawns: [BitBoard; 2],
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Status {
     InProgress,
     Win(Color),
@@ -32,6 +59,146 @@ impl Status {
     pub fn is_in_progress(&self) -> bool { matches!(self, Status::InProgress) }
 }
 
+/// Why a FEN string couldn't be loaded by `MyBoard::from_fen`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FenError {
+    /// The FEN couldn't be parsed
+    Malformed,
+    /// The given player has no king, so the game would already be over
+    MissingKing(Color),
+    /// The halfmove clock isn't a number, or has already reached the dead
+    /// move limit
+    InvalidHalfmoveClock,
+    /// The given player has a castle right without their king and the
+    /// matching rook on their home squares
+    InvalidCastleRights(Color),
+}
+
+/// Why a position couldn't be built by `MyBoard::from_pieces`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PositionError {
+    /// The given player has no king
+    MissingKing(Color),
+    /// The given player has more than one king
+    ExtraKing(Color),
+    /// The given player has a castle right without their king and the
+    /// matching rook on their home squares
+    InvalidCastleRights(Color),
+}
+
+/// Why a move couldn't be applied by `MyBoard::try_apply_move`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MoveError {
+    /// The game has already finished
+    GameOver,
+    /// The bonus for the previous move hasn't been applied yet
+    AwaitingBonus,
+    /// The source square doesn't have a piece belonging to the side to move
+    NotYourPiece,
+    /// The piece can't make that move
+    Illegal,
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::GameOver => write!(f, "the game is over"),
+            MoveError::AwaitingBonus => write!(f, "the bonus hasn't been applied yet"),
+            MoveError::NotYourPiece => write!(f, "there is no piece of yours there"),
+            MoveError::Illegal => write!(f, "that piece can't move there"),
+        }
+    }
+}
+
+/// The input a board is waiting for. A turn is a move followed by the
+/// resolution of the bonus roll, after which the same side moves again if the
+/// bonus was rolled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The side to move should make a move with `apply_move`
+    AwaitingMove,
+    /// The bonus roll for the last move should be given with `apply_bonus`
+    AwaitingBonus,
+    /// The game is over, so no more input is expected
+    GameOver,
+}
+
+/// The rules used to decide which moves are legal
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Variant {
+    /// Moves that leave the king attacked are allowed, and the game is won
+    /// by capturing the king
+    #[default]
+    KingCapture,
+    /// Moves that leave the king attacked (or castle out of or through an
+    /// attacked square) are illegal, and a player with no moves loses if
+    /// they are in check (and draws otherwise), as in standard chess
+    Standard,
+}
+
+/// What a move does, as classified by `MyBoard::move_kind`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MoveKind {
+    /// A move that doesn't capture, castle or promote
+    Quiet,
+    /// A capture of the given piece, including en passant
+    Capture(Piece),
+    Castle,
+    /// A promotion, which may also capture a piece
+    Promotion {
+        captured: Option<Piece>,
+    },
+}
+
+impl MoveKind {
+    pub fn captured(&self) -> Option<Piece> {
+        match *self {
+            MoveKind::Capture(p) => Some(p),
+            MoveKind::Promotion { captured } => captured,
+            _ => None,
+        }
+    }
+}
+
+/// Counts of the moves made in the tree searched by `MyBoard::perft_stats`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PerftStats {
+    /// The number of leaf positions (or finished games) reached
+    pub nodes: u64,
+    pub captures: u64,
+    pub castles: u64,
+    pub promotions: u64,
+    pub king_captures: u64,
+    /// The number of moves followed by a bonus, i.e. half of the moves which
+    /// didn't end the game
+    pub bonus_branches: u64,
+}
+
+/// A square along with its old and new contents
+pub type SquareChange = (Square, Option<(Piece, Color)>, Option<(Piece, Color)>);
+
+/// The differences between two boards, as returned by `MyBoard::diff`. Each
+/// change is stored as a pair of the old value and the new value.
+#[derive(Clone, Debug, Default)]
+pub struct BoardDiff {
+    pub squares: Vec<SquareChange>,
+    pub side_to_move: Option<(Color, Color)>,
+    /// Indexed by color
+    pub castle_rights: [Option<(CastleRights, CastleRights)>; 2],
+    pub en_passant: Option<(Option<Square>, Option<Square>)>,
+    pub status: Option<(Status, Status)>,
+}
+
+impl BoardDiff {
+    pub fn is_empty(&self) -> bool {
+        self.squares.is_empty()
+            && self.side_to_move.is_none()
+            && self.castle_rights.iter().all(|r| r.is_none())
+            && self.en_passant.is_none()
+            && self.status.is_none()
+    }
+}
+
 impl MyBoard {
     pub fn get_side_to_move(&self) -> Color { self.side_to_move }
     pub fn get_castle_rights(&self, color: Color) -> CastleRights {
@@ -40,12 +207,70 @@ impl MyBoard {
             Color::Black => self.castle_rights[1],
         }
     }
+    pub fn get_en_passant(&self) -> Option<Square> { self.en_passant }
     pub fn get_dead_moves(&self) -> u8 { self.dead_moves }
+    pub fn get_dead_move_limit(&self) -> u8 { self.dead_move_limit }
+    /// Whether a move has been made and the bonus roll for it is needed
+    /// before any moves can be requested
+    pub fn is_awaiting_bonus(&self) -> bool { self.awaiting_bonus }
+
+    /// The number of dead moves that can still be played before the game is
+    /// drawn by the fifty move rule
+    pub fn moves_until_fifty_move_draw(&self) -> u8 {
+        self.dead_move_limit.saturating_sub(self.dead_moves)
+    }
+
+    /// Whether the game is within `margin` dead moves of being drawn by the
+    /// fifty move rule
+    pub fn is_near_draw(&self, margin: u8) -> bool { self.moves_until_fifty_move_draw() <= margin }
     pub fn get_status(&self) -> Status { self.status }
+    pub fn get_variant(&self) -> Variant { self.variant }
+    pub fn get_castle_through_attack(&self) -> bool { self.castle_through_attack }
     pub fn get_white_pieces(&self) -> BitBoard { self.white_pieces }
     pub fn get_black_pieces(&self) -> BitBoard { self.black_pieces }
     pub fn get_zobrist_hash(&self) -> u64 { self.zobrist_hash }
 
+    /// Every piece on the board along with its square
+    pub fn pieces(&self) -> impl Iterator<Item = (Square, Piece, Color)> + '_ {
+        self.combined()
+            .filter_map(move |sq| self.pieces[sq.to_index()].map(|(p, c)| (sq, p, c)))
+    }
+
+    /// The number of each kind of piece that `color` has, indexed by
+    /// `Piece::to_index`
+    pub fn material(&self, color: Color) -> [u8; 6] { self.material[color.to_index()] }
+
+    /// The sum of how many ranks `color`'s pawns have advanced from their
+    /// starting rank (so 0 for the initial board)
+    pub fn pawn_advancement(&self, color: Color) -> u16 { self.pawn_advancement[color.to_index()] }
+
+    /// The squares of `color`'s pawns
+    pub fn get_pawns(&self, color: Color) -> BitBoard { self.pawns[color.to_index()] }
+
+    /// Sums the values of each player's pieces, with `values` indexed by
+    /// `Piece::to_index`, returning `(white, black)`
+    pub fn material_value(&self, values: &[u8; 6]) -> (u32, u32) {
+        let value = |color| {
+            self.material(color)
+                .iter()
+                .zip(values)
+                .map(|(&count, &value)| count as u32 * value as u32)
+                .sum()
+        };
+        (value(Color::White), value(Color::Black))
+    }
+
+    /// The input the board is waiting for next
+    pub fn pending_event(&self) -> Event {
+        if !self.status.is_in_progress() {
+            Event::GameOver
+        } else if self.awaiting_bonus {
+            Event::AwaitingBonus
+        } else {
+            Event::AwaitingMove
+        }
+    }
+
     /// Sets the castle rights, updating the zobrist hash
     fn set_castle_rights(&mut self, color: Color, rights: CastleRights) {
         self.zobrist_hash ^= Zobrist::castles(self.get_castle_rights(color), color);
@@ -56,12 +281,69 @@ impl MyBoard {
         }
     }
 
+    /// Sets the en passant square, updating the zobrist hash
+    fn set_en_passant(&mut self, square: Option<Square>) {
+        if let Some(old) = self.en_passant {
+            self.zobrist_hash ^= Zobrist::en_passant(old.get_file());
+        }
+        if let Some(new) = square {
+            self.zobrist_hash ^= Zobrist::en_passant(new.get_file());
+        }
+        self.en_passant = square;
+    }
+
+    /// The en passant square after a pawn of `color` double pushes to `dest`.
+    /// This is only set if an enemy pawn is beside `dest` to capture it, so
+    /// that positions which only differ by an unusable en passant square
+    /// still have the same hash.
+    fn en_passant_after_push(&self, dest: Square, color: Color) -> Option<Square> {
+        [dest.left(), dest.right()]
+            .iter()
+            .flatten()
+            .any(|&sq| self[sq] == Some((Piece::Pawn, !color)))
+            .then(|| dest.ubackward(color))
+    }
+
+    /// If `m` captures en passant, returns the square of the pawn it captures
+    fn en_passant_victim(&self, m: ChessMove) -> Option<Square> {
+        let ep = self.en_passant?;
+        if m.get_dest() != ep || !matches!(self[m.get_source()], Some((Piece::Pawn, _))) {
+            return None;
+        }
+        Some(Square::make_square(
+            m.get_source().get_rank(),
+            ep.get_file(),
+        ))
+    }
+
+    /// Whether `color` could hold `rights`, which needs their king on its
+    /// home square and a rook on the home square of each side they can
+    /// castle to
+    fn castle_rights_allowed(&self, color: Color, rights: CastleRights) -> bool {
+        let rank = color.to_my_backrank();
+        let has = |file, piece| self[Square::make_square(rank, file)] == Some((piece, color));
+        rights == CastleRights::NoRights
+            || (has(File::E, Piece::King)
+                && (!rights.has_kingside() || has(File::H, Piece::Rook))
+                && (!rights.has_queenside() || has(File::A, Piece::Rook)))
+    }
+
+    /// Returns the board with the castle rights for `color` replaced by
+    /// `rights`, keeping the zobrist hash consistent. This is useful for
+    /// setting up particular positions.
+    pub fn with_castle_rights(mut self, color: Color, rights: CastleRights) -> MyBoard {
+        self.set_castle_rights(color, rights);
+        self
+    }
+
     pub fn initial_board(starting_color: Color) -> MyBoard {
         let board = BoardBuilder::default();
         let mut pieces = [None; 64];
         let mut white_pieces = EMPTY;
         let mut black_pieces = EMPTY;
         let mut zobrist_hash = 0;
+        let mut material = [[0; 6]; 2];
+        let mut pawns = [EMPTY; 2];
         for sq in ALL_SQUARES {
             if let Some((piece, color)) = board[sq] {
                 pieces[sq.to_index()] = Some((piece, color));
@@ -70,6 +352,10 @@ impl MyBoard {
                     Color::Black => black_pieces |= BitBoard::from_square(sq),
                 }
                 zobrist_hash ^= Zobrist::piece(piece, sq, color);
+                material[color.to_index()][piece.to_index()] += 1;
+                if piece == Piece::Pawn {
+                    pawns[color.to_index()] |= BitBoard::from_square(sq);
+                }
             }
         }
         zobrist_hash ^= Zobrist::castles(CastleRights::Both, Color::White);
@@ -81,16 +367,169 @@ impl MyBoard {
             pieces,
             side_to_move: starting_color,
             castle_rights: [CastleRights::Both, CastleRights::Both],
+            en_passant: None,
             dead_moves: 0,
+            dead_move_limit: DEAD_MOVE_LIMIT,
             status: Status::InProgress,
             awaiting_bonus: false,
+            variant: Variant::default(),
+            castle_through_attack: true,
             white_pieces,
             black_pieces,
             zobrist_hash,
+            material,
+            pawn_advancement: [0, 0],
+            pawns,
         }
     }
 
+    /// Parses a board from FEN. The en passant square is only kept if an
+    /// enemy pawn could capture onto it, and the halfmove clock (if given) is
+    /// used as the number of dead moves.
+    pub fn from_fen(fen: &str) -> Result<MyBoard, FenError> {
+        let builder: BoardBuilder = fen.parse().map_err(|_| FenError::Malformed)?;
+        let mut board = MyBoard::initial_board(builder.get_side_to_move());
+        for sq in ALL_SQUARES {
+            board.set_piece(sq, builder[sq]);
+        }
+        for color in [Color::White, Color::Black] {
+            board
+                .king_square(color)
+                .ok_or(FenError::MissingKing(color))?;
+            let rights = builder.get_castle_rights(color);
+            if !board.castle_rights_allowed(color, rights) {
+                return Err(FenError::InvalidCastleRights(color));
+            }
+            board.set_castle_rights(color, rights);
+        }
+        // The builder gives the square of the pawn which double pushed
+        let pusher = !builder.get_side_to_move();
+        if let Some(pawn) = builder.get_en_passant() {
+            if board[pawn] == Some((Piece::Pawn, pusher)) {
+                board.set_en_passant(board.en_passant_after_push(pawn, pusher));
+            }
+        }
+
+        board.dead_moves = match fen.split_whitespace().nth(4) {
+            Some(clock) => clock
+                .parse()
+                .ok()
+                .filter(|&c| c < DEAD_MOVE_LIMIT)
+                .ok_or(FenError::InvalidHalfmoveClock)?,
+            None => 0,
+        };
+        if board.all_moves().next().is_none() {
+            board.status = board.status_without_moves();
+        }
+        Ok(board)
+    }
+
+    /// Builds a board from the piece on each square, indexed by rank and then
+    /// file (so `pieces[0][4]` is e1), with the given castle rights indexed
+    /// by color. Each player must have exactly one king, and any castle rights
+    /// need the king and the matching rook on their home squares.
+    pub fn from_pieces(
+        pieces: [[Option<(Piece, Color)>; 8]; 8], side_to_move: Color,
+        castle_rights: [CastleRights; 2],
+    ) -> Result<MyBoard, PositionError> {
+        let mut board = MyBoard::initial_board(side_to_move);
+        for sq in ALL_SQUARES {
+            board.set_piece(
+                sq,
+                pieces[sq.get_rank().to_index()][sq.get_file().to_index()],
+            );
+        }
+        for color in [Color::White, Color::Black] {
+            let kings = board
+                .color_combined(color)
+                .filter(|&sq| board[sq] == Some((Piece::King, color)))
+                .count();
+            match kings {
+                0 => return Err(PositionError::MissingKing(color)),
+                1 => {}
+                _ => return Err(PositionError::ExtraKing(color)),
+            }
+            let rights = castle_rights[color.to_index()];
+            if !board.castle_rights_allowed(color, rights) {
+                return Err(PositionError::InvalidCastleRights(color));
+            }
+            board.set_castle_rights(color, rights);
+        }
+        if board.all_moves().next().is_none() {
+            board.status = board.status_without_moves();
+        }
+        Ok(board)
+    }
+
+    /// Formats the board as FEN, the inverse of `from_fen`. The halfmove
+    /// clock is the number of dead moves, and the fullmove number is always
+    /// 1, since it isn't tracked.
+    ///
+    /// Returns `None` if the board is awaiting a bonus, since FEN can't
+    /// represent a turn which is only half finished.
+    pub fn to_fen(&self) -> Option<String> {
+        if self.awaiting_bonus {
+            return None;
+        }
+
+        let mut placement = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                let sq = Square::make_square(Rank::from_index(rank), File::from_index(file));
+                match self[sq] {
+                    Some((piece, color)) => {
+                        if empty > 0 {
+                            placement.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        placement.push_str(&piece.to_string(color));
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                placement.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                placement.push('/');
+            }
+        }
+
+        let side = match self.side_to_move {
+            Color::White => "w",
+            Color::Black => "b",
+        };
+        let castling = self.castle_rights[0].to_string(Color::White)
+            + &self.castle_rights[1].to_string(Color::Black);
+        let castling = if castling.is_empty() {
+            "-".to_string()
+        } else {
+            castling
+        };
+
+        let en_passant = self.en_passant.map_or("-".to_string(), |sq| sq.to_string());
+
+        Some(format!(
+            "{} {} {} {} {} 1",
+            placement, side, castling, en_passant, self.dead_moves
+        ))
+    }
+
+    /// The moves for the piece on `sq` that are legal under the board's
+    /// variant
     pub fn moves_from(&self, sq: Square) -> Vec<ChessMove> {
+        match self.variant {
+            Variant::KingCapture => self.pseudo_moves_from(sq),
+            Variant::Standard => self.legal_moves_from(sq),
+        }
+    }
+
+    /// The moves for the piece on `sq` that follow its movement rules,
+    /// without checking whether they leave the king attacked (although
+    /// castling through attacked squares is checked if it isn't allowed).
+    /// These are the legal moves in the king capture variant.
+    pub fn pseudo_moves_from(&self, sq: Square) -> Vec<ChessMove> {
         assert!(
             !self.awaiting_bonus,
             "Tried to request move from board awaiting bonus"
@@ -130,16 +569,32 @@ impl MyBoard {
         if piece == Piece::King {
             if self.get_castle_rights(color).has_kingside()
                 && all & CastleRights::Both.kingside_squares(color) == EMPTY
+                && self.castle_path_allowed(sq, kingside_castle_square(color))
             {
                 moves.push(ChessMove::new(sq, kingside_castle_square(color), None));
             }
             if self.get_castle_rights(color).has_queenside()
                 && all & CastleRights::Both.queenside_squares(color) == EMPTY
+                && self.castle_path_allowed(sq, queenside_castle_square(color))
             {
                 moves.push(ChessMove::new(sq, queenside_castle_square(color), None));
             }
         }
 
+        // Add the en passant capture. Checking for the enemy pawn makes sure
+        // the square was made by the opponent, rather than by this side
+        // before a bonus move.
+        if piece == Piece::Pawn {
+            if let Some(ep) = self.en_passant {
+                let victim = Square::make_square(sq.get_rank(), ep.get_file());
+                if chess::get_pawn_attacks(sq, color, BitBoard::from_square(ep)) != EMPTY
+                    && self[victim] == Some((Piece::Pawn, !color))
+                {
+                    moves.push(ChessMove::new(sq, ep, None));
+                }
+            }
+        }
+
         // Transform backrank pawn moves to promotions
         if piece == Piece::Pawn {
             moves = moves
@@ -161,9 +616,102 @@ impl MyBoard {
         moves
     }
 
+    /// Whether the king on `source` can castle to `dest` given which squares
+    /// are attacked. This is always true if castling through attacked
+    /// squares is allowed.
+    fn castle_path_allowed(&self, source: Square, dest: Square) -> bool {
+        if self.castle_through_attack {
+            return true;
+        }
+        let passed = Square::make_square(
+            source.get_rank(),
+            File::from_index((source.get_file().to_index() + dest.get_file().to_index()) / 2),
+        );
+        let path = BitBoard::from_square(source)
+            | BitBoard::from_square(passed)
+            | BitBoard::from_square(dest);
+        self.attacked_by(!self.side_to_move) & path == EMPTY
+    }
+
+    /// The pseudo-legal moves for the piece on `sq` which don't leave the
+    /// mover's king attacked. These are the legal moves in standard chess,
+    /// where castling through attacked squares is also disallowed.
+    pub fn legal_moves_from(&self, sq: Square) -> Vec<ChessMove> {
+        let color = self.side_to_move;
+
+        self.pseudo_moves_from(sq)
+            .into_iter()
+            .filter(|m| {
+                let mut bd = *self;
+                bd.apply_move_unchecked(*m);
+                bd.king_square(color)
+                    .is_some_and(|k| bd.attacked_by(!color) & BitBoard::from_square(k) == EMPTY)
+            })
+            .collect()
+    }
+
+    /// Sets the rules used to decide which moves are legal. Castling through
+    /// attacked squares is allowed only in the king capture variant, but this
+    /// can be changed afterwards with `with_castle_through_attack`.
+    ///
+    /// If the side to move has no moves under the new rules, the status is
+    /// recomputed, so a position which is checkmate or stalemate in standard
+    /// chess is over straight away.
+    pub fn with_variant(mut self, variant: Variant) -> MyBoard {
+        let old_status = self.status_without_moves();
+        self.variant = variant;
+        self.castle_through_attack = variant == Variant::KingCapture;
+        if (self.status.is_in_progress() || self.status == old_status)
+            && !self.awaiting_bonus
+            && self.all_moves().next().is_none()
+        {
+            self.status = self.status_without_moves();
+        }
+        self
+    }
+
+    /// Sets the number of dead moves after which the game is drawn, which is
+    /// 50 by default. The game is drawn straight away if that many dead moves
+    /// have already been played.
+    pub fn with_dead_move_limit(mut self, limit: u8) -> MyBoard {
+        assert!(limit > 0, "the dead move limit must be positive");
+        self.dead_move_limit = limit;
+        if self.status.is_in_progress() && self.dead_moves >= limit {
+            self.status = Status::Draw;
+        }
+        self
+    }
+
+    /// Sets whether the king may castle out of, through or into an attacked
+    /// square
+    pub fn with_castle_through_attack(mut self, allowed: bool) -> MyBoard {
+        self.castle_through_attack = allowed;
+        self
+    }
+
+    /// Applies `m` if it is legal, panicking otherwise
     pub fn apply_move(&mut self, m: ChessMove) {
-        assert!(self.moves_from(m.get_source()).contains(&m));
+        self.try_apply_move(m)
+            .unwrap_or_else(|e| panic!("can't apply move {}: {}", m, e));
+    }
+
+    /// Applies `m` if it is legal, or returns why it isn't without changing
+    /// the board. This should be used for moves from user input.
+    pub fn try_apply_move(&mut self, m: ChessMove) -> Result<(), MoveError> {
+        if !self.status.is_in_progress() {
+            return Err(MoveError::GameOver);
+        }
+        if self.awaiting_bonus {
+            return Err(MoveError::AwaitingBonus);
+        }
+        if !matches!(self[m.get_source()], Some((_, c)) if c == self.side_to_move) {
+            return Err(MoveError::NotYourPiece);
+        }
+        if !self.moves_from(m.get_source()).contains(&m) {
+            return Err(MoveError::Illegal);
+        }
         self.apply_move_unchecked(m);
+        Ok(())
     }
 
     pub fn apply_move_unchecked(&mut self, m: ChessMove) {
@@ -196,12 +744,28 @@ impl MyBoard {
             self.dead_moves = 0;
         } else {
             self.dead_moves += 1;
-            assert!(self.dead_moves <= 50);
-            if self.dead_moves == 50 {
+            if self.dead_moves >= self.dead_move_limit {
                 self.status = Status::Draw;
             }
         }
 
+        // Remove a pawn captured en passant, and record the en passant square
+        // for a double push. Any other move clears it.
+        if let Some(victim) = self.en_passant_victim(m) {
+            self.set_piece(victim, None);
+        }
+        let is_double_push = p == Piece::Pawn
+            && m.get_source()
+                .get_rank()
+                .to_index()
+                .abs_diff(m.get_dest().get_rank().to_index())
+                == 2;
+        self.set_en_passant(if is_double_push {
+            self.en_passant_after_push(m.get_dest(), c)
+        } else {
+            None
+        });
+
         // Apply the move
         self.set_piece(m.get_dest(), Some((p, c)));
         self.set_piece(m.get_source(), None);
@@ -246,10 +810,11 @@ impl MyBoard {
 
         // Switch turns
         self.switch_side_to_move();
+        self.debug_check_material();
     }
 
-    /// Updates the piece at a particular square. Also updates the bitboards
-    /// and zobrist hash.
+    /// Updates the piece at a particular square. Also updates the bitboards,
+    /// zobrist hash, material, pawn advancement and pawns.
     fn set_piece(&mut self, sq: Square, piece: Option<(Piece, Color)>) {
         if let Some((p, c)) = self[sq] {
             if c == Color::White {
@@ -258,6 +823,11 @@ impl MyBoard {
                 self.black_pieces &= !BitBoard::from_square(sq);
             }
             self.zobrist_hash ^= Zobrist::piece(p, sq, c);
+            self.material[c.to_index()][p.to_index()] -= 1;
+            if p == Piece::Pawn {
+                self.pawn_advancement[c.to_index()] -= u16::from(pawn_advancement(sq, c));
+                self.pawns[c.to_index()] &= !BitBoard::from_square(sq);
+            }
         }
         if let Some((p, c)) = piece {
             if c == Color::White {
@@ -266,6 +836,11 @@ impl MyBoard {
                 self.black_pieces |= BitBoard::from_square(sq);
             }
             self.zobrist_hash ^= Zobrist::piece(p, sq, c);
+            self.material[c.to_index()][p.to_index()] += 1;
+            if p == Piece::Pawn {
+                self.pawn_advancement[c.to_index()] += u16::from(pawn_advancement(sq, c));
+                self.pawns[c.to_index()] |= BitBoard::from_square(sq);
+            }
         }
         self.pieces[sq.to_index()] = piece;
     }
@@ -276,6 +851,34 @@ impl MyBoard {
         self.side_to_move = !self.side_to_move;
     }
 
+    /// Returns a copy of the board with the other side to move. The zobrist
+    /// hash is updated with a single XOR rather than being recomputed.
+    pub fn with_side_switched(&self) -> MyBoard {
+        let mut board = *self;
+        board.switch_side_to_move();
+        board
+    }
+
+    /// Returns a copy of the board after the side to move passes, which
+    /// isn't a legal move but is useful for null-move pruning. The result is
+    /// the position after a pass without the bonus, since with the bonus
+    /// the position would be unchanged. The en passant square is cleared
+    /// because the capture it allowed has been passed up, and the dead moves
+    /// are unchanged since no piece moved.
+    pub fn with_null_move(&self) -> MyBoard {
+        assert!(
+            !self.awaiting_bonus,
+            "Tried to pass on a board awaiting bonus"
+        );
+        let mut board = *self;
+        board.set_en_passant(None);
+        board.switch_side_to_move();
+        if board.all_moves().next().is_none() && board.status.is_in_progress() {
+            board.status = board.status_without_moves();
+        }
+        board
+    }
+
     /// Applies the bonus move but doesn't check for a draw
     pub fn apply_bonus_unchecked(&mut self, is_bonus: bool) {
         assert!(self.awaiting_bonus);
@@ -288,9 +891,21 @@ impl MyBoard {
     pub fn apply_bonus(&mut self, is_bonus: bool) {
         self.apply_bonus_unchecked(is_bonus);
 
-        // Detect no moves draw
+        // Detect no moves draw (or checkmate)
         if self.all_moves().next().is_none() && self.status.is_in_progress() {
-            self.status = Status::Draw;
+            self.status = self.status_without_moves();
+        }
+    }
+
+    /// The result of the game if the side to move has no moves. This is
+    /// always a draw when the king can be captured, but in the standard
+    /// variant it is a loss if the side to move is in check.
+    pub fn status_without_moves(&self) -> Status {
+        match self.variant {
+            Variant::Standard if self.in_check(self.side_to_move) => {
+                Status::Win(!self.side_to_move)
+            }
+            _ => Status::Draw,
         }
     }
 
@@ -302,16 +917,184 @@ impl MyBoard {
         .flat_map(move |sq| self.moves_from(sq))
     }
 
-    pub fn in_check(&self, color: Color) -> bool {
-        let mut bd = *self;
-        bd.awaiting_bonus = false;
-        if bd.side_to_move == color {
-            bd.switch_side_to_move();
+    /// Classifies a move from this board. The move isn't checked for
+    /// legality, but there should be a piece on its source square.
+    pub fn move_kind(&self, m: ChessMove) -> MoveKind {
+        let (p, c) = self[m.get_source()].expect("No piece at source");
+        let captured = match self.en_passant_victim(m) {
+            Some(victim) => self[victim],
+            None => self[m.get_dest()],
         }
-        let ret = bd
-            .all_moves()
-            .any(|m| bd[m.get_dest()] == Some((Piece::King, color)));
-        ret
+        .filter(|&(_, captured_color)| captured_color != c)
+        .map(|(captured, _)| captured);
+
+        if m.get_promotion().is_some() {
+            MoveKind::Promotion { captured }
+        } else if let Some(captured) = captured {
+            MoveKind::Capture(captured)
+        } else if p == Piece::King
+            && m.get_source().get_file() == File::E
+            && matches!(m.get_dest().get_file(), File::C | File::G)
+        {
+            MoveKind::Castle
+        } else {
+            MoveKind::Quiet
+        }
+    }
+
+    /// Formats a legal move in standard algebraic notation, like `Nf3`,
+    /// `exd5`, `O-O` or `e8=Q`. Check (`+`) and checkmate (`#`) are only
+    /// marked in the standard variant, since there is no check otherwise.
+    /// The board shouldn't be awaiting a bonus.
+    pub fn move_to_san(&self, m: ChessMove) -> String {
+        let (p, c) = self[m.get_source()].expect("No piece at source");
+        let kind = self.move_kind(m);
+        let file_char = |sq: Square| (b'a' + sq.get_file().to_index() as u8) as char;
+        let rank_char = |sq: Square| (b'1' + sq.get_rank().to_index() as u8) as char;
+
+        let mut san = String::new();
+        if kind == MoveKind::Castle {
+            san.push_str(if m.get_dest().get_file() == File::G {
+                "O-O"
+            } else {
+                "O-O-O"
+            });
+        } else {
+            if p == Piece::Pawn {
+                if kind.captured().is_some() {
+                    san.push(file_char(m.get_source()));
+                }
+            } else {
+                san.push_str(&p.to_string(Color::White));
+
+                // Disambiguate from other pieces of the same type that can
+                // move to the same square
+                let others: Vec<_> = self
+                    .color_combined(c)
+                    .filter(|&sq| sq != m.get_source() && self[sq] == Some((p, c)))
+                    .filter(|&sq| {
+                        self.moves_from(sq)
+                            .iter()
+                            .any(|o| o.get_dest() == m.get_dest())
+                    })
+                    .collect();
+                if !others.is_empty() {
+                    let src = m.get_source();
+                    if others.iter().all(|sq| sq.get_file() != src.get_file()) {
+                        san.push(file_char(src));
+                    } else if others.iter().all(|sq| sq.get_rank() != src.get_rank()) {
+                        san.push(rank_char(src));
+                    } else {
+                        san.push_str(&src.to_string());
+                    }
+                }
+            }
+            if kind.captured().is_some() {
+                san.push('x');
+            }
+            san.push_str(&m.get_dest().to_string());
+            if let Some(promotion) = m.get_promotion() {
+                san.push('=');
+                san.push_str(&promotion.to_string(Color::White));
+            }
+        }
+
+        if self.variant == Variant::Standard {
+            let mut after = *self;
+            after.apply_move_unchecked(m);
+            if after.in_check(!c) {
+                after.apply_bonus(false);
+                san.push(if after.status == Status::Win(c) {
+                    '#'
+                } else {
+                    '+'
+                });
+            }
+        }
+        san
+    }
+
+    /// Counts the positions reached after `depth` moves, where each move is
+    /// followed by both bonus rolls. Games which finish early count as a
+    /// single position. This is for checking move generation.
+    pub fn perft(&self, depth: u8) -> u64 { self.perft_stats(depth).nodes }
+
+    /// Like `perft`, but also counts the kinds of moves made in the tree, so
+    /// that bugs which don't change the number of positions are caught
+    pub fn perft_stats(&self, depth: u8) -> PerftStats {
+        let mut stats = PerftStats::default();
+        self.perft_into(depth, &mut stats);
+        stats
+    }
+
+    fn perft_into(&self, depth: u8, stats: &mut PerftStats) {
+        if depth == 0 || !self.status.is_in_progress() {
+            stats.nodes += 1;
+            return;
+        }
+        for m in self.all_moves() {
+            let kind = self.move_kind(m);
+            match kind {
+                MoveKind::Castle => stats.castles += 1,
+                MoveKind::Promotion { .. } => stats.promotions += 1,
+                _ => {}
+            }
+            if let Some(captured) = kind.captured() {
+                stats.captures += 1;
+                if captured == Piece::King {
+                    stats.king_captures += 1;
+                }
+            }
+
+            let mut after = *self;
+            after.apply_move_unchecked(m);
+            if !after.status.is_in_progress() {
+                stats.nodes += 1;
+                continue;
+            }
+            for is_bonus in [false, true] {
+                let mut next = after;
+                next.apply_bonus(is_bonus);
+                if is_bonus {
+                    stats.bonus_branches += 1;
+                }
+                next.perft_into(depth - 1, stats);
+            }
+        }
+    }
+
+    /// Each of the side to move's pieces along with its legal moves, which
+    /// are the same moves as `all_moves` but grouped by source square.
+    /// Pieces without any moves are skipped.
+    pub fn moves_by_piece(&self) -> impl Iterator<Item = (Square, Piece, Vec<ChessMove>)> + '_ {
+        self.color_combined(self.side_to_move)
+            .filter_map(move |sq| {
+                let (piece, _) = self[sq].expect("piece should be on its bitboard");
+                let moves = self.moves_from(sq);
+                (!moves.is_empty()).then_some((sq, piece, moves))
+            })
+    }
+
+    /// Whether any enemy piece attacks the king of `color`. This only looks
+    /// at where the pieces are, so it doesn't matter whose turn it is or
+    /// whether the board is awaiting a bonus.
+    pub fn in_check(&self, color: Color) -> bool {
+        self.king_square(color)
+            .is_some_and(|k| self.attacked_by(!color) & BitBoard::from_square(k) != EMPTY)
+    }
+
+    /// The legal moves after which the opponent can't capture the king
+    /// straight away (when there is no bonus). Moves that capture the
+    /// opponent's king are always included, since they end the game.
+    pub fn non_losing_moves(&self) -> Vec<ChessMove> {
+        let color = self.side_to_move;
+        self.all_moves()
+            .filter(|&mv| {
+                let mut bd = *self;
+                bd.apply_move(mv);
+                !bd.status.is_in_progress() || !bd.in_check(color)
+            })
+            .collect()
     }
 
     pub fn move_is_dangerous(&self, mv: ChessMove) -> bool {
@@ -321,10 +1104,89 @@ impl MyBoard {
     }
 
     pub fn king_square(&self, color: Color) -> Option<Square> {
-        ALL_SQUARES
-            .iter()
-            .find(|&&sq| self[sq] == Some((Piece::King, color)))
-            .copied()
+        self.color_combined(color)
+            .find(|&sq| self[sq] == Some((Piece::King, color)))
+    }
+
+    /// The squares attacked by any piece of the given color, regardless of
+    /// whose turn it is. This is computed from bitboards, without generating
+    /// any moves.
+    pub fn attacked_by(&self, color: Color) -> BitBoard {
+        let mut attacked = EMPTY;
+        for sq in self.color_combined(color) {
+            attacked |= self.attacks_from(sq);
+        }
+        attacked
+    }
+
+    /// The squares attacked by the piece on `sq`, given the current occupancy
+    fn attacks_from(&self, sq: Square) -> BitBoard {
+        let Some((piece, color)) = self[sq] else {
+            return EMPTY;
+        };
+        let all = self.combined();
+        match piece {
+            Piece::Pawn => chess::get_pawn_attacks(sq, color, !EMPTY),
+            Piece::Knight => chess::get_knight_moves(sq),
+            Piece::Bishop => chess::get_bishop_moves(sq, all),
+            Piece::Rook => chess::get_rook_moves(sq, all),
+            Piece::Queen => chess::get_bishop_moves(sq, all) | chess::get_rook_moves(sq, all),
+            Piece::King => chess::get_king_moves(sq),
+        }
+    }
+
+    /// The squares whose attackers could change as a result of `mv`, i.e.
+    /// everything the moved pieces attack before and after the move, and
+    /// the squares along the rays through each square that is vacated or
+    /// occupied (which sliding pieces might now reach or be blocked from).
+    /// This is a superset of the changes to `attacked_by`, so attack maps
+    /// only need to be recomputed on these squares.
+    pub fn affected_squares(&self, mv: ChessMove) -> BitBoard {
+        let (source, dest) = (mv.get_source(), mv.get_dest());
+        let mut changed = BitBoard::from_square(source) | BitBoard::from_square(dest);
+
+        // Castling moves the rook as well
+        if matches!(self[source], Some((Piece::King, _)))
+            && source
+                .get_file()
+                .to_index()
+                .abs_diff(dest.get_file().to_index())
+                == 2
+        {
+            let rank = source.get_rank();
+            let (rook_from, rook_to) = if dest.get_file() == File::G {
+                (File::H, File::F)
+            } else {
+                (File::A, File::D)
+            };
+            changed |= BitBoard::from_square(Square::make_square(rank, rook_from))
+                | BitBoard::from_square(Square::make_square(rank, rook_to));
+        }
+
+        // Capturing en passant removes a pawn from another square
+        if let Some(victim) = self.en_passant_victim(mv) {
+            changed |= BitBoard::from_square(victim);
+        }
+
+        // Using only the pieces that stay put as blockers makes the rays as
+        // long as they could be either before or after the move
+        let blockers = self.combined() & !changed;
+        let mut affected = changed;
+        for sq in changed {
+            affected |= chess::get_rook_moves(sq, blockers)
+                | chess::get_bishop_moves(sq, blockers)
+                | chess::get_knight_moves(sq)
+                | chess::get_king_moves(sq);
+        }
+        affected
+    }
+
+    /// Returns true if neither side has a capture available. Static
+    /// evaluations of positions which aren't quiet are less trustworthy, since
+    /// material (or the king) is about to change hands.
+    pub fn is_quiet(&self) -> bool {
+        self.attacked_by(Color::White) & self.black_pieces == EMPTY
+            && self.attacked_by(Color::Black) & self.white_pieces == EMPTY
     }
 
     fn color_combined(&self, c: Color) -> BitBoard {
@@ -338,7 +1200,7 @@ impl MyBoard {
         self.color_combined(Color::White) | self.color_combined(Color::Black)
     }
 
-    #[cfg(test)]
+    #[cfg(any(test, feature = "test-util"))]
     pub fn invert_ranks_and_colors(&mut self) {
         self.switch_side_to_move();
 
@@ -347,6 +1209,13 @@ impl MyBoard {
         self.set_castle_rights(Color::White, black_rights);
         self.set_castle_rights(Color::Black, white_rights);
 
+        self.set_en_passant(self.en_passant.map(|sq| {
+            Square::make_square(
+                Rank::from_index(7 - sq.get_rank().to_index()),
+                sq.get_file(),
+            )
+        }));
+
         self.status = match self.status {
             Status::Win(c) => Status::Win(!c),
             _ => self.status,
@@ -367,10 +1236,11 @@ impl MyBoard {
                 self.set_piece(sq2, tmp);
             }
         }
+        self.debug_check_material();
     }
 
     /// Note that this stuffs up the castling logic
-    #[cfg(test)]
+    #[cfg(any(test, feature = "test-util"))]
     pub fn invert_files(&mut self) {
         for rank in 0..8 {
             for file in 0..4 {
@@ -382,6 +1252,13 @@ impl MyBoard {
             }
         }
 
+        self.set_en_passant(self.en_passant.map(|sq| {
+            Square::make_square(
+                sq.get_rank(),
+                File::from_index(7 - sq.get_file().to_index()),
+            )
+        }));
+
         self.castle_rights.iter_mut().for_each(|r| {
             *r = match r {
                 CastleRights::KingSide => CastleRights::QueenSide,
@@ -389,13 +1266,129 @@ impl MyBoard {
                 _ => *r,
             }
         });
+        self.debug_check_material();
     }
 
-    #[cfg(test)]
+    #[cfg(any(test, feature = "test-util"))]
     pub fn strip_castle_rights(&mut self) {
         self.set_castle_rights(Color::White, CastleRights::NoRights);
         self.set_castle_rights(Color::Black, CastleRights::NoRights);
     }
+
+    /// Lists the differences between `self` and `other`, treating `self` as the
+    /// old board. This is mostly useful for debugging move application.
+    pub fn diff(&self, other: &MyBoard) -> BoardDiff {
+        let mut diff = BoardDiff::default();
+        for sq in ALL_SQUARES {
+            if self[sq] != other[sq] {
+                diff.squares.push((sq, self[sq], other[sq]));
+            }
+        }
+        if self.side_to_move != other.side_to_move {
+            diff.side_to_move = Some((self.side_to_move, other.side_to_move));
+        }
+        for color in [Color::White, Color::Black] {
+            let old = self.get_castle_rights(color);
+            let new = other.get_castle_rights(color);
+            if old != new {
+                diff.castle_rights[color.to_index()] = Some((old, new));
+            }
+        }
+        if self.en_passant != other.en_passant {
+            diff.en_passant = Some((self.en_passant, other.en_passant));
+        }
+        if self.status != other.status {
+            diff.status = Some((self.status, other.status));
+        }
+        diff
+    }
+
+    /// Builds a board with just the given pieces and no castling rights
+    #[cfg(test)]
+    pub fn from_piece_list(side_to_move: Color, pieces: &[(Square, Piece, Color)]) -> MyBoard {
+        let mut board = MyBoard {
+            pieces: [None; 64],
+            side_to_move,
+            castle_rights: [CastleRights::NoRights, CastleRights::NoRights],
+            en_passant: None,
+            dead_moves: 0,
+            dead_move_limit: DEAD_MOVE_LIMIT,
+            status: Status::InProgress,
+            awaiting_bonus: false,
+            variant: Variant::default(),
+            castle_through_attack: true,
+            white_pieces: EMPTY,
+            black_pieces: EMPTY,
+            zobrist_hash: 0,
+            material: [[0; 6]; 2],
+            pawn_advancement: [0, 0],
+            pawns: [EMPTY; 2],
+        };
+        board.zobrist_hash = board.compute_zobrist_hash();
+        for &(sq, p, c) in pieces {
+            board.set_piece(sq, Some((p, c)));
+        }
+        board
+    }
+
+    /// Computes the material, pawn advancement and pawns from scratch, rather
+    /// than incrementally, as `(material, pawn_advancement, pawns)`
+    pub(crate) fn compute_material(&self) -> ([[u8; 6]; 2], [u16; 2], [BitBoard; 2]) {
+        let mut material = [[0; 6]; 2];
+        let mut advancement = [0; 2];
+        let mut pawns = [EMPTY; 2];
+        for sq in ALL_SQUARES {
+            let Some((p, c)) = self[sq] else { continue };
+            material[c.to_index()][p.to_index()] += 1;
+            if p == Piece::Pawn {
+                advancement[c.to_index()] += u16::from(pawn_advancement(sq, c));
+                pawns[c.to_index()] |= BitBoard::from_square(sq);
+            }
+        }
+        (material, advancement, pawns)
+    }
+
+    /// In debug builds, panics if the incrementally updated material, pawn
+    /// advancement and pawns don't match `compute_material`
+    pub(crate) fn debug_check_material(&self) {
+        debug_assert_eq!(
+            (self.material, self.pawn_advancement, self.pawns),
+            self.compute_material(),
+            "Incremental material is out of date on\n{}",
+            self
+        );
+    }
+
+    /// Computes the zobrist hash from scratch, rather than incrementally
+    #[cfg(test)]
+    pub fn compute_zobrist_hash(&self) -> u64 {
+        let mut hash = 0;
+        for sq in ALL_SQUARES {
+            if let Some((p, c)) = self[sq] {
+                hash ^= Zobrist::piece(p, sq, c);
+            }
+        }
+        hash ^= Zobrist::castles(self.castle_rights[0], Color::White);
+        hash ^= Zobrist::castles(self.castle_rights[1], Color::Black);
+        if let Some(sq) = self.en_passant {
+            hash ^= Zobrist::en_passant(sq.get_file());
+        }
+        if self.side_to_move == Color::Black {
+            hash ^= Zobrist::color();
+        }
+        hash
+    }
+}
+
+/// How many ranks a `color` pawn on `sq` has advanced from its starting rank.
+/// A pawn can't be behind its starting rank, but one placed there by
+/// `from_pieces` counts as not having advanced.
+pub(crate) fn pawn_advancement(sq: Square, color: Color) -> u8 {
+    let rank = sq.get_rank().to_index() as u8;
+    match color {
+        Color::White => rank.saturating_sub(1),
+        Color::Black => 6u8.saturating_sub(rank),
+    }
 }
 
 fn kingside_castle_square(color: Color) -> Square {
@@ -443,7 +1436,7 @@ impl std::fmt::Display for MyBoard {
         for rank in (0..8).rev() {
             s.push_str(
                 Colour::Fixed(94)
-                    .paint(&format!("  {} ", rank + 1))
+                    .paint(format!("  {} ", rank + 1))
                     .to_string()
                     .as_str(),
             );
@@ -0,0 +1,893 @@
+use chess::{BitBoard, CastleRights, ChessMove, Color, Piece, Square, EMPTY};
+
+use super::*;
+use crate::utils::SeededRng;
+
+#[test]
+fn test_with_side_switched_hash() {
+    let mut board = MyBoard::initial_board(Color::White);
+
+    let switched = board.with_side_switched();
+    assert_eq!(switched.get_side_to_move(), Color::Black);
+    assert_eq!(switched.get_zobrist_hash(), switched.compute_zobrist_hash());
+    assert_eq!(
+        switched.get_zobrist_hash(),
+        MyBoard::initial_board(Color::Black).get_zobrist_hash()
+    );
+
+    board.apply_move(ChessMove::new(Square::E2, Square::E4, None));
+    board.apply_bonus(false);
+    board.apply_move(ChessMove::new(Square::D7, Square::D5, None));
+    board.apply_bonus(false);
+
+    let switched = board.with_side_switched();
+    assert_eq!(switched.get_zobrist_hash(), switched.compute_zobrist_hash());
+    assert_eq!(
+        switched.with_side_switched().get_zobrist_hash(),
+        board.get_zobrist_hash()
+    );
+}
+
+#[test]
+fn test_with_null_move() {
+    let board = MyBoard::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 3 1").unwrap();
+    assert_eq!(board.get_en_passant(), Some(Square::D6));
+
+    let passed = board.with_null_move();
+    assert_eq!(passed.get_side_to_move(), Color::Black);
+    assert_eq!(passed.get_en_passant(), None);
+    assert_eq!(passed.get_dead_moves(), 3);
+    assert!(!passed.is_awaiting_bonus());
+    assert_eq!(passed.get_zobrist_hash(), passed.compute_zobrist_hash());
+}
+
+#[test]
+fn test_is_quiet() {
+    let mut board = MyBoard::initial_board(Color::White);
+    assert!(board.is_quiet());
+
+    board.apply_move(ChessMove::new(Square::E2, Square::E4, None));
+    board.apply_bonus(false);
+    assert!(board.is_quiet());
+
+    // The pawn on d5 is hanging to the pawn on e4 (and vice versa)
+    board.apply_move(ChessMove::new(Square::D7, Square::D5, None));
+    board.apply_bonus(false);
+    assert!(!board.is_quiet());
+    assert_ne!(
+        board.attacked_by(Color::White) & BitBoard::from_square(Square::D5),
+        EMPTY
+    );
+}
+
+#[test]
+fn test_from_pieces() {
+    let mut pieces = [[None; 8]; 8];
+    pieces[0][4] = Some((Piece::King, Color::White));
+    pieces[0][7] = Some((Piece::Rook, Color::White));
+    pieces[1][3] = Some((Piece::Pawn, Color::White));
+    pieces[7][4] = Some((Piece::King, Color::Black));
+    pieces[6][2] = Some((Piece::Knight, Color::Black));
+
+    let board = MyBoard::from_pieces(pieces, Color::Black, [
+        CastleRights::KingSide,
+        CastleRights::NoRights,
+    ])
+    .unwrap();
+    let fen = MyBoard::from_fen("4k3/2n5/8/8/8/8/3P4/4K2R b K - 0 1").unwrap();
+    assert!(fen.diff(&board).is_empty());
+    assert_eq!(board.get_zobrist_hash(), fen.get_zobrist_hash());
+    assert_eq!(board.get_zobrist_hash(), board.compute_zobrist_hash());
+
+    // There's no rook on a1 for white to castle queenside with, and black's
+    // king isn't on e8 once it moves
+    assert_eq!(
+        MyBoard::from_pieces(pieces, Color::White, [
+            CastleRights::Both,
+            CastleRights::NoRights
+        ])
+        .unwrap_err(),
+        PositionError::InvalidCastleRights(Color::White)
+    );
+    let mut moved = pieces;
+    moved[7][4] = None;
+    moved[7][3] = Some((Piece::King, Color::Black));
+    assert_eq!(
+        MyBoard::from_pieces(moved, Color::White, [
+            CastleRights::NoRights,
+            CastleRights::KingSide
+        ])
+        .unwrap_err(),
+        PositionError::InvalidCastleRights(Color::Black)
+    );
+
+    pieces[7][4] = None;
+    assert_eq!(
+        MyBoard::from_pieces(pieces, Color::White, [CastleRights::NoRights; 2]).unwrap_err(),
+        PositionError::MissingKing(Color::Black)
+    );
+    pieces[7][4] = Some((Piece::King, Color::Black));
+    pieces[3][3] = Some((Piece::King, Color::White));
+    assert_eq!(
+        MyBoard::from_pieces(pieces, Color::White, [CastleRights::NoRights; 2]).unwrap_err(),
+        PositionError::ExtraKing(Color::White)
+    );
+}
+
+#[test]
+fn test_to_fen() {
+    let initial = MyBoard::initial_board(Color::White);
+    assert_eq!(
+        initial.to_fen().unwrap(),
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+    );
+
+    let fen = "4k3/8/8/8/8/8/8/4K2R b K - 12 1";
+    assert_eq!(MyBoard::from_fen(fen).unwrap().to_fen().unwrap(), fen);
+
+    let mut board = initial;
+    for mv in [
+        ChessMove::new(Square::G1, Square::F3, None),
+        ChessMove::new(Square::D7, Square::D5, None),
+        ChessMove::new(Square::H1, Square::G1, None),
+    ] {
+        board.apply_move(mv);
+        assert_eq!(board.to_fen(), None);
+        board.apply_bonus(false);
+        let fen = board.to_fen().unwrap();
+        let parsed = MyBoard::from_fen(&fen).unwrap();
+        assert_eq!(parsed.get_zobrist_hash(), board.get_zobrist_hash());
+        assert!(parsed.diff(&board).is_empty());
+    }
+    assert_eq!(
+        board.to_fen().unwrap(),
+        "rnbqkbnr/ppp1pppp/8/3p4/8/5N2/PPPPPPPP/RNBQKBR1 b Qkq - 1 1"
+    );
+}
+
+#[test]
+fn test_moves_until_fifty_move_draw() {
+    let mut board = MyBoard::initial_board(Color::White);
+    assert_eq!(board.moves_until_fifty_move_draw(), 50);
+
+    for (from, to, remaining) in [
+        (Square::G1, Square::F3, 49),
+        (Square::B8, Square::C6, 48),
+        (Square::F3, Square::E5, 47),
+        // Capturing resets the count
+        (Square::C6, Square::E5, 50),
+    ] {
+        board.apply_move(ChessMove::new(from, to, None));
+        board.apply_bonus(false);
+        assert_eq!(board.moves_until_fifty_move_draw(), remaining);
+    }
+
+    assert!(!board.is_near_draw(10));
+    assert!(board.is_near_draw(50));
+}
+
+#[test]
+fn test_dead_move_limit() {
+    let mut board = MyBoard::initial_board(Color::White).with_dead_move_limit(4);
+    assert_eq!(board.moves_until_fifty_move_draw(), 4);
+
+    for (from, to) in [
+        (Square::G1, Square::F3),
+        (Square::G8, Square::F6),
+        (Square::F3, Square::G1),
+        (Square::F6, Square::G8),
+    ] {
+        assert!(board.get_status().is_in_progress());
+        board.apply_move(ChessMove::new(from, to, None));
+        board.apply_bonus(false);
+    }
+    assert_eq!(board.get_status(), Status::Draw);
+
+    // Lowering the limit below the dead moves already played draws the game
+    let board = MyBoard::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 10 1").unwrap();
+    assert_eq!(
+        board.with_dead_move_limit(20).get_status(),
+        Status::InProgress
+    );
+    let lowered = board.with_dead_move_limit(5);
+    assert_eq!(lowered.get_status(), Status::Draw);
+    assert_eq!(lowered.moves_until_fifty_move_draw(), 0);
+}
+
+#[test]
+fn test_pending_event() {
+    let mut board = MyBoard::initial_board(Color::White);
+    assert_eq!(board.pending_event(), Event::AwaitingMove);
+
+    board.apply_move(ChessMove::new(Square::E2, Square::E4, None));
+    assert_eq!(board.pending_event(), Event::AwaitingBonus);
+    board.apply_bonus(true);
+    assert_eq!(board.pending_event(), Event::AwaitingMove);
+    assert_eq!(board.get_side_to_move(), Color::White);
+
+    board.apply_move(ChessMove::new(Square::D1, Square::H5, None));
+    board.apply_bonus(false);
+    board.apply_move(ChessMove::new(Square::F7, Square::F6, None));
+    board.apply_bonus(false);
+    assert_eq!(board.pending_event(), Event::AwaitingMove);
+
+    // Capturing the king ends the game without waiting for the bonus
+    board.apply_move(ChessMove::new(Square::H5, Square::E8, None));
+    assert_eq!(board.pending_event(), Event::GameOver);
+}
+
+#[test]
+fn test_affected_squares() {
+    let board = MyBoard::from_piece_list(Color::White, &[
+        (Square::E1, Piece::King, Color::White),
+        (Square::A1, Piece::Rook, Color::White),
+        (Square::A6, Piece::Pawn, Color::Black),
+        (Square::E8, Piece::King, Color::Black),
+    ]);
+    let mv = ChessMove::new(Square::A1, Square::A4, None);
+    let affected = board.affected_squares(mv);
+
+    // The rook's old ray along the first rank, its new ray along the fourth
+    // rank, and the file it moved along up to the pawn
+    for sq in [
+        Square::B1,
+        Square::D1,
+        Square::E1,
+        Square::B4,
+        Square::H4,
+        Square::A2,
+        Square::A5,
+        Square::A6,
+    ] {
+        assert_ne!(affected & BitBoard::from_square(sq), EMPTY, "{}", sq);
+    }
+    // The pawn blocks the rest of the file
+    assert_eq!(affected & BitBoard::from_square(Square::A7), EMPTY);
+
+    // Everything whose attackers changed must be included
+    let mut after = board;
+    after.apply_move(mv);
+    for color in [Color::White, Color::Black] {
+        let changed = board.attacked_by(color) ^ after.attacked_by(color);
+        assert_eq!(changed & !affected, EMPTY);
+    }
+}
+
+#[test]
+fn test_with_castle_rights() {
+    let board = MyBoard::from_piece_list(Color::White, &[
+        (Square::E1, Piece::King, Color::White),
+        (Square::A1, Piece::Rook, Color::White),
+        (Square::H1, Piece::Rook, Color::White),
+        (Square::E8, Piece::King, Color::Black),
+    ])
+    .with_castle_rights(Color::White, CastleRights::Both);
+    assert_eq!(board.get_zobrist_hash(), board.compute_zobrist_hash());
+
+    let kingside = ChessMove::new(Square::E1, Square::G1, None);
+    let queenside = ChessMove::new(Square::E1, Square::C1, None);
+    assert!(board.moves_from(Square::E1).contains(&kingside));
+    assert!(board.moves_from(Square::E1).contains(&queenside));
+
+    let board = board.with_castle_rights(Color::White, CastleRights::QueenSide);
+    assert_eq!(board.get_zobrist_hash(), board.compute_zobrist_hash());
+    assert!(!board.moves_from(Square::E1).contains(&kingside));
+    assert!(board.moves_from(Square::E1).contains(&queenside));
+}
+
+#[test]
+fn test_diff() {
+    let board = MyBoard::initial_board(Color::White);
+    assert!(board.diff(&board).is_empty());
+
+    let mut pushed = board;
+    pushed.apply_move(ChessMove::new(Square::E2, Square::E4, None));
+    pushed.apply_bonus(false);
+
+    let diff = board.diff(&pushed);
+    assert_eq!(diff.squares, vec![
+        (Square::E2, Some((Piece::Pawn, Color::White)), None),
+        (Square::E4, None, Some((Piece::Pawn, Color::White))),
+    ]);
+    assert_eq!(diff.side_to_move, Some((Color::White, Color::Black)));
+    assert_eq!(diff.castle_rights, [None, None]);
+    assert_eq!(diff.status, None);
+}
+
+#[test]
+fn test_legal_moves() {
+    // The bishop is pinned to the king by the rook
+    let board = MyBoard::from_piece_list(Color::White, &[
+        (Square::E1, Piece::King, Color::White),
+        (Square::E2, Piece::Bishop, Color::White),
+        (Square::E8, Piece::Rook, Color::Black),
+        (Square::A8, Piece::King, Color::Black),
+    ]);
+    let standard = board.with_variant(Variant::Standard);
+    let pinned_move = ChessMove::new(Square::E2, Square::D3, None);
+
+    assert!(board.pseudo_moves_from(Square::E2).contains(&pinned_move));
+    assert!(board.moves_from(Square::E2).contains(&pinned_move));
+    assert!(standard
+        .pseudo_moves_from(Square::E2)
+        .contains(&pinned_move));
+    assert!(standard.legal_moves_from(Square::E2).is_empty());
+    assert!(standard.moves_from(Square::E2).is_empty());
+
+    // The pin doesn't stop the bishop from protecting the king
+    assert!(!standard.in_check(Color::White));
+    assert!(standard.moves_from(Square::E1).contains(&ChessMove::new(
+        Square::E1,
+        Square::D1,
+        None
+    )));
+
+    // Castling through an attacked square is illegal in standard chess
+    let castling = MyBoard::from_piece_list(Color::White, &[
+        (Square::E1, Piece::King, Color::White),
+        (Square::H1, Piece::Rook, Color::White),
+        (Square::F8, Piece::Rook, Color::Black),
+        (Square::A8, Piece::King, Color::Black),
+    ])
+    .with_castle_rights(Color::White, CastleRights::KingSide);
+    let castle = ChessMove::new(Square::E1, Square::G1, None);
+    assert!(castling.moves_from(Square::E1).contains(&castle));
+    assert!(!castling
+        .with_variant(Variant::Standard)
+        .moves_from(Square::E1)
+        .contains(&castle));
+}
+
+#[test]
+fn test_castle_through_attack() {
+    // The black rook attacks f1, which the king passes through
+    let board = MyBoard::from_fen("k4r2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+    let castle = ChessMove::new(Square::E1, Square::G1, None);
+    assert!(board.get_castle_through_attack());
+    assert!(board.moves_from(Square::E1).contains(&castle));
+
+    let strict = board.with_castle_through_attack(false);
+    assert!(!strict.moves_from(Square::E1).contains(&castle));
+    assert_eq!(strict.get_variant(), Variant::KingCapture);
+
+    // Castling out of or into an attacked square is also disallowed
+    for fen in [
+        "k3r3/8/8/8/8/8/8/4K2R w K - 0 1",
+        "k5r1/8/8/8/8/8/8/4K2R w K - 0 1",
+    ] {
+        let board = MyBoard::from_fen(fen).unwrap();
+        assert!(board.moves_from(Square::E1).contains(&castle));
+        assert!(!board
+            .with_castle_through_attack(false)
+            .moves_from(Square::E1)
+            .contains(&castle));
+    }
+
+    // The standard variant disallows it by default, but can allow it
+    let standard = board.with_variant(Variant::Standard);
+    assert!(!standard.get_castle_through_attack());
+    assert!(!standard.moves_from(Square::E1).contains(&castle));
+    assert!(standard
+        .with_castle_through_attack(true)
+        .moves_from(Square::E1)
+        .contains(&castle));
+}
+
+#[test]
+fn test_from_fen() {
+    let initial = MyBoard::initial_board(Color::White);
+    let board =
+        MyBoard::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+    assert!(initial.diff(&board).is_empty());
+    assert_eq!(board.get_zobrist_hash(), initial.get_zobrist_hash());
+    assert_eq!(board.get_dead_moves(), 0);
+    assert_eq!(board.pending_event(), Event::AwaitingMove);
+
+    let board = MyBoard::from_fen("4k3/8/8/8/8/8/8/4K2R b K e3 12 40").unwrap();
+    assert_eq!(board.get_side_to_move(), Color::Black);
+    assert_eq!(board.get_dead_moves(), 12);
+    assert_eq!(
+        board.get_castle_rights(Color::White),
+        CastleRights::KingSide
+    );
+    assert_eq!(board.get_zobrist_hash(), board.compute_zobrist_hash());
+
+    assert_eq!(
+        MyBoard::from_fen("not a fen").err(),
+        Some(FenError::Malformed)
+    );
+    assert_eq!(
+        MyBoard::from_fen("8/8/8/8/8/8/8/4K2R b K - 0 1").err(),
+        Some(FenError::MissingKing(Color::Black))
+    );
+    assert_eq!(
+        MyBoard::from_fen("4k3/8/8/8/8/8/8/4K2R b K - 50 40").err(),
+        Some(FenError::InvalidHalfmoveClock)
+    );
+
+    // Castle rights need the king and rook on their home squares
+    for (fen, color) in [
+        ("4k3/8/8/8/8/8/8/R3K3 w K - 0 1", Color::White),
+        ("4k3/8/8/8/8/8/8/3K3R w K - 0 1", Color::White),
+        ("4k3/8/8/8/8/8/8/4K2N w K - 0 1", Color::White),
+        ("4k2r/8/8/8/8/8/8/4K3 w q - 0 1", Color::Black),
+        ("4k2R/8/8/8/8/8/8/4K3 w k - 0 1", Color::Black),
+    ] {
+        assert_eq!(
+            MyBoard::from_fen(fen).err(),
+            Some(FenError::InvalidCastleRights(color)),
+            "{}",
+            fen
+        );
+    }
+    let board = MyBoard::from_fen("r3k3/8/8/8/8/8/8/R3K2R w KQq - 0 1").unwrap();
+    assert_eq!(board.get_castle_rights(Color::White), CastleRights::Both);
+    assert_eq!(
+        board.get_castle_rights(Color::Black),
+        CastleRights::QueenSide
+    );
+}
+
+#[test]
+fn test_en_passant() {
+    let mut board = MyBoard::initial_board(Color::White);
+    for (from, to) in [
+        (Square::E2, Square::E4),
+        (Square::A7, Square::A6),
+        (Square::E4, Square::E5),
+    ] {
+        board.apply_move(ChessMove::new(from, to, None));
+        board.apply_bonus(false);
+    }
+    // There's no black pawn beside e4 to capture it
+    assert_eq!(board.get_en_passant(), None);
+
+    board.apply_move(ChessMove::new(Square::D7, Square::D5, None));
+    board.apply_bonus(false);
+    assert_eq!(board.get_en_passant(), Some(Square::D6));
+    assert_eq!(board.get_zobrist_hash(), board.compute_zobrist_hash());
+    assert_eq!(
+        board.to_fen().unwrap(),
+        "rnbqkbnr/1pp1pppp/p7/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 1"
+    );
+    let parsed = MyBoard::from_fen(&board.to_fen().unwrap()).unwrap();
+    assert_eq!(parsed.get_zobrist_hash(), board.get_zobrist_hash());
+
+    let capture = ChessMove::new(Square::E5, Square::D6, None);
+    assert!(board.moves_from(Square::E5).contains(&capture));
+    assert_eq!(
+        board.affected_squares(capture) & BitBoard::from_square(Square::D5),
+        BitBoard::from_square(Square::D5)
+    );
+
+    // Any other move clears the en passant square
+    let mut other = board;
+    other.apply_move(ChessMove::new(Square::G1, Square::F3, None));
+    other.apply_bonus(false);
+    assert_eq!(other.get_en_passant(), None);
+    assert_eq!(other.get_zobrist_hash(), other.compute_zobrist_hash());
+
+    board.apply_move(capture);
+    board.apply_bonus(false);
+    assert_eq!(board[Square::D5], None);
+    assert_eq!(board[Square::D6], Some((Piece::Pawn, Color::White)));
+    assert_eq!(board.get_en_passant(), None);
+    assert_eq!(board.get_zobrist_hash(), board.compute_zobrist_hash());
+
+    // The side which double pushed can't capture onto the square itself
+    // after a bonus
+    let mut board = MyBoard::from_fen("4k3/8/8/8/5p2/8/3PP3/4K3 w - - 0 1").unwrap();
+    board.apply_move(ChessMove::new(Square::E2, Square::E4, None));
+    board.apply_bonus(true);
+    assert_eq!(board.get_en_passant(), Some(Square::E3));
+    assert!(board.all_moves().all(|m| m.get_dest() != Square::E3));
+}
+
+#[test]
+fn test_in_check() {
+    let initial = MyBoard::initial_board(Color::White);
+    assert_eq!(initial.king_square(Color::White), Some(Square::E1));
+    assert_eq!(initial.king_square(Color::Black), Some(Square::E8));
+    assert!(!initial.in_check(Color::White));
+    assert!(!initial.in_check(Color::Black));
+
+    let mut board = MyBoard::from_piece_list(Color::White, &[
+        (Square::E1, Piece::King, Color::White),
+        (Square::E5, Piece::Rook, Color::White),
+        (Square::E8, Piece::King, Color::Black),
+    ]);
+    assert!(board.in_check(Color::Black));
+    assert!(!board.in_check(Color::White));
+
+    // It doesn't matter whether the board is awaiting a bonus
+    board.apply_move(ChessMove::new(Square::E5, Square::E6, None));
+    assert_eq!(board.pending_event(), Event::AwaitingBonus);
+    assert!(board.in_check(Color::Black));
+}
+
+#[test]
+fn test_standard_checkmate() {
+    // Back rank mate: the rook move leaves black in check with no moves
+    let board = MyBoard::from_piece_list(Color::White, &[
+        (Square::G1, Piece::King, Color::White),
+        (Square::A1, Piece::Rook, Color::White),
+        (Square::G8, Piece::King, Color::Black),
+        (Square::F7, Piece::Pawn, Color::Black),
+        (Square::G7, Piece::Pawn, Color::Black),
+        (Square::H7, Piece::Pawn, Color::Black),
+    ]);
+    let mut mate = board.with_variant(Variant::Standard);
+    let mut king_capture = board;
+    let rook_move = ChessMove::new(Square::A1, Square::A8, None);
+
+    mate.apply_move(rook_move);
+    mate.apply_bonus(false);
+    assert_eq!(mate.get_status(), Status::Win(Color::White));
+
+    // The king can still move into check when it can be captured
+    king_capture.apply_move(rook_move);
+    king_capture.apply_bonus(false);
+    assert_eq!(king_capture.get_status(), Status::InProgress);
+
+    // Having no moves without being in check is still a draw
+    let stalemate = MyBoard::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1")
+        .unwrap()
+        .with_variant(Variant::Standard);
+    assert_eq!(stalemate.status_without_moves(), Status::Draw);
+    assert_eq!(stalemate.get_status(), Status::Draw);
+    assert!(stalemate.all_moves().next().is_none());
+
+    // A position which is only mate under the standard rules is over as soon
+    // as they apply
+    let mated = MyBoard::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+    assert_eq!(mated.get_status(), Status::InProgress);
+    let mated = mated.with_variant(Variant::Standard);
+    assert!(mated.all_moves().next().is_none());
+    assert_eq!(mated.get_status(), Status::Win(Color::White));
+    assert_eq!(mated.pending_event(), Event::GameOver);
+}
+
+#[test]
+fn test_try_apply_move() {
+    let mut board = MyBoard::initial_board(Color::White);
+    let before = board;
+
+    assert_eq!(
+        board.try_apply_move(ChessMove::new(Square::E7, Square::E5, None)),
+        Err(MoveError::NotYourPiece)
+    );
+    assert_eq!(
+        board.try_apply_move(ChessMove::new(Square::E3, Square::E4, None)),
+        Err(MoveError::NotYourPiece)
+    );
+    assert_eq!(
+        board.try_apply_move(ChessMove::new(Square::E2, Square::E5, None)),
+        Err(MoveError::Illegal)
+    );
+    assert!(before.diff(&board).is_empty());
+
+    let e4 = ChessMove::new(Square::E2, Square::E4, None);
+    assert!(!board.is_awaiting_bonus());
+    assert_eq!(board.try_apply_move(e4), Ok(()));
+    assert!(board.is_awaiting_bonus());
+    assert_eq!(
+        board.try_apply_move(ChessMove::new(Square::E7, Square::E5, None)),
+        Err(MoveError::AwaitingBonus)
+    );
+
+    let mut finished = MyBoard::from_piece_list(Color::White, &[
+        (Square::E1, Piece::King, Color::White),
+        (Square::E5, Piece::Rook, Color::White),
+        (Square::E8, Piece::King, Color::Black),
+    ]);
+    finished.apply_move(ChessMove::new(Square::E5, Square::E8, None));
+    finished.apply_bonus(false);
+    assert_eq!(
+        finished.try_apply_move(ChessMove::new(Square::E8, Square::E7, None)),
+        Err(MoveError::GameOver)
+    );
+}
+
+#[test]
+fn test_undo_board() {
+    // The sequence is random but reproducible
+    let mut rng = SeededRng::new(12345);
+
+    let mut board = UndoBoard::new(MyBoard::initial_board(Color::White));
+    let mut snapshots = vec![*board.board()];
+    while board.board().get_status().is_in_progress() && snapshots.len() < 400 {
+        let moves: Vec<_> = board.board().all_moves().collect();
+        board.apply_move(moves[rng.below(moves.len())]);
+        snapshots.push(*board.board());
+        board.apply_bonus(rng.below(6) == 0);
+        snapshots.push(*board.board());
+    }
+    assert_eq!(board.len(), snapshots.len() - 1);
+
+    // Undo everything, checking the board is identical at each step
+    snapshots.pop();
+    while let Some(expected) = snapshots.pop() {
+        if board.board().awaiting_bonus {
+            board.undo_move();
+        } else {
+            board.undo_bonus();
+        }
+        assert_eq!(
+            board.board().get_zobrist_hash(),
+            expected.compute_zobrist_hash()
+        );
+        assert_eq!(format!("{:?}", board.board()), format!("{:?}", expected));
+    }
+    assert!(board.is_empty());
+}
+
+#[test]
+fn test_undo_special_moves() {
+    let castle = ChessMove::new(Square::E1, Square::G1, None);
+    let en_passant = ChessMove::new(Square::E5, Square::D6, None);
+    let promotion = ChessMove::new(Square::B7, Square::A8, Some(Piece::Queen));
+    let original = MyBoard::from_fen("r3k3/1P6/8/3pP3/8/8/8/4K2R w K d6 0 1").unwrap();
+
+    for mv in [castle, en_passant, promotion] {
+        let mut board = UndoBoard::new(original);
+        board.apply_move(mv);
+        board.apply_bonus(true);
+        board.undo_bonus();
+        board.undo_move();
+        assert_eq!(format!("{:?}", board.board()), format!("{:?}", original));
+    }
+}
+
+#[test]
+fn test_perft_stats() {
+    let initial = MyBoard::initial_board(Color::White);
+    assert_eq!(initial.perft(1), 40);
+    // Black has 20 replies to each of white's 20 moves, and white has 445
+    // second moves in total after a bonus
+    assert_eq!(initial.perft_stats(2), PerftStats {
+        nodes: 1690,
+        captures: 0,
+        castles: 0,
+        promotions: 0,
+        king_captures: 0,
+        bonus_branches: 865,
+    });
+
+    // Castling, en passant, and promotions with and without captures
+    let board = MyBoard::from_fen("r3k3/1P6/8/3pP3/8/8/8/4K2R w K d6 0 1").unwrap();
+    assert_eq!(
+        board.move_kind(ChessMove::new(Square::E5, Square::D6, None)),
+        MoveKind::Capture(Piece::Pawn)
+    );
+    assert_eq!(
+        board.move_kind(ChessMove::new(Square::B7, Square::A8, Some(Piece::Knight))),
+        MoveKind::Promotion {
+            captured: Some(Piece::Rook)
+        }
+    );
+    assert_eq!(
+        board.move_kind(ChessMove::new(Square::E1, Square::G1, None)),
+        MoveKind::Castle
+    );
+    assert_eq!(board.perft_stats(1), PerftStats {
+        nodes: 50,
+        captures: 5,
+        castles: 1,
+        promotions: 8,
+        king_captures: 0,
+        bonus_branches: 25,
+    });
+}
+
+#[test]
+fn test_move_to_san() {
+    let san = |fen: &str, src: Square, dest: Square, promotion: Option<Piece>| {
+        let board = MyBoard::from_fen(fen).unwrap();
+        let mv = ChessMove::new(src, dest, promotion);
+        assert!(board.moves_from(src).contains(&mv));
+        board.move_to_san(mv)
+    };
+
+    let initial = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    assert_eq!(san(initial, Square::G1, Square::F3, None), "Nf3");
+    assert_eq!(san(initial, Square::E2, Square::E4, None), "e4");
+
+    let captures = "4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1";
+    assert_eq!(san(captures, Square::E4, Square::D5, None), "exd5");
+    let en_passant = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+    assert_eq!(san(en_passant, Square::E5, Square::D6, None), "exd6");
+
+    let castling = "4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1";
+    assert_eq!(san(castling, Square::E1, Square::G1, None), "O-O");
+    assert_eq!(san(castling, Square::E1, Square::C1, None), "O-O-O");
+    // Both rooks can reach d1, so the file is given
+    let rooks = "4k3/8/8/8/8/8/4K3/R6R w - - 0 1";
+    assert_eq!(san(rooks, Square::A1, Square::D1, None), "Rad1");
+
+    let promotions = "r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1";
+    assert_eq!(
+        san(promotions, Square::B7, Square::B8, Some(Piece::Queen)),
+        "b8=Q"
+    );
+    assert_eq!(
+        san(promotions, Square::B7, Square::A8, Some(Piece::Knight)),
+        "bxa8=N"
+    );
+
+    // Knights on the same file are disambiguated by rank, and a piece that
+    // shares both a file and a rank with others needs the full square
+    let knights = "4k3/8/8/1N6/8/1N6/8/4K3 w - - 0 1";
+    assert_eq!(san(knights, Square::B5, Square::D4, None), "N5d4");
+    let queens = "4k3/8/8/8/Q2Q4/8/8/Q3K3 w - - 0 1";
+    assert_eq!(san(queens, Square::A4, Square::D1, None), "Qa4d1");
+
+    // Checks are only shown in the standard variant
+    let check = "4k3/8/8/8/8/8/8/R3K3 w - - 0 1";
+    assert_eq!(san(check, Square::A1, Square::A8, None), "Ra8");
+    let board = MyBoard::from_fen(check)
+        .unwrap()
+        .with_variant(Variant::Standard);
+    assert_eq!(
+        board.move_to_san(ChessMove::new(Square::A1, Square::A8, None)),
+        "Ra8+"
+    );
+    let board = MyBoard::from_fen("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1")
+        .unwrap()
+        .with_variant(Variant::Standard);
+    assert_eq!(
+        board.move_to_san(ChessMove::new(Square::A1, Square::A8, None)),
+        "Ra8#"
+    );
+}
+
+#[test]
+fn test_moves_by_piece() {
+    let board = MyBoard::from_fen("r3k3/1P6/8/3pP3/8/8/8/4K2R w K d6 0 1").unwrap();
+    let grouped: Vec<_> = board.moves_by_piece().collect();
+    let flattened: Vec<_> = grouped
+        .iter()
+        .flat_map(|(_, _, moves)| moves.iter().copied())
+        .collect();
+    assert_eq!(flattened, board.all_moves().collect::<Vec<_>>());
+
+    for (sq, piece, moves) in &grouped {
+        assert_eq!(board[*sq], Some((*piece, Color::White)));
+        assert!(moves.iter().all(|m| m.get_source() == *sq));
+    }
+    let pieces: Vec<_> = grouped.iter().map(|&(sq, piece, _)| (sq, piece)).collect();
+    assert_eq!(pieces, vec![
+        (Square::E1, Piece::King),
+        (Square::H1, Piece::Rook),
+        (Square::E5, Piece::Pawn),
+        (Square::B7, Piece::Pawn),
+    ]);
+
+    // Pieces which can't move aren't included
+    let initial = MyBoard::initial_board(Color::White);
+    assert_eq!(initial.moves_by_piece().count(), 10);
+}
+
+#[test]
+fn test_pieces() {
+    let board = MyBoard::from_fen("4k3/2n5/8/8/8/8/3P4/4K2R b K - 0 1").unwrap();
+    let mut pieces: Vec<_> = board.pieces().collect();
+    pieces.sort_by_key(|(sq, _, _)| sq.to_index());
+    assert_eq!(pieces, vec![
+        (Square::E1, Piece::King, Color::White),
+        (Square::H1, Piece::Rook, Color::White),
+        (Square::D2, Piece::Pawn, Color::White),
+        (Square::C7, Piece::Knight, Color::Black),
+        (Square::E8, Piece::King, Color::Black),
+    ]);
+    assert_eq!(MyBoard::initial_board(Color::White).pieces().count(), 32);
+}
+
+#[test]
+fn test_material() {
+    let board = MyBoard::from_fen("4k3/2n5/8/8/8/8/3P4/4K2R b K - 0 1").unwrap();
+    assert_eq!(board.material(Color::White), [1, 0, 0, 1, 0, 1]);
+    assert_eq!(board.material(Color::Black), [0, 1, 0, 0, 0, 1]);
+    assert_eq!(board.material_value(&[1, 3, 3, 5, 9, 0]), (6, 3));
+
+    let initial = MyBoard::initial_board(Color::White);
+    assert_eq!(initial.material(Color::Black), [8, 2, 2, 2, 1, 1]);
+    assert_eq!(initial.material_value(&[1, 3, 3, 5, 9, 0]), (39, 39));
+}
+
+#[test]
+fn test_incremental_material() {
+    let board = MyBoard::from_fen("4k3/2n5/8/8/8/8/3P4/4K2R b K - 0 1").unwrap();
+    assert_eq!(board.pawn_advancement(Color::White), 0);
+    assert_eq!(board.pawn_advancement(Color::Black), 0);
+
+    // Captures, promotions and en passant all go through `set_piece`
+    let mut board = MyBoard::from_fen("r3k3/1P4p1/8/3pP3/8/8/8/4K3 w q d6 0 1").unwrap();
+    assert_eq!(board.pawn_advancement(Color::White), 5 + 3);
+    assert_eq!(board.pawn_advancement(Color::Black), 2);
+    let mut undo = UndoBoard::new(board);
+    for (mv, pawn_advancement, queens) in [
+        ("e5d6", [5 + 4, 0], 0),
+        ("g7g5", [5 + 4, 2], 0),
+        ("b7a8q", [4, 2], 1),
+    ] {
+        let mv: ChessMove = mv.parse().unwrap();
+        board.apply_move(mv);
+        board.apply_bonus(false);
+        undo.apply_move(mv);
+        undo.apply_bonus(false);
+        assert_eq!(
+            [Color::White, Color::Black].map(|c| board.pawn_advancement(c)),
+            pawn_advancement
+        );
+        assert_eq!(
+            board.material(Color::White)[Piece::Queen.to_index()],
+            queens
+        );
+        assert_eq!(
+            (board.material, board.pawn_advancement, board.pawns),
+            board.compute_material()
+        );
+    }
+    assert_eq!(board.material(Color::Black), [1, 0, 0, 0, 0, 1]);
+
+    for _ in 0..3 {
+        undo.undo_bonus();
+        undo.undo_move();
+    }
+    let initial = MyBoard::from_fen("r3k3/1P4p1/8/3pP3/8/8/8/4K3 w q d6 0 1").unwrap();
+    assert_eq!(undo.board().material, initial.material);
+    assert_eq!(undo.board().pawn_advancement, initial.pawn_advancement);
+    assert_eq!(undo.board().pawns, initial.pawns);
+
+    // Boards that aren't reachable in a game can have many advanced pawns
+    let crowded: Vec<_> = ALL_SQUARES
+        .iter()
+        .map(|&sq| match sq.get_rank().to_index() {
+            0..=2 => (sq, Piece::Pawn, Color::Black),
+            _ => (sq, Piece::Pawn, Color::White),
+        })
+        .collect();
+    let crowded = MyBoard::from_piece_list(Color::White, &crowded);
+    assert_eq!(
+        crowded.pawn_advancement(Color::White),
+        8 * (2 + 3 + 4 + 5 + 6)
+    );
+    assert_eq!(crowded.pawn_advancement(Color::Black), 8 * (6 + 5 + 4));
+    assert_eq!(
+        (crowded.material, crowded.pawn_advancement, crowded.pawns),
+        crowded.compute_material()
+    );
+
+    let mut inverted = initial;
+    inverted.invert_ranks_and_colors();
+    assert_eq!(
+        inverted.material(Color::White),
+        initial.material(Color::Black)
+    );
+    assert_eq!(inverted.pawn_advancement(Color::Black), 5 + 3);
+    inverted.invert_files();
+    assert_eq!(
+        (inverted.material, inverted.pawn_advancement, inverted.pawns),
+        inverted.compute_material()
+    );
+}
+
+#[test]
+fn test_non_losing_moves() {
+    // The king has to leave the e-file without stepping onto the second rank,
+    // or the knight has to block the rook on e8
+    let board = MyBoard::from_fen("4r2k/8/8/8/8/2N5/r7/4K3 w - - 0 1").unwrap();
+    let mut moves: Vec<_> = board
+        .non_losing_moves()
+        .into_iter()
+        .map(|mv| mv.to_string())
+        .collect();
+    moves.sort();
+    assert_eq!(moves, vec!["c3e2", "c3e4", "e1d1", "e1f1"]);
+
+    // Capturing the king is safe even though the rook on h1 attacks the king
+    let board = MyBoard::from_fen("k7/8/8/8/8/8/8/R3K2r w - - 0 1").unwrap();
+    let mv = ChessMove::new(Square::A1, Square::A8, None);
+    assert_eq!(board.move_kind(mv), MoveKind::Capture(Piece::King));
+    assert!(board.non_losing_moves().contains(&mv));
+}
@@ -0,0 +1,152 @@
+use chess::{CastleRights, ChessMove, Color, File, Piece, Square};
+
+use super::{MyBoard, Status};
+
+/// The state overwritten by a move or a bonus, which is needed to undo it
+#[derive(Copy, Clone, Debug)]
+enum Undo {
+    Move {
+        mv: ChessMove,
+        piece: (Piece, Color),
+        /// The captured piece and the square it was on, which is different
+        /// to the destination when capturing en passant
+        captured: Option<(Square, (Piece, Color))>,
+        castle_rights: [CastleRights; 2],
+        en_passant: Option<Square>,
+        dead_moves: u8,
+        status: Status,
+        zobrist_hash: u64,
+    },
+    Bonus {
+        is_bonus: bool,
+        status: Status,
+        zobrist_hash: u64,
+    },
+}
+
+/// A board that keeps a stack of the moves and bonuses applied to it, so
+/// that they can be undone. `MyBoard` is copied rather than undone during
+/// searches, so it doesn't keep the stack itself.
+#[derive(Clone, Debug)]
+pub struct UndoBoard {
+    board: MyBoard,
+    stack: Vec<Undo>,
+}
+
+impl UndoBoard {
+    pub fn new(board: MyBoard) -> UndoBoard {
+        UndoBoard {
+            board,
+            stack: Vec::new(),
+        }
+    }
+
+    pub fn board(&self) -> &MyBoard { &self.board }
+
+    /// The number of moves and bonuses that can be undone
+    pub fn len(&self) -> usize { self.stack.len() }
+
+    pub fn is_empty(&self) -> bool { self.stack.is_empty() }
+
+    /// Applies a legal move, panicking if it is illegal
+    pub fn apply_move(&mut self, m: ChessMove) {
+        assert!(self.board.moves_from(m.get_source()).contains(&m));
+        self.apply_move_unchecked(m);
+    }
+
+    pub fn apply_move_unchecked(&mut self, m: ChessMove) {
+        let board = &self.board;
+        let piece = board[m.get_source()].expect("No piece at source");
+        let captured_square = board.en_passant_victim(m).unwrap_or(m.get_dest());
+        self.stack.push(Undo::Move {
+            mv: m,
+            piece,
+            captured: board[captured_square].map(|captured| (captured_square, captured)),
+            castle_rights: board.castle_rights,
+            en_passant: board.en_passant,
+            dead_moves: board.dead_moves,
+            status: board.status,
+            zobrist_hash: board.zobrist_hash,
+        });
+        self.board.apply_move_unchecked(m);
+    }
+
+    pub fn apply_bonus(&mut self, is_bonus: bool) {
+        self.stack.push(Undo::Bonus {
+            is_bonus,
+            status: self.board.status,
+            zobrist_hash: self.board.zobrist_hash,
+        });
+        self.board.apply_bonus(is_bonus);
+    }
+
+    /// Undoes the last move, panicking if the last thing applied wasn't a
+    /// move (i.e. its bonus has to be undone first)
+    pub fn undo_move(&mut self) {
+        let Some(Undo::Move {
+            mv,
+            piece,
+            captured,
+            castle_rights,
+            en_passant,
+            dead_moves,
+            status,
+            zobrist_hash,
+        }) = self.stack.pop()
+        else {
+            panic!("Tried to undo a move when the last change wasn't a move");
+        };
+        let board = &mut self.board;
+
+        board.set_piece(mv.get_dest(), None);
+        board.set_piece(mv.get_source(), Some(piece));
+        if let Some((sq, captured)) = captured {
+            board.set_piece(sq, Some(captured));
+        }
+
+        // Put the rook back if the move castled
+        let (p, c) = piece;
+        if p == Piece::King && mv.get_source().get_file() == File::E {
+            let rank = mv.get_source().get_rank();
+            let rook_squares = match mv.get_dest().get_file() {
+                File::G => Some((File::H, File::F)),
+                File::C => Some((File::A, File::D)),
+                _ => None,
+            };
+            if let Some((src, dst)) = rook_squares {
+                board.set_piece(Square::make_square(rank, dst), None);
+                board.set_piece(Square::make_square(rank, src), Some((Piece::Rook, c)));
+            }
+        }
+
+        board.castle_rights = castle_rights;
+        board.en_passant = en_passant;
+        board.dead_moves = dead_moves;
+        board.status = status;
+        board.awaiting_bonus = false;
+        board.side_to_move = c;
+        board.zobrist_hash = zobrist_hash;
+        board.debug_check_material();
+    }
+
+    /// Undoes the last bonus, panicking if the last thing applied wasn't a
+    /// bonus
+    pub fn undo_bonus(&mut self) {
+        let Some(Undo::Bonus {
+            is_bonus,
+            status,
+            zobrist_hash,
+        }) = self.stack.pop()
+        else {
+            panic!("Tried to undo a bonus when the last change wasn't a bonus");
+        };
+        let board = &mut self.board;
+
+        if is_bonus {
+            board.side_to_move = !board.side_to_move;
+        }
+        board.status = status;
+        board.awaiting_bonus = true;
+        board.zobrist_hash = zobrist_hash;
+    }
+}
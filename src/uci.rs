@@ -0,0 +1,74 @@
+use chess::Color;
+
+use crate::Score;
+
+/// The number of pawns which corresponds to a change of 1 in the logit of the
+/// score. This matches the `scale_down` used with the standard weights for
+/// `FeatureEval`, where a pawn has a weight of 1.
+const PAWN_SCALE: f32 = 15.0;
+
+const MIN_PROBABILITY: f32 = 1e-6;
+
+/// Converts a score (the expected value for white) into an advantage for
+/// white in pawns. This is the inverse of the sigmoid that `FeatureEval` uses,
+/// so a score of 0.5 is 0 pawns.
+pub fn score_to_pawns(score: Score) -> f32 {
+    // Clamp so that decisive scores give a large but finite advantage
+    let p = score
+        .to_num::<f32>()
+        .clamp(MIN_PROBABILITY, 1.0 - MIN_PROBABILITY);
+    PAWN_SCALE * (p / (1.0 - p)).ln()
+}
+
+/// Formats a score the way engine front-ends expect, from the perspective of
+/// `side_to_move`.
+///
+/// If `plies_to_result` is known (because the search found a forced king
+/// capture), then the score is given as `"mate N"`, where `N` is the number
+/// of moves the side to move needs to make, negated if it is the side to move
+/// whose king is captured. Otherwise it is given as `"cp N"` in centipawns.
+pub fn score_to_uci(score: Score, side_to_move: Color, plies_to_result: Option<u8>) -> String {
+    let pawns = match side_to_move {
+        Color::White => score_to_pawns(score),
+        Color::Black => -score_to_pawns(score),
+    };
+    match plies_to_result {
+        Some(plies) if pawns > 0.0 => format!("mate {}", (plies as i32 + 1) / 2),
+        Some(plies) if pawns < 0.0 => format!("mate -{}", (plies as i32 + 1) / 2),
+        _ => format!("cp {}", (pawns * 100.0).round() as i32),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ONE;
+
+    #[test]
+    fn test_score_to_uci() {
+        assert_eq!(
+            score_to_uci(Score::from_num(0.5), Color::White, None),
+            "cp 0"
+        );
+        assert_eq!(
+            score_to_uci(Score::from_num(0.5), Color::Black, None),
+            "cp 0"
+        );
+
+        assert_eq!(score_to_uci(ONE, Color::White, Some(3)), "mate 2");
+        assert_eq!(score_to_uci(ONE, Color::Black, Some(2)), "mate -1");
+        assert_eq!(score_to_uci(Score::ZERO, Color::Black, Some(1)), "mate 1");
+
+        let better = score_to_uci(Score::from_num(0.6), Color::White, None);
+        let worse = score_to_uci(Score::from_num(0.6), Color::Black, None);
+        assert!(better.starts_with("cp ") && !better.contains('-'));
+        assert!(worse.starts_with("cp -"));
+    }
+
+    #[test]
+    fn test_score_to_pawns() {
+        assert_eq!(score_to_pawns(Score::from_num(0.5)), 0.0);
+        assert!(score_to_pawns(ONE).is_finite());
+        assert!(score_to_pawns(Score::from_num(0.7)) > score_to_pawns(Score::from_num(0.6)));
+    }
+}
@@ -8,3 +8,33 @@ pub fn set_panic_hook() {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
 }
+
+/// A linear congruential generator, which is enough for reproducible
+/// randomness in benchmarks and tests without depending on a random number
+/// crate
+pub(crate) struct SeededRng(u64);
+
+impl SeededRng {
+    pub(crate) fn new(seed: u64) -> SeededRng { SeededRng(seed) }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.0
+    }
+
+    /// A uniformly random number in `[0, 1)`
+    pub(crate) fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// A random number in `[0, n)`
+    #[cfg(test)]
+    pub(crate) fn below(&mut self, n: usize) -> usize { (self.next_u64() >> 33) as usize % n }
+
+    /// 32 random bits
+    #[cfg(test)]
+    pub(crate) fn next_u32(&mut self) -> u32 { (self.next_u64() >> 32) as u32 }
+}
@@ -1,8 +1,16 @@
-use chess::{CastleRights, Color, Piece, Square, NUM_COLORS, NUM_PIECES, NUM_SQUARES};
+use chess::{
+    CastleRights, Color, File, Piece, Square, NUM_COLORS, NUM_FILES, NUM_PIECES, NUM_SQUARES,
+};
 
 /// Type that contains functions which return the zobrist hash values.
 /// This code, and the generated lookup tables, are based on the code in the
 /// chess crate.
+///
+/// The tables are compile-time constants rather than being generated at
+/// startup, so a given position always has the same hash. This keeps the
+/// position table, and hence any search with a node budget, deterministic
+/// across runs. To try other tables, set `RANDOM_CHESS_ZOBRIST_SEED` to a
+/// number when building, and they are generated from that seed instead.
 pub struct Zobrist;
 
 impl Zobrist {
@@ -29,14 +37,101 @@ impl Zobrist {
         }
     }
 
+    /// When there is an en passant square, the value for its file should be
+    /// XORed into the hash.
+    #[inline]
+    pub fn en_passant(file: File) -> u64 {
+        unsafe { *ZOBRIST_EN_PASSANT.get_unchecked(file.to_index()) }
+    }
+
     /// When the color is black, this should be XORed into the hash
     #[inline]
     pub fn color() -> u64 { SIDE_TO_MOVE }
 }
 
-const SIDE_TO_MOVE: u64 = 4527170993230009529;
+/// The seed given by `RANDOM_CHESS_ZOBRIST_SEED` when building, if any
+const SEED: Option<u64> = match option_env!("RANDOM_CHESS_ZOBRIST_SEED") {
+    Some(seed) => match u64::from_str_radix(seed, 10) {
+        Ok(seed) => Some(seed),
+        Err(_) => panic!("RANDOM_CHESS_ZOBRIST_SEED should be a decimal number"),
+    },
+    None => None,
+};
+
+/// The values for every part of the hash
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct ZobristTables {
+    side_to_move: u64,
+    pieces: [[[u64; NUM_SQUARES]; NUM_PIECES]; NUM_COLORS],
+    castles: [[u64; 4]; NUM_COLORS],
+    en_passant: [u64; NUM_FILES],
+}
+
+impl ZobristTables {
+    /// Generates tables from `seed` with SplitMix64, so that the same seed
+    /// always gives the same tables
+    const fn from_seed(seed: u64) -> ZobristTables {
+        const fn next(state: &mut u64) -> u64 {
+            *state = state.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = *state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        }
+
+        let mut state = seed;
+        let mut tables = ZobristTables {
+            side_to_move: next(&mut state),
+            pieces: [[[0; NUM_SQUARES]; NUM_PIECES]; NUM_COLORS],
+            castles: [[0; 4]; NUM_COLORS],
+            en_passant: [0; NUM_FILES],
+        };
+        // `for` loops can't be used in a const fn
+        let mut color = 0;
+        while color < NUM_COLORS {
+            let mut piece = 0;
+            while piece < NUM_PIECES {
+                let mut square = 0;
+                while square < NUM_SQUARES {
+                    tables.pieces[color][piece][square] = next(&mut state);
+                    square += 1;
+                }
+                piece += 1;
+            }
+            let mut rights = 0;
+            while rights < 4 {
+                tables.castles[color][rights] = next(&mut state);
+                rights += 1;
+            }
+            color += 1;
+        }
+        let mut file = 0;
+        while file < NUM_FILES {
+            tables.en_passant[file] = next(&mut state);
+            file += 1;
+        }
+        tables
+    }
+}
+
+const TABLES: ZobristTables = match SEED {
+    Some(seed) => ZobristTables::from_seed(seed),
+    None => ZobristTables {
+        side_to_move: DEFAULT_SIDE_TO_MOVE,
+        pieces: DEFAULT_PIECES,
+        castles: DEFAULT_CASTLES,
+        en_passant: DEFAULT_EN_PASSANT,
+    },
+};
 
-const ZOBRIST_PIECES: [[[u64; NUM_SQUARES]; NUM_PIECES]; NUM_COLORS] = [
+const SIDE_TO_MOVE: u64 = TABLES.side_to_move;
+const ZOBRIST_PIECES: [[[u64; NUM_SQUARES]; NUM_PIECES]; NUM_COLORS] = TABLES.pieces;
+const ZOBRIST_CASTLES: [[u64; 4]; NUM_COLORS] = TABLES.castles;
+const ZOBRIST_EN_PASSANT: [u64; NUM_FILES] = TABLES.en_passant;
+
+const DEFAULT_SIDE_TO_MOVE: u64 = 4527170993230009529;
+
+const DEFAULT_PIECES: [[[u64; NUM_SQUARES]; NUM_PIECES]; NUM_COLORS] = [
     [
         [
             8085185598151760447,
@@ -835,7 +930,7 @@ const ZOBRIST_PIECES: [[[u64; NUM_SQUARES]; NUM_PIECES]; NUM_COLORS] = [
     ],
 ];
 
-const ZOBRIST_CASTLES: [[u64; 4]; NUM_COLORS] = [
+const DEFAULT_CASTLES: [[u64; 4]; NUM_COLORS] = [
     [
         4730299224526584862,
         16029894244043714123,
@@ -849,3 +944,61 @@ const ZOBRIST_CASTLES: [[u64; 4]; NUM_COLORS] = [
         9785248589528863946,
     ],
 ];
+
+const DEFAULT_EN_PASSANT: [u64; NUM_FILES] = [
+    6730635371581257076,
+    16903631293147338836,
+    5921151383813144196,
+    17564223980509430146,
+    819848797230836317,
+    12365141973395916035,
+    14603208732804265170,
+    8702628267714145358,
+];
+
+#[cfg(test)]
+mod tests {
+    use chess::Color;
+
+    use super::*;
+    use crate::my_board::MyBoard;
+
+    /// The tables are constants, so hashes (and therefore the position table
+    /// indices and node-limited searches) are the same in every process. If
+    /// this changes, searches won't be reproducible across versions either.
+    #[test]
+    fn test_hashes_are_fixed() {
+        if SEED.is_some() {
+            // The hashes below are for the default tables
+            return;
+        }
+        assert_eq!(
+            MyBoard::initial_board(Color::White).get_zobrist_hash(),
+            9023329949471135578
+        );
+        assert_eq!(
+            MyBoard::initial_board(Color::Black).get_zobrist_hash(),
+            9023329949471135578 ^ SIDE_TO_MOVE
+        );
+    }
+
+    #[test]
+    fn test_tables_from_seed() {
+        // Generating the tables again, as another process would, gives the
+        // same values
+        assert_eq!(ZobristTables::from_seed(721), ZobristTables::from_seed(721));
+        assert_ne!(ZobristTables::from_seed(721), ZobristTables::from_seed(722));
+
+        let tables = ZobristTables::from_seed(721);
+        let mut values: Vec<_> = tables.pieces.iter().flatten().flatten().copied().collect();
+        values.extend(tables.castles.iter().flatten());
+        values.extend(tables.en_passant);
+        values.push(tables.side_to_move);
+        values.sort_unstable();
+        values.dedup();
+        assert_eq!(
+            values.len(),
+            NUM_COLORS * (NUM_PIECES * NUM_SQUARES + 4) + NUM_FILES + 1
+        );
+    }
+}